@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS `Rankings_Users_Failures` (
+  `User_ID` int(11) NOT NULL,
+  `Error_Kind` varchar(20) DEFAULT NULL,
+  `Count_Failed` int(11) DEFAULT NULL,
+  `Last_Failed` timestamp NULL DEFAULT NULL,
+  PRIMARY KEY (`User_ID`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci;
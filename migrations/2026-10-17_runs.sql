@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS `Rankings_Script_Runs` (
+  `ID` int(11) NOT NULL AUTO_INCREMENT,
+  `History_ID` int(11) NOT NULL,
+  `Type` varchar(30) DEFAULT NULL,
+  `Started_At` timestamp NULL DEFAULT NULL,
+  `Finished_At` timestamp NULL DEFAULT NULL,
+  `Is_Cancelled` tinyint(1) NOT NULL DEFAULT 0,
+  `Count_Requested` int(11) DEFAULT NULL,
+  `Count_Failed` int(11) DEFAULT NULL,
+  `Count_Restricted` int(11) DEFAULT NULL,
+  `Count_New_Medals` int(11) DEFAULT NULL,
+  `Count_New_Badges` int(11) DEFAULT NULL,
+  `Count_Badge_Owners_Added` int(11) DEFAULT NULL,
+  `Count_Badge_Owners_Updated` int(11) DEFAULT NULL,
+  `Count_Badge_Owners_Removed` int(11) DEFAULT NULL,
+  `Count_Rows_Written` int(11) DEFAULT NULL,
+  `Durations` text DEFAULT NULL,
+  `Rows_Written` text DEFAULT NULL,
+  `Errors` text DEFAULT NULL,
+  PRIMARY KEY (`ID`),
+  KEY `History_ID` (`History_ID`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci;
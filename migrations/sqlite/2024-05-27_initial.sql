@@ -0,0 +1,85 @@
+CREATE TABLE IF NOT EXISTS `Badges_Data` (
+  `Name` TEXT NOT NULL,
+  `Image_URL` TEXT DEFAULT NULL,
+  PRIMARY KEY (`Name`)
+);
+
+
+CREATE TABLE IF NOT EXISTS `Badge_Name` (
+  `Name` TEXT NOT NULL,
+  `User_ID` INTEGER NOT NULL,
+  `Description` TEXT DEFAULT NULL,
+  `Date_Awarded` TEXT DEFAULT NULL,
+  PRIMARY KEY (`Name`,`User_ID`)
+);
+
+
+CREATE TABLE IF NOT EXISTS `Medals_Data` (
+  `Medal_ID` INTEGER NOT NULL,
+  `Name` TEXT DEFAULT NULL,
+  `Link` TEXT DEFAULT NULL,
+  `Description` TEXT DEFAULT NULL,
+  `Gamemode` TEXT DEFAULT NULL,
+  `Grouping` TEXT DEFAULT NULL,
+  `Instructions` TEXT DEFAULT NULL,
+  `Ordering` INTEGER DEFAULT NULL,
+  `Frequency` REAL DEFAULT NULL,
+  `Count_Achieved_By` INTEGER DEFAULT NULL,
+  PRIMARY KEY (`Medal_ID`)
+);
+
+
+CREATE TABLE IF NOT EXISTS `Rankings_Script_History` (
+  `ID` INTEGER NOT NULL,
+  `Type` TEXT DEFAULT NULL,
+  `Time` TEXT DEFAULT NULL,
+  `Count_Current` INTEGER DEFAULT NULL,
+  `Count_Total` INTEGER DEFAULT NULL,
+  `Elapsed_Seconds` INTEGER DEFAULT NULL,
+  `Elapsed_Last_Update` TEXT DEFAULT NULL,
+  PRIMARY KEY (`ID`)
+);
+
+
+CREATE TABLE IF NOT EXISTS `Rankings_Users` (
+  `ID` INTEGER NOT NULL,
+  `Accuracy_Catch` REAL DEFAULT NULL,
+  `Accuracy_Mania` REAL DEFAULT NULL,
+  `Accuracy_Standard` REAL DEFAULT NULL,
+  `Accuracy_Stdev` REAL DEFAULT NULL,
+  `Accuracy_Taiko` REAL DEFAULT NULL,
+  `Count_Badges` INTEGER DEFAULT NULL,
+  `Count_Maps_Loved` INTEGER DEFAULT NULL,
+  `Count_Maps_Ranked` INTEGER DEFAULT NULL,
+  `Count_Medals` INTEGER DEFAULT NULL,
+  `Count_Replays_Watched` INTEGER DEFAULT NULL,
+  `Count_Subscribers` INTEGER DEFAULT NULL,
+  `Country_Code` TEXT DEFAULT NULL,
+  `Is_Restricted` INTEGER DEFAULT NULL,
+  `Level_Catch` INTEGER DEFAULT NULL,
+  `Level_Mania` INTEGER DEFAULT NULL,
+  `Level_Standard` INTEGER DEFAULT NULL,
+  `Level_Stdev` INTEGER DEFAULT NULL,
+  `Level_Taiko` INTEGER DEFAULT NULL,
+  `Name` TEXT DEFAULT NULL,
+  `PP_Catch` REAL DEFAULT NULL,
+  `PP_Mania` REAL DEFAULT NULL,
+  `PP_Standard` REAL DEFAULT NULL,
+  `PP_Stdev` REAL DEFAULT NULL,
+  `PP_Taiko` REAL DEFAULT NULL,
+  `PP_Total` REAL DEFAULT NULL,
+  `Rank_Global_Catch` INTEGER DEFAULT NULL,
+  `Rank_Global_Mania` INTEGER DEFAULT NULL,
+  `Rank_Global_Standard` INTEGER DEFAULT NULL,
+  `Rank_Global_Taiko` INTEGER DEFAULT NULL,
+  `Rarest_Medal_Achieved` TEXT DEFAULT NULL,
+  `Rarest_Medal_ID` INTEGER DEFAULT NULL,
+  PRIMARY KEY (`ID`)
+);
+
+CREATE TABLE IF NOT EXISTS `System_Users` (
+  `User_ID` INTEGER NOT NULL,
+  `Name` TEXT DEFAULT NULL,
+  `Joined_Date` TEXT DEFAULT NULL,
+  PRIMARY KEY (`User_ID`)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS `Rankings_Users_Failures` (
+  `User_ID` INTEGER NOT NULL,
+  `Error_Kind` TEXT DEFAULT NULL,
+  `Count_Failed` INTEGER DEFAULT NULL,
+  `Last_Failed` TEXT DEFAULT NULL,
+  PRIMARY KEY (`User_ID`)
+);
@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS `Rankings_Script_Runs` (
+  `ID` INTEGER NOT NULL,
+  `History_ID` INTEGER NOT NULL,
+  `Type` TEXT DEFAULT NULL,
+  `Started_At` TEXT DEFAULT NULL,
+  `Finished_At` TEXT DEFAULT NULL,
+  `Is_Cancelled` INTEGER NOT NULL DEFAULT 0,
+  `Count_Requested` INTEGER DEFAULT NULL,
+  `Count_Failed` INTEGER DEFAULT NULL,
+  `Count_Restricted` INTEGER DEFAULT NULL,
+  `Count_New_Medals` INTEGER DEFAULT NULL,
+  `Count_New_Badges` INTEGER DEFAULT NULL,
+  `Count_Badge_Owners_Added` INTEGER DEFAULT NULL,
+  `Count_Badge_Owners_Updated` INTEGER DEFAULT NULL,
+  `Count_Badge_Owners_Removed` INTEGER DEFAULT NULL,
+  `Count_Rows_Written` INTEGER DEFAULT NULL,
+  `Durations` TEXT DEFAULT NULL,
+  `Rows_Written` TEXT DEFAULT NULL,
+  `Errors` TEXT DEFAULT NULL,
+  PRIMARY KEY (`ID`)
+);
+
+CREATE INDEX IF NOT EXISTS `Rankings_Script_Runs_History_ID`
+  ON `Rankings_Script_Runs` (`History_ID`);
@@ -0,0 +1,129 @@
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use eyre::{Context as _, Report, Result};
+use rosu_v2::prelude::GameMode;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::task::Task;
+
+/// Directory of recorded osu!api responses and scraped pages so that a run
+/// can be reproduced without network access.
+pub struct Cassette {
+    dir: PathBuf,
+    mode: CassetteMode,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum CassetteMode {
+    /// Responses are requested as usual and saved into the cassette.
+    Record,
+    /// Responses are served from the cassette instead of being requested.
+    Replay,
+}
+
+/// A single recorded response.
+pub enum CassetteEntry {
+    /// Statistics of a user as part of a batch request; `None` if the user
+    /// was missing in the batch.
+    Stats(u32),
+    /// Extended data of a user; `None` if the user was not found.
+    User(u32),
+    /// Leaderboard page of a mode.
+    Rankings(GameMode, u32),
+    /// Webpage that is scraped for medals.
+    Webpage,
+    /// User ids that were gathered for a task before extra users are added.
+    UserIds(Task),
+}
+
+impl CassetteEntry {
+    fn path(&self) -> PathBuf {
+        match self {
+            Self::Stats(user_id) => format!("stats/{user_id}.json").into(),
+            Self::User(user_id) => format!("users/{user_id}.json").into(),
+            Self::Rankings(mode, page) => format!("rankings/{mode:?}-{page}.json").into(),
+            Self::Webpage => "webpage.html".into(),
+            Self::UserIds(task) => format!("user-ids/{}.json", task.bits()).into(),
+        }
+    }
+}
+
+impl Cassette {
+    pub fn new(dir: PathBuf, mode: CassetteMode) -> Result<Self> {
+        match mode {
+            CassetteMode::Record => {
+                for subdir in ["stats", "users", "rankings", "user-ids"] {
+                    let path = dir.join(subdir);
+
+                    fs::create_dir_all(&path).with_context(|| {
+                        format!("failed to create cassette directory `{}`", path.display())
+                    })?;
+                }
+            }
+            CassetteMode::Replay => ensure!(
+                dir.is_dir(),
+                "cassette directory `{}` does not exist",
+                dir.display()
+            ),
+        }
+
+        Ok(Self { dir, mode })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.mode == CassetteMode::Replay
+    }
+
+    /// Save the value as entry if the cassette is recording.
+    pub fn record<T: Serialize>(&self, entry: &CassetteEntry, value: &T) {
+        if self.mode != CassetteMode::Record {
+            return;
+        }
+
+        match serde_json::to_vec(value) {
+            Ok(bytes) => self.record_bytes(entry, &bytes),
+            Err(err) => warn!(?err, "Failed to serialize cassette entry"),
+        }
+    }
+
+    /// Save the bytes as entry if the cassette is recording.
+    pub fn record_bytes(&self, entry: &CassetteEntry, bytes: &[u8]) {
+        if self.mode != CassetteMode::Record {
+            return;
+        }
+
+        let path = self.dir.join(entry.path());
+
+        if let Err(err) = fs::write(&path, bytes) {
+            warn!(?err, "Failed to write cassette entry `{}`", path.display());
+        }
+    }
+
+    /// Load an entry; fails if it was not recorded or is invalid.
+    pub fn replay<T: DeserializeOwned>(&self, entry: &CassetteEntry) -> Result<T> {
+        let bytes = self.replay_bytes(entry)?;
+
+        serde_json::from_slice(&bytes).with_context(|| {
+            format!(
+                "failed to deserialize cassette entry `{}`",
+                entry.path().display()
+            )
+        })
+    }
+
+    /// Load the bytes of an entry; fails if it was not recorded.
+    pub fn replay_bytes(&self, entry: &CassetteEntry) -> Result<Vec<u8>> {
+        let path = self.dir.join(entry.path());
+
+        match fs::read(&path) {
+            Ok(bytes) => Ok(bytes),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                bail!("missing cassette entry `{}`", path.display())
+            }
+            Err(err) => Err(Report::new(err).wrap_err(format!(
+                "failed to read cassette entry `{}`",
+                path.display()
+            ))),
+        }
+    }
+}
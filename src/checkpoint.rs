@@ -0,0 +1,361 @@
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use eyre::{Context as _, ContextCompat as _, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::{Badges, MedalCounts, OsuUser},
+    task::Task,
+    util::IntHasher,
+};
+
+const DIRECTORY: &str = "./checkpoints";
+
+/// Intermediate state of a run that is written to disk regularly while users
+/// are being requested so that the run can be resumed after an interruption.
+///
+/// Runs of different lanes may start in the same second so checkpoints are
+/// told apart by their id and their task.
+#[derive(Deserialize)]
+pub struct Checkpoint {
+    /// Unix timestamp of the run's start, same as the progress id.
+    pub id: i64,
+    #[serde(with = "crate::task::bits")]
+    pub task: Task,
+    /// Amount of user ids that were considered when the run started.
+    pub total: usize,
+    /// User ids that have not been requested successfully yet.
+    pub remaining: HashSet<u32, IntHasher>,
+    /// The file of users that were requested but not stored yet.
+    #[serde(rename = "users")]
+    pub users_log: UsersLog,
+    /// Users of the [`UsersLog`]; loaded alongside the checkpoint.
+    #[serde(skip)]
+    pub users: Vec<OsuUser>,
+    pub badges: Badges,
+    #[serde(default)]
+    pub medal_counts: Option<MedalCounts>,
+}
+
+/// Borrowed counterpart of [`Checkpoint`] so that the current state does
+/// not need to be cloned to be saved.
+#[derive(Serialize)]
+pub struct CheckpointRef<'a> {
+    pub id: i64,
+    #[serde(with = "crate::task::bits")]
+    pub task: Task,
+    pub total: usize,
+    pub remaining: &'a HashSet<u32, IntHasher>,
+    pub users: &'a UsersLog,
+    pub badges: &'a Badges,
+    pub medal_counts: Option<&'a MedalCounts>,
+}
+
+/// Users of a checkpoint are kept in a separate file that is only appended
+/// to so that each checkpoint writes just the users that were added since
+/// the previous one.
+///
+/// Once the held users were stored, the next checkpoint starts a new file.
+#[derive(Default, Deserialize, Serialize)]
+pub struct UsersLog {
+    /// Increased whenever a new file is started
+    generation: u32,
+    /// Amount of bytes of the file that belong to the checkpoint; anything
+    /// after that was written by an interrupted save.
+    len: u64,
+    /// Amount of held users that are already in the file
+    #[serde(skip)]
+    written: usize,
+    /// Whether the held users that are in the file were stored
+    #[serde(skip)]
+    stale: bool,
+}
+
+impl Checkpoint {
+    /// Amount of handled users after which the checkpoint is saved again.
+    pub const INTERVAL: usize = 5000;
+
+    /// Load the most recent checkpoint, only considering those of the given
+    /// task if there is one.
+    pub fn load_latest(task: Option<Task>) -> Result<Self> {
+        let entries = fs::read_dir(DIRECTORY)
+            .with_context(|| format!("failed to read checkpoint directory `{DIRECTORY}`"))?;
+
+        let mut latest = None;
+
+        for entry in entries {
+            let path = entry
+                .context("failed to read checkpoint directory entry")?
+                .path();
+
+            let key = path
+                .extension()
+                .filter(|ext| *ext == "json")
+                .and(path.file_stem())
+                .and_then(|stem| stem.to_str())
+                .and_then(parse_stem);
+
+            let Some((id, bits)) = key else {
+                continue;
+            };
+
+            if task.is_some_and(|task| task.bits() != bits) {
+                continue;
+            }
+
+            if latest.as_ref().is_none_or(|(latest_id, _)| *latest_id < id) {
+                latest = Some((id, path));
+            }
+        }
+
+        let (_, path) = latest.with_context(|| match task {
+            Some(task) => format!("no checkpoint of task `{task}` found in `{DIRECTORY}`"),
+            None => format!("no checkpoint found in `{DIRECTORY}`"),
+        })?;
+
+        Self::load(&path)
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path)
+            .with_context(|| format!("failed to read checkpoint `{}`", path.display()))?;
+
+        let mut checkpoint: Self = serde_json::from_slice(&bytes)
+            .with_context(|| format!("failed to deserialize checkpoint `{}`", path.display()))?;
+
+        checkpoint.users = checkpoint.users_log.load(checkpoint.id, checkpoint.task)?;
+
+        Ok(checkpoint)
+    }
+
+    /// Remove the checkpoint files of the given id and task if they exist.
+    pub fn remove(id: i64, task: Task) {
+        match fs::remove_file(path(id, task)) {
+            Ok(_) => debug!("Removed checkpoint {id} of task `{task}`"),
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => warn!(?err, "Failed to remove checkpoint {id} of task `{task}`"),
+        }
+
+        let Ok(entries) = fs::read_dir(DIRECTORY) else {
+            return;
+        };
+
+        let prefix = format!("{}.users-", stem(id, task));
+
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                remove_users_file(&entry.path());
+            }
+        }
+    }
+}
+
+impl CheckpointRef<'_> {
+    /// Write the checkpoint to disk, replacing a previous one of the same id.
+    ///
+    /// The users must have been appended to the [`UsersLog`] beforehand.
+    pub fn save(&self) -> Result<()> {
+        let bytes = serde_json::to_vec(self).context("failed to serialize checkpoint")?;
+
+        // Write into a temporary file first so that an interruption while
+        // writing does not corrupt the previous checkpoint
+        let path = path(self.id, self.task);
+        let tmp_path = path.with_extension("json.tmp");
+
+        fs::write(&tmp_path, bytes)
+            .with_context(|| format!("failed to write checkpoint `{}`", tmp_path.display()))?;
+
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("failed to rename checkpoint `{}`", tmp_path.display()))
+    }
+}
+
+impl UsersLog {
+    /// Append the held users that are not in the file yet.
+    ///
+    /// Returns the path of the previous file if a new one was started; it
+    /// should be removed once the checkpoint that refers to the new one was
+    /// saved.
+    pub fn append(&mut self, id: i64, task: Task, users: &[OsuUser]) -> Result<Option<PathBuf>> {
+        fs::create_dir_all(DIRECTORY)
+            .with_context(|| format!("failed to create checkpoint directory `{DIRECTORY}`"))?;
+
+        let prev = if self.stale {
+            let prev = users_path(id, task, self.generation);
+            self.generation += 1;
+            self.len = 0;
+            self.stale = false;
+
+            Some(prev)
+        } else {
+            None
+        };
+
+        let path = users_path(id, task, self.generation);
+
+        let mut bytes = Vec::new();
+
+        for user in &users[self.written..] {
+            serde_json::to_writer(&mut bytes, user).context("failed to serialize user")?;
+            bytes.push(b'\n');
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("failed to open checkpoint users `{}`", path.display()))?;
+
+        file.set_len(self.len)
+            .and_then(|_| file.seek(SeekFrom::End(0)))
+            .and_then(|_| file.write_all(&bytes))
+            .with_context(|| format!("failed to write checkpoint users `{}`", path.display()))?;
+
+        self.len += bytes.len() as u64;
+        self.written = users.len();
+
+        Ok(prev)
+    }
+
+    /// All held users were taken so the file's users are no longer needed.
+    pub fn clear(&mut self) {
+        self.stale |= self.len > 0;
+        self.written = 0;
+    }
+
+    fn load(&mut self, id: i64, task: Task) -> Result<Vec<OsuUser>> {
+        if self.len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let path = users_path(id, task, self.generation);
+
+        let bytes = fs::read(&path)
+            .with_context(|| format!("failed to read checkpoint users `{}`", path.display()))?;
+
+        let bytes = bytes
+            .get(..self.len as usize)
+            .with_context(|| format!("checkpoint users `{}` are incomplete", path.display()))?;
+
+        let users = bytes
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<Result<Vec<OsuUser>, _>>()
+            .with_context(|| {
+                format!(
+                    "failed to deserialize checkpoint users `{}`",
+                    path.display()
+                )
+            })?;
+
+        self.written = users.len();
+
+        Ok(users)
+    }
+}
+
+/// Remove a file of users that no checkpoint refers to anymore.
+pub fn remove_users_file(path: &Path) {
+    match fs::remove_file(path) {
+        Ok(_) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => warn!(
+            ?err,
+            "Failed to remove checkpoint users `{}`",
+            path.display()
+        ),
+    }
+}
+
+/// File name of a checkpoint without extension
+fn stem(id: i64, task: Task) -> String {
+    format!("{id}-{}", task.bits())
+}
+
+/// The id and task bits of a checkpoint's file name without extension
+fn parse_stem(stem: &str) -> Option<(i64, u8)> {
+    let (id, bits) = stem.rsplit_once('-')?;
+
+    Some((id.parse().ok()?, bits.parse().ok()?))
+}
+
+fn path(id: i64, task: Task) -> PathBuf {
+    Path::new(DIRECTORY).join(format!("{}.json", stem(id, task)))
+}
+
+fn users_path(id: i64, task: Task, generation: u32) -> PathBuf {
+    Path::new(DIRECTORY).join(format!("{}.users-{generation}.ndjson", stem(id, task)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restricted(user_ids: &[u32]) -> Vec<OsuUser> {
+        user_ids
+            .iter()
+            .map(|&user_id| OsuUser::Restricted { user_id })
+            .collect()
+    }
+
+    fn user_ids(users: &[OsuUser]) -> Vec<u32> {
+        users
+            .iter()
+            .map(|user| match user {
+                OsuUser::Available(user) => user.user_id,
+                OsuUser::Restricted { user_id } => *user_id,
+            })
+            .collect()
+    }
+
+    /// The log as it is restored from a saved checkpoint
+    fn restore(log: &UsersLog) -> UsersLog {
+        serde_json::from_slice(&serde_json::to_vec(log).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn users_log_round_trip() {
+        // Negative ids don't collide with checkpoints of actual runs
+        let (id, task) = (-1, Task::RANKING);
+        let mut log = UsersLog::default();
+        let mut users = restricted(&[1, 2]);
+
+        assert!(log.append(id, task, &users).unwrap().is_none());
+        let saved = restore(&log);
+
+        // Written before an interruption but never referred to by a checkpoint
+        users.extend(restricted(&[3]));
+        log.append(id, task, &users).unwrap();
+
+        // Resuming truncates the file to the saved length before appending
+        let mut log = saved;
+        let mut users = log.load(id, task).unwrap();
+        assert_eq!(user_ids(&users), [1, 2]);
+
+        users.extend(restricted(&[4]));
+        log.append(id, task, &users).unwrap();
+        assert_eq!(user_ids(&restore(&log).load(id, task).unwrap()), [1, 2, 4]);
+
+        // Once the users were stored, the next append starts a new file
+        log.clear();
+        let users = restricted(&[5]);
+        let prev = log.append(id, task, &users).unwrap();
+
+        assert!(prev.as_deref() == Some(users_path(id, task, 0).as_path()));
+        assert_eq!(user_ids(&restore(&log).load(id, task).unwrap()), [5]);
+
+        remove_users_file(&prev.unwrap());
+        Checkpoint::remove(id, task);
+        assert!(!users_path(id, task, 1).exists());
+
+        // Only removed if there are no other checkpoints
+        let _ = fs::remove_dir(DIRECTORY);
+    }
+}
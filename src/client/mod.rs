@@ -26,16 +26,19 @@ static MY_USER_AGENT: HeaderValue = HeaderValue::from_static(concat!(
     env!("CARGO_PKG_VERSION")
 ));
 static FORM_URLENCODED: HeaderValue = HeaderValue::from_static("application/x-www-form-urlencoded");
+static APPLICATION_JSON: HeaderValue = HeaderValue::from_static("application/json");
 
 type Body = Full<Bytes>;
 
 /// Client that makes all requests that do not go to the osu!api itself
 pub struct Client {
     client: HyperClient<HttpsConnector<HttpConnector>, Body>,
+    /// Whether webhook notifications should only be logged
+    dry_run: bool,
 }
 
 impl Client {
-    pub fn new() -> Self {
+    pub fn new(dry_run: bool) -> Self {
         let crypto_provider = rustls::crypto::ring::default_provider();
 
         let https = HttpsConnectorBuilder::new()
@@ -50,7 +53,7 @@ impl Client {
             .http2_only(true)
             .build(https);
 
-        Self { client }
+        Self { client, dry_run }
     }
 
     /// Requests peppy's webpage and returns its bytes
@@ -94,7 +97,7 @@ impl Client {
         self.notify_webhook(content).await
     }
 
-    async fn send_get_request(&self, url: Uri) -> Result<Bytes> {
+    pub async fn send_get_request(&self, url: Uri) -> Result<Bytes> {
         trace!("Sending GET request to url {url}");
 
         let req = Request::get(&url)
@@ -123,7 +126,45 @@ impl Client {
             .map(Collected::to_bytes)
     }
 
+    /// Send a POST request with the json body and return the response bytes
+    pub async fn send_json_post_request(&self, url: Uri, body: Vec<u8>) -> Result<Bytes> {
+        trace!("Sending POST request to url {url}");
+
+        let req = Request::post(&url)
+            .header(USER_AGENT, &MY_USER_AGENT)
+            .header(CONTENT_TYPE, &APPLICATION_JSON)
+            .header(CONTENT_LENGTH, body.len())
+            .body(Full::from(body))
+            .context("failed to build POST request")?;
+
+        let response = self
+            .client
+            .request(req)
+            .await
+            .context("failed to fetch POST response")?;
+
+        let status = response.status();
+
+        ensure!(
+            status.is_success(),
+            "failed with status code {status} when posting to url {url}"
+        );
+
+        response
+            .into_body()
+            .collect()
+            .await
+            .context("failed to collect response bytes")
+            .map(Collected::to_bytes)
+    }
+
     async fn notify_webhook(&self, content: String) -> Result<()> {
+        if self.dry_run {
+            info!("Dry run: skipping webhook notification `{content}`");
+
+            return Ok(());
+        }
+
         trace!("Sending POST request for webhook");
 
         #[derive(Serialize)]
@@ -137,7 +178,7 @@ impl Client {
             .context("failed to urlencode webhook notification")?
             .into_bytes();
 
-        let req = Request::post(&Config::get().webhook_url)
+        let req = Request::post(&Config::get().reloadable().webhook_url)
             .header(USER_AGENT, &MY_USER_AGENT)
             .header(CONTENT_TYPE, &FORM_URLENCODED)
             .header(CONTENT_LENGTH, body.len())
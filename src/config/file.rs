@@ -0,0 +1,270 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use eyre::{Context as _, Result};
+use serde::Deserialize;
+use toml::{Table, Value};
+
+/// The config file and profile given through the arguments
+static FILE: OnceLock<(PathBuf, Option<String>)> = OnceLock::new();
+
+/// Config that is read from a TOML file.
+///
+/// Its values are put into the env variables of the same meaning unless
+/// they're already set so env variables take precedence.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    osu: Osu,
+    database: Database,
+    webhook: Webhook,
+    metrics: Metrics,
+    requests: Requests,
+    leaderboards: Leaderboards,
+    logging: Logging,
+    schedule: Option<ScheduleValue>,
+    extra_users: Option<Vec<u32>>,
+    /// Tasks by name
+    presets: Option<BTreeMap<String, String>>,
+    /// Tables of the same structure that override the values above
+    profiles: Table,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Osu {
+    client_id: Option<u64>,
+    client_secret: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Database {
+    url: Option<String>,
+    batch_size: Option<usize>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Webhook {
+    url: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Metrics {
+    addr: Option<SocketAddr>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Requests {
+    concurrency: Option<usize>,
+    ratelimit: Option<u32>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Leaderboards {
+    ranking_pages: Option<usize>,
+    rarity_pages: Option<usize>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Logging {
+    filter: Option<String>,
+    directory: Option<String>,
+}
+
+/// Either the same string as `SCHEDULE`, a list of entries, or a list of
+/// lanes which are lists of entries
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScheduleValue {
+    Text(String),
+    Entries(Vec<EntryValue>),
+    Lanes(Vec<Vec<EntryValue>>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EntryValue {
+    Text(String),
+    Table { task: String, at: Option<String> },
+}
+
+impl ConfigFile {
+    fn load(path: &Path, profile: Option<&str>) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file `{}`", path.display()))?;
+
+        let mut table: Table = content
+            .parse()
+            .with_context(|| format!("failed to parse config file `{}`", path.display()))?;
+
+        if let Some(profile) = profile {
+            let profiles = table.get("profiles").and_then(Value::as_table);
+
+            let overrides = profiles
+                .and_then(|profiles| profiles.get(profile))
+                .and_then(Value::as_table)
+                .cloned();
+
+            let Some(overrides) = overrides else {
+                let names: Vec<_> = profiles
+                    .into_iter()
+                    .flat_map(Table::keys)
+                    .map(String::as_str)
+                    .collect();
+
+                bail!(
+                    "unknown profile `{profile}` in config file `{}`; available profiles: [{}]",
+                    path.display(),
+                    names.join(", ")
+                );
+            };
+
+            merge(&mut table, overrides);
+        }
+
+        Table::try_into(table).with_context(|| format!("invalid config file `{}`", path.display()))
+    }
+
+    /// Env variables and their values
+    fn into_vars(self) -> Vec<(&'static str, String)> {
+        fn push<T: ToString>(
+            vars: &mut Vec<(&'static str, String)>,
+            name: &'static str,
+            value: Option<T>,
+        ) {
+            if let Some(value) = value {
+                vars.push((name, value.to_string()));
+            }
+        }
+
+        let mut vars = Vec::new();
+
+        push(&mut vars, "OSU_CLIENT_ID", self.osu.client_id);
+        push(&mut vars, "OSU_CLIENT_SECRET", self.osu.client_secret);
+        push(&mut vars, "DATABASE_URL", self.database.url);
+        push(&mut vars, "DATABASE_BATCH_SIZE", self.database.batch_size);
+        push(&mut vars, "WEBHOOK_URL", self.webhook.url);
+        push(&mut vars, "METRICS_ADDR", self.metrics.addr);
+        push(&mut vars, "CONCURRENCY", self.requests.concurrency);
+        push(&mut vars, "RATELIMIT", self.requests.ratelimit);
+        push(&mut vars, "RANKING_PAGES", self.leaderboards.ranking_pages);
+        push(&mut vars, "RARITY_PAGES", self.leaderboards.rarity_pages);
+        push(&mut vars, "RUST_LOG", self.logging.filter);
+        push(&mut vars, "LOG_DIRECTORY", self.logging.directory);
+        push(
+            &mut vars,
+            "SCHEDULE",
+            self.schedule.map(ScheduleValue::into_string),
+        );
+
+        let extra_users = self.extra_users.map(|users| {
+            users
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        });
+
+        push(&mut vars, "EXTRA_USERS", extra_users);
+
+        let presets = self.presets.map(|presets| {
+            presets
+                .iter()
+                .map(|(name, task)| format!("{name} = {task}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        });
+
+        push(&mut vars, "PRESETS", presets);
+
+        vars
+    }
+}
+
+impl ScheduleValue {
+    fn into_string(self) -> String {
+        fn join(entries: Vec<EntryValue>) -> String {
+            entries
+                .into_iter()
+                .map(EntryValue::into_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+
+        match self {
+            Self::Text(text) => text,
+            Self::Entries(entries) => join(entries),
+            Self::Lanes(lanes) => lanes.into_iter().map(join).collect::<Vec<_>>().join("; "),
+        }
+    }
+}
+
+impl EntryValue {
+    fn into_string(self) -> String {
+        match self {
+            Self::Text(text)
+            | Self::Table {
+                task: text,
+                at: None,
+            } => text,
+            Self::Table { task, at: Some(at) } => format!("{task} @ {at}"),
+        }
+    }
+}
+
+/// Recursively override the values of `base` with those of `overrides`.
+fn merge(base: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(value)) => merge(base, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Load the config file and put its values into env variables that are not
+/// set yet.
+pub fn init(path: PathBuf, profile: Option<String>) -> Result<()> {
+    let file = FILE.get_or_init(|| (path, profile));
+
+    apply(file)
+}
+
+/// Load the config file again if one was given and return its values as
+/// env variables without setting them.
+pub fn read_vars() -> Result<Vec<(&'static str, String)>> {
+    match FILE.get() {
+        Some((path, profile)) => Ok(ConfigFile::load(path, profile.as_deref())?.into_vars()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The path of the config file if one was given
+pub fn path() -> Option<&'static Path> {
+    FILE.get().map(|(path, _)| path.as_path())
+}
+
+fn apply((path, profile): &(PathBuf, Option<String>)) -> Result<()> {
+    let vars = ConfigFile::load(path, profile.as_deref())?.into_vars();
+
+    for (name, value) in vars {
+        if env::var_os(name).is_none() {
+            env::set_var(name, value);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,314 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use eyre::{Context as _, Result};
+use hyper::Uri;
+
+pub mod file;
+
+use crate::{
+    schedule::Schedule,
+    task::Task,
+    util::{Args, IntHasher},
+};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Env variables of the process before the `.env` file and the config file
+/// were applied so that they can be read again with the same precedence.
+static PROCESS_ENV: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+pub struct Config {
+    pub tokens: Tokens,
+    pub database_url: Box<str>,
+    /// Maximum amount of rows per multi-row database statement
+    pub database_batch_size: usize,
+    /// Address to serve Prometheus metrics on
+    pub metrics_addr: Option<SocketAddr>,
+    pub requests: Requests,
+    pub leaderboard_pages: LeaderboardPages,
+    /// The `.env` file that the config was loaded from, if any
+    pub env_path: Option<PathBuf>,
+    reloadable: RwLock<Arc<Reloadable>>,
+}
+
+/// Part of the config that can be reloaded while the script is running.
+pub struct Reloadable {
+    pub schedule: Schedule,
+    /// User ids of `EXTRA_USERS`
+    pub extra_users: HashSet<u32, IntHasher>,
+    pub webhook_url: Uri,
+}
+
+pub struct Tokens {
+    pub osu_client_id: u64,
+    pub osu_client_secret: Box<str>,
+}
+
+pub struct Requests {
+    /// Maximum amount of concurrent user requests
+    pub concurrency: usize,
+    /// Maximum amount of osu!api requests per second
+    pub ratelimit: u32,
+}
+
+/// Amount of leaderboard pages per mode that are requested
+pub struct LeaderboardPages {
+    /// For tasks with `ranking` but without `rarity`
+    pub ranking: usize,
+    /// For tasks with `rarity`
+    pub rarity: usize,
+}
+
+impl Config {
+    pub fn get() -> &'static Self {
+        CONFIG.get().expect("CONFIG not yet initialized")
+    }
+
+    /// The most recently applied reloadable part of the config
+    pub fn reloadable(&self) -> Arc<Reloadable> {
+        Arc::clone(&self.reloadable.read().unwrap())
+    }
+
+    /// Swap in a reloaded config.
+    pub fn apply(&self, reloadable: Arc<Reloadable>) {
+        *self.reloadable.write().unwrap() = reloadable;
+    }
+}
+
+impl Reloadable {
+    /// Read the reloadable part of the config from the env variables.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(&env_vars())
+    }
+
+    /// Read the reloadable part of the config from the given variables.
+    pub fn from_vars(vars: &HashMap<String, String>) -> Result<Self> {
+        let extra_users = vars.get("EXTRA_USERS").cloned().unwrap_or_else(|| {
+            warn!(
+                "missing env variable `EXTRA_USERS`; \
+                will consider this as no extra users"
+            );
+
+            String::new()
+        });
+
+        let extra_users = extra_users
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .with_context(|| {
+                format!(
+                    "failed to parse env variable `EXTRA_USERS=\"{extra_users}\"`; \
+                    expected a list of comma-separated user ids"
+                )
+            })?;
+
+        Ok(Self {
+            schedule: parse_schedule(vars.get("SCHEDULE").cloned())?,
+            extra_users,
+            webhook_url: parse_var("WEBHOOK_URL", vars.get("WEBHOOK_URL").cloned())?,
+        })
+    }
+}
+
+/// Remember the env variables of the process; must be called before the
+/// `.env` file is loaded.
+pub fn capture_process_env() {
+    PROCESS_ENV.get_or_init(env_vars);
+}
+
+/// Read the `.env` file and the config file again and merge them over the
+/// env variables of the process with the same precedence as on startup.
+///
+/// Env variables themselves are left untouched.
+pub fn read_vars() -> Result<HashMap<String, String>> {
+    let mut vars = PROCESS_ENV.get().cloned().unwrap_or_else(env_vars);
+
+    if let Some(ref path) = Config::get().env_path {
+        let iter = dotenvy::from_path_iter(path)
+            .with_context(|| format!("failed to read env file `{}`", path.display()))?;
+
+        for item in iter {
+            let (name, value) =
+                item.with_context(|| format!("failed to parse env file `{}`", path.display()))?;
+
+            vars.entry(name).or_insert(value);
+        }
+    }
+
+    for (name, value) in file::read_vars()? {
+        vars.entry(name.to_owned()).or_insert(value);
+    }
+
+    Ok(vars)
+}
+
+/// Whether `PRESETS` differs from the one that presets were defined with
+/// on startup; presets can't be changed without a restart.
+pub fn presets_changed(vars: &HashMap<String, String>) -> bool {
+    let current = env::var("PRESETS").unwrap_or_default();
+
+    vars.get("PRESETS").map_or("", String::as_str) != current
+}
+
+/// All env variables that are valid unicode
+fn env_vars() -> HashMap<String, String> {
+    env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect()
+}
+
+pub fn init(args: &Args, env_path: Option<PathBuf>) -> Result<()> {
+    let config = Config {
+        tokens: Tokens {
+            osu_client_id: env_var("OSU_CLIENT_ID")?,
+            osu_client_secret: env_var("OSU_CLIENT_SECRET")?,
+        },
+        database_url: env_var("DATABASE_URL")?,
+        database_batch_size: env_var_or("DATABASE_BATCH_SIZE", 1000)?,
+        metrics_addr: env_var_opt("METRICS_ADDR")?,
+        requests: Requests {
+            concurrency: match args.concurrency {
+                Some(concurrency) => concurrency,
+                None => env_var_or("CONCURRENCY", 4)?,
+            },
+            ratelimit: match args.ratelimit {
+                Some(ratelimit) => ratelimit,
+                None => env_var_or("RATELIMIT", 10)?,
+            },
+        },
+        leaderboard_pages: LeaderboardPages {
+            ranking: env_var_or("RANKING_PAGES", 5)?,
+            rarity: env_var_or("RARITY_PAGES", 200)?,
+        },
+        env_path,
+        reloadable: RwLock::new(Arc::new(Reloadable::from_env()?)),
+    };
+
+    CONFIG
+        .set(config)
+        .map_err(|_| eyre!("`Config::init` has already been called"))
+}
+
+/// Initialize the config with defaults instead of env variables so that
+/// tests can create a [`Context`](crate::context::Context).
+#[cfg(test)]
+pub fn init_test() {
+    CONFIG.get_or_init(|| Config {
+        tokens: Tokens {
+            osu_client_id: 0,
+            osu_client_secret: Box::default(),
+        },
+        database_url: Box::from(crate::database::InMemoryStorage::URL),
+        database_batch_size: 1000,
+        metrics_addr: None,
+        requests: Requests {
+            concurrency: 4,
+            ratelimit: 10,
+        },
+        leaderboard_pages: LeaderboardPages {
+            ranking: 5,
+            rarity: 200,
+        },
+        env_path: None,
+        reloadable: RwLock::new(Arc::new(Reloadable {
+            schedule: "default".parse().unwrap(),
+            extra_users: HashSet::default(),
+            webhook_url: Uri::default(),
+        })),
+    });
+}
+
+/// Define the presets of the `PRESETS` env variable so that tasks can
+/// refer to them.
+pub fn init_presets() -> Result<()> {
+    let presets = env::var("PRESETS").unwrap_or_default();
+
+    Task::init_presets(&presets)
+        .with_context(|| format!("failed to parse env variable `PRESETS=\"{presets}\"`"))
+}
+
+/// Only the database url for commands that don't require the full config
+pub fn database_url() -> Result<Box<str>> {
+    env_var("DATABASE_URL")
+}
+
+/// Only the schedule for commands that don't require the full config
+pub fn schedule() -> Result<Schedule> {
+    parse_schedule(env::var("SCHEDULE").ok())
+}
+
+fn parse_schedule(value: Option<String>) -> Result<Schedule> {
+    value
+        .ok_or_else(|| eyre!("missing env variable `SCHEDULE`"))?
+        .parse()
+        .context("failed to parse schedule; must be a comma-separated list of tasks")
+}
+
+trait EnvKind: Sized {
+    const EXPECTED: &'static str;
+
+    fn from_str(s: String) -> Result<Self, String>;
+}
+
+macro_rules! env_kind {
+    ($($ty:ty: $arg:ident => $impl:block,)*) => {
+        $(
+            impl EnvKind for $ty {
+                const EXPECTED: &'static str = stringify!($ty);
+
+                fn from_str($arg: String) -> Result<Self, String> {
+                    $impl
+                }
+            }
+        )*
+    };
+}
+
+env_kind! {
+    Box<str>: s => { Ok(s.into_boxed_str()) },
+    u32: s => { s.parse().map_err(|_| s) },
+    u64: s => { s.parse().map_err(|_| s) },
+    usize: s => { s.parse().map_err(|_| s) },
+    Uri: s => { s.parse().map_err(|_| s) },
+    SocketAddr: s => { s.parse().map_err(|_| s) },
+}
+
+fn env_var<T: EnvKind>(name: &'static str) -> Result<T> {
+    parse_var(name, env::var(name).ok())
+}
+
+fn parse_var<T: EnvKind>(name: &'static str, value: Option<String>) -> Result<T> {
+    let value = value.ok_or_else(|| eyre!("missing env variable `{name}`"))?;
+
+    T::from_str(value).map_err(|value| {
+        eyre!(
+            "failed to parse env variable `{name}={value}`; expected {expected}",
+            expected = T::EXPECTED
+        )
+    })
+}
+
+/// Same as [`env_var`] but returns `None` if the variable is not set.
+fn env_var_opt<T: EnvKind>(name: &'static str) -> Result<Option<T>> {
+    match env::var(name) {
+        Ok(_) => env_var(name).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Same as [`env_var`] but returns `default` if the variable is not set.
+fn env_var_or<T: EnvKind>(name: &'static str, default: T) -> Result<T> {
+    match env::var(name) {
+        Ok(_) => env_var(name),
+        Err(_) => Ok(default),
+    }
+}
@@ -0,0 +1,283 @@
+use std::{collections::HashSet, mem};
+
+use eyre::Report;
+
+use crate::{
+    checkpoint::{self, Checkpoint, CheckpointRef, UsersLog},
+    metrics::METRICS,
+    model::{
+        Badges, FailureKind, MedalCounts, MedalRarities, OsuUser, Progress, RankingsIter,
+        RequestError, RequestResult, UserFailures,
+    },
+    util::IntHasher,
+};
+
+/// What happens with users once their badges and medals were processed.
+pub(super) enum UserRetention {
+    /// Users are not needed any further.
+    Discard,
+    /// Users are kept until all users were requested.
+    Keep,
+    /// Users are kept in bounded chunks which are turned into rankings
+    /// based on the given rarities while other users are still requested.
+    Stream(MedalRarities),
+}
+
+/// Users and badges that were gathered while requesting users.
+pub(super) struct GatheredUsers {
+    /// Users that are currently held based on the [`UserRetention`].
+    pub users: Vec<OsuUser>,
+    pub badges: Badges,
+    /// Present if medal rarities should be calculated.
+    pub medal_counts: Option<MedalCounts>,
+    pub retention: UserRetention,
+    /// User ids that have not been requested successfully yet.
+    pub remaining: HashSet<u32, IntHasher>,
+    /// Users whose last request failed.
+    pub failed: UserFailures,
+    /// Users that are stored as failing but were requested successfully.
+    pub resolved: Vec<u32>,
+    /// Users that are stored as failing.
+    prev_failed: HashSet<u32, IntHasher>,
+    /// Amount of restricted users since the last (re)start.
+    pub restricted: usize,
+    /// Amount of user ids that were considered initially.
+    total: usize,
+    /// Amount of handled results since the last (re)start.
+    handled: usize,
+    /// Amount of handled results when the last checkpoint was saved.
+    last_checkpoint: usize,
+    /// The checkpoint's file of held users
+    users_log: UsersLog,
+    check_badges: bool,
+    badge_name_buf: String,
+}
+
+impl GatheredUsers {
+    /// Amount of held users after which they're turned into rankings when streaming.
+    const RANKINGS_CHUNK_SIZE: usize = 1000;
+
+    pub fn new(
+        user_ids: HashSet<u32, IntHasher>,
+        retention: UserRetention,
+        count_medals: bool,
+        check_badges: bool,
+    ) -> Self {
+        let badge_capacity = if check_badges { 10_000 } else { 0 };
+
+        let users_capacity = match retention {
+            UserRetention::Discard => 0,
+            UserRetention::Keep => user_ids.len(),
+            UserRetention::Stream(_) => Self::RANKINGS_CHUNK_SIZE,
+        };
+
+        Self {
+            users: Vec::with_capacity(users_capacity),
+            badges: Badges::with_capacity(badge_capacity),
+            medal_counts: count_medals.then(MedalCounts::default),
+            retention,
+            total: user_ids.len(),
+            remaining: user_ids,
+            failed: UserFailures::default(),
+            resolved: Vec::new(),
+            prev_failed: HashSet::default(),
+            restricted: 0,
+            handled: 0,
+            last_checkpoint: 0,
+            users_log: UsersLog::default(),
+            check_badges,
+            badge_name_buf: String::new(),
+        }
+    }
+
+    pub fn resume(
+        checkpoint: Checkpoint,
+        retention: UserRetention,
+        count_medals: bool,
+        check_badges: bool,
+    ) -> Self {
+        let Checkpoint {
+            total,
+            remaining,
+            mut users,
+            mut users_log,
+            badges,
+            medal_counts,
+            ..
+        } = checkpoint;
+
+        match retention {
+            UserRetention::Discard => {
+                users.clear();
+                users_log.clear();
+            }
+            UserRetention::Keep => users.reserve(remaining.len()),
+            UserRetention::Stream(_) => {}
+        }
+
+        Self {
+            users,
+            badges,
+            medal_counts: count_medals.then(|| medal_counts.unwrap_or_default()),
+            retention,
+            remaining,
+            failed: UserFailures::default(),
+            resolved: Vec::new(),
+            prev_failed: HashSet::default(),
+            restricted: 0,
+            total,
+            handled: 0,
+            last_checkpoint: 0,
+            users_log,
+            check_badges,
+            badge_name_buf: String::new(),
+        }
+    }
+
+    pub fn handle_result(&mut self, user_id: u32, res: RequestResult<OsuUser>) {
+        self.handled += 1;
+
+        let mut user = match res {
+            Ok(user) => user,
+            Err(err) => {
+                METRICS.users_failed(1);
+                self.failed.insert(user_id, FailureKind::from(&err));
+                error!(err = ?Report::new(err), "Failed to request user {user_id} from osu!api");
+
+                return;
+            }
+        };
+
+        self.remaining.remove(&user_id);
+        self.failed.remove(&user_id);
+
+        if self.prev_failed.remove(&user_id) {
+            self.resolved.push(user_id);
+        }
+
+        match user {
+            OsuUser::Available(_) => METRICS.user_succeeded(),
+            OsuUser::Restricted { .. } => {
+                self.restricted += 1;
+                METRICS.user_restricted();
+            }
+        }
+
+        // Process badges if required
+        if self.check_badges {
+            if let OsuUser::Available(ref mut user) = user {
+                let mut valid = true;
+
+                for badge in user.badges.iter_mut() {
+                    valid &= self
+                        .badges
+                        .push(user.user_id, badge, &mut self.badge_name_buf);
+                }
+
+                if valid {
+                    self.badges.checked_users.insert(user.user_id);
+                }
+            }
+        }
+
+        // Count medals if required
+        if let Some(ref mut medal_counts) = self.medal_counts {
+            medal_counts.add(&user);
+        }
+
+        if !matches!(self.retention, UserRetention::Discard) {
+            self.users.push(user);
+        }
+    }
+
+    /// Keep track of which of the users that are stored as failing are
+    /// requested successfully.
+    pub fn track_resolved(&mut self, prev_failed: HashSet<u32, IntHasher>) {
+        self.prev_failed = prev_failed;
+    }
+
+    /// Consider all users of a batch as failed.
+    pub fn handle_batch_error(&mut self, user_ids: &[u32], err: RequestError) {
+        self.handled += user_ids.len();
+        METRICS.users_failed(user_ids.len());
+
+        let kind = FailureKind::from(&err);
+        self.failed
+            .extend(user_ids.iter().map(|&user_id| (user_id, kind)));
+
+        error!(
+            err = ?Report::new(err),
+            "Failed to request batch of {} users from osu!api",
+            user_ids.len()
+        );
+    }
+
+    /// Take the ids of all users whose last request failed.
+    pub fn take_failed(&mut self) -> Vec<u32> {
+        mem::take(&mut self.failed).into_keys().collect()
+    }
+
+    /// When streaming, turn the held users into rankings once the chunk is
+    /// full or, if `force` is set, as soon as there are any.
+    pub fn take_rankings(&mut self, force: bool) -> Option<RankingsIter> {
+        let UserRetention::Stream(ref rarities) = self.retention else {
+            return None;
+        };
+
+        if self.users.is_empty() || (!force && self.users.len() < Self::RANKINGS_CHUNK_SIZE) {
+            return None;
+        }
+
+        let users = mem::replace(
+            &mut self.users,
+            Vec::with_capacity(Self::RANKINGS_CHUNK_SIZE),
+        );
+
+        self.users_log.clear();
+
+        Some(RankingsIter::new(users, rarities.clone()))
+    }
+
+    /// Amount of handled results since the last (re)start.
+    pub fn handled(&self) -> usize {
+        self.handled
+    }
+
+    /// Whether enough users were handled since the last checkpoint.
+    ///
+    /// Failed batches count all of their users at once so the amount
+    /// may skip past a multiple of the interval.
+    pub fn checkpoint_due(&self) -> bool {
+        self.handled - self.last_checkpoint >= Checkpoint::INTERVAL
+    }
+
+    pub fn save_checkpoint(&mut self, progress: &Progress) {
+        let id = progress.start.unix_timestamp();
+        let task = progress.task;
+        self.last_checkpoint = self.handled;
+
+        let prev_users = match self.users_log.append(id, task, &self.users) {
+            Ok(prev_users) => prev_users,
+            Err(err) => return warn!(?err, "Failed to save checkpoint"),
+        };
+
+        let checkpoint = CheckpointRef {
+            id,
+            task,
+            total: self.total,
+            remaining: &self.remaining,
+            users: &self.users_log,
+            badges: &self.badges,
+            medal_counts: self.medal_counts.as_ref(),
+        };
+
+        match checkpoint.save() {
+            Ok(_) => debug!("Saved checkpoint {id} of task `{task}`"),
+            Err(err) => return warn!(?err, "Failed to save checkpoint"),
+        }
+
+        if let Some(path) = prev_users {
+            checkpoint::remove_users_file(&path);
+        }
+    }
+}
@@ -1,93 +1,349 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     time::{Duration, Instant},
 };
 
 use eyre::{Context as _, Report, Result};
-use futures_util::{stream::FuturesUnordered, StreamExt as _};
-use rosu_v2::Osu;
+use futures_util::{future::Either, stream::FuturesUnordered, StreamExt as _};
+use rosu_v2::{prelude::UserStatisticsModes, Osu};
+use time::OffsetDateTime;
 use tokio::{
+    sync::{Mutex as AsyncMutex, MutexGuard},
     task::JoinHandle,
-    time::{interval, sleep},
+    time::{interval_at, sleep, Instant as TokioInstant},
 };
 
 use crate::{
+    cassette::{Cassette, CassetteEntry, CassetteMode},
+    checkpoint::Checkpoint,
     client::Client,
-    config::Config,
-    database::Database,
-    model::{Badges, MedalRarities, OsuUser, Progress, RankingsIter, ScrapedMedal},
+    config::{Config, Reloadable},
+    database::{MeteredStorage, Storage, StoreResult},
+    metrics::{Phase, METRICS},
+    model::{
+        BadgeSummary, Badges, BadgesDiff, FailureKind, Finish, MedalCounts, MedalRarities, OsuUser,
+        Progress, RankingsIter, RequestResult, ScrapedMedal, SharedReport, UserFailures,
+    },
+    schedule::{Lane, Schedule, ScheduleState},
     task::Task,
-    util::{Eta, IntHasher, TimeEstimate},
+    util::{format_datetime, AdaptiveConcurrency, Eta, IntHasher, Reload, Shutdown, TimeEstimate},
     Args,
 };
 
+use self::{
+    gathered::{GatheredUsers, UserRetention},
+    plan::{Output, Plan},
+};
+
+mod gathered;
 mod medal;
+mod plan;
 mod user;
 mod webhook;
 
+#[cfg(test)]
+mod tests;
+
 pub struct Context {
     client: Client,
-    osu: Osu,
-    mysql: Database,
+    /// `None` when replaying a cassette
+    osu: Option<Osu>,
+    cassette: Option<Cassette>,
+    storage: Arc<dyn Storage>,
+    shutdown: Shutdown,
+    concurrency: Mutex<AdaptiveConcurrency>,
+    /// Held while requesting users so that lanes don't request them at
+    /// the same time
+    users_lock: AsyncMutex<()>,
+    /// Held while storing medals and rarities so that lanes don't
+    /// deadlock on `Medals_Data`
+    medals_lock: AsyncMutex<()>,
+    /// Tasks that are currently running in any lane
+    running: Mutex<Vec<RunningTask>>,
+    next_run_id: AtomicU64,
+    /// Whether checkpoints are saved and removed; a dry run must not leave
+    /// checkpoints behind that a real run would resume from
+    checkpoints: bool,
+    /// Whether the schedule state is saved; neither a dry run nor a replay
+    /// must move the schedule of real runs along
+    persist_schedule: bool,
 }
 
 impl Context {
-    pub async fn new() -> Result<Self> {
+    pub async fn new(shutdown: Shutdown, args: &Args, storage: Arc<dyn Storage>) -> Result<Self> {
         let config = Config::get();
 
-        let osu = Osu::builder()
-            .client_id(config.tokens.osu_client_id)
-            .client_secret(&*config.tokens.osu_client_secret)
-            .ratelimit(10)
-            .build()
+        let cassette = match (&args.record, &args.replay) {
+            (Some(dir), _) => Some(Cassette::new(dir.to_owned(), CassetteMode::Record)?),
+            (None, Some(dir)) => Some(Cassette::new(dir.to_owned(), CassetteMode::Replay)?),
+            (None, None) => None,
+        };
+
+        // Replaying does not require access to the osu!api
+        let osu = if cassette.as_ref().is_some_and(Cassette::is_replay) {
+            None
+        } else {
+            let osu = Osu::builder()
+                .client_id(config.tokens.osu_client_id)
+                .client_secret(&*config.tokens.osu_client_secret)
+                .ratelimit(config.requests.ratelimit)
+                .build()
+                .await
+                .context("failed to create osu client")?;
+
+            Some(osu)
+        };
+
+        let pending = storage
+            .pending_migrations()
             .await
-            .context("failed to create osu client")?;
+            .context("failed to check database schema version")?;
+
+        if !pending.is_empty() {
+            bail!(
+                "database schema is outdated; missing migration(s) {}; \
+                apply them with the `migrate` subcommand",
+                pending.join(", ")
+            );
+        }
 
-        let client = Client::new();
+        let client = Client::new(args.dry_run.is_some());
+
+        Ok(Self {
+            client,
+            osu,
+            cassette,
+            storage: Arc::new(MeteredStorage::new(storage)),
+            shutdown,
+            concurrency: Mutex::new(AdaptiveConcurrency::new(config.requests.concurrency)),
+            users_lock: AsyncMutex::new(()),
+            medals_lock: AsyncMutex::new(()),
+            running: Mutex::new(Vec::new()),
+            next_run_id: AtomicU64::new(0),
+            checkpoints: args.dry_run.is_none(),
+            persist_schedule: args.dry_run.is_none() && args.replay.is_none(),
+        })
+    }
 
-        let mysql = Database::new(&config.database_url).await?;
+    /// Ensure that the task, or each task of the schedule if there is none,
+    /// can be run with the storage.
+    pub fn check_tasks(&self, task: Option<Task>) -> Result<()> {
+        let Some(task) = task else {
+            let reloadable = Config::get().reloadable();
+
+            return reloadable
+                .schedule
+                .lanes()
+                .flat_map(Lane::iter)
+                .try_for_each(|entry| self.plan(entry.task).map(drop));
+        };
 
-        Ok(Self { client, osu, mysql })
+        self.plan(task).map(drop)
     }
 
-    /// Runs one iteration and then returns
-    pub async fn run_once(self, task: Task, args: Args) {
-        log_args_delay(Some(task), &args).await;
+    /// Runs one iteration and then returns.
+    ///
+    /// If a checkpoint is given, the iteration continues where it left off.
+    pub async fn run_once(self, task: Task, args: Args, checkpoint: Option<Checkpoint>) {
+        log_args_delay(Some(task), &args, &self.shutdown).await;
+
+        if self.shutdown.is_requested() {
+            return;
+        }
+
         let start = Instant::now();
 
-        self.iteration(task, &args).await;
+        self.iteration(task, &args, checkpoint, None).await;
 
         let elapsed = TimeEstimate::new(start.elapsed());
         info!("Finished task `{task}` in {elapsed}");
     }
 
-    /// Runs forever based on the schedule in the .env file
+    /// Runs forever based on the schedule in the .env file.
+    ///
+    /// A reloaded config is applied right away. Each lane that changed
+    /// through it restarts at its own task boundary while unchanged lanes
+    /// keep running.
     pub async fn loop_forever(self, args: Args) {
-        let schedule = &Config::get().schedule;
+        let reload = Reload::listen(args.watch_env);
+        log_schedule(&reload.applied().schedule);
 
-        info!("Schedule:");
+        log_args_delay(None, &args, &self.shutdown).await;
 
-        for (task, i) in schedule.iter().zip(1..) {
-            info!("  {i}. {task}");
+        if self.shutdown.is_requested() {
+            return;
         }
 
-        info!("");
+        let state = match ScheduleState::load() {
+            Ok(state) => state,
+            Err(err) => {
+                warn!(?err, "Failed to load schedule state; lanes start over");
+
+                ScheduleState::default()
+            }
+        };
+
+        let state = Mutex::new(state);
 
-        log_args_delay(None, &args).await;
+        // The lane at `idx` of the given config; returns `idx` once the lane
+        // changed or a shutdown is requested
+        let run_lane = |idx: usize, reloadable: Arc<Reloadable>| {
+            let this = &self;
+            let state = &state;
+            let reload = &reload;
+            let args = &args;
 
-        info!("First task starting now...");
+            async move {
+                if let Some(lane) = reloadable.schedule.lanes().nth(idx) {
+                    let fut = this.run_lane(idx, lane, state, reload, args);
+                    METRICS.lane_scope(idx, fut).await;
+                }
+
+                idx
+            }
+        };
+
+        let applied = reload.applied();
+        let mut running: HashSet<usize> = (0..applied.schedule.lanes().len()).collect();
+
+        let mut lanes: FuturesUnordered<_> = running
+            .iter()
+            .map(|&idx| run_lane(idx, Arc::clone(&applied)))
+            .collect();
+
+        loop {
+            tokio::select! {
+                Some(idx) = lanes.next() => {
+                    running.remove(&idx);
+
+                    if self.shutdown.is_requested() {
+                        continue;
+                    }
+
+                    let applied = reload.applied();
+
+                    if applied.schedule.lanes().nth(idx).is_some() {
+                        info!("Restarting lane {} with its reloaded tasks", idx + 1);
+                        running.insert(idx);
+                        lanes.push(run_lane(idx, applied));
+                    } else {
+                        info!("Stopped lane {} since it was removed from the schedule", idx + 1);
+                    }
+                }
+                _ = reload.pending() => {
+                    let Some(next) = reload.take() else {
+                        continue;
+                    };
+
+                    reload.apply(Arc::clone(&next));
+                    info!("Applied reloaded config");
+                    log_schedule(&next.schedule);
+
+                    // Lanes that were added start right away
+                    for idx in 0..next.schedule.lanes().len() {
+                        if running.insert(idx) {
+                            lanes.push(run_lane(idx, Arc::clone(&next)));
+                        }
+                    }
+                }
+                _ = self.shutdown.requested(), if lanes.is_empty() => return,
+            }
+        }
+    }
+
+    /// Runs the tasks of the lane at `idx` until a shutdown is requested or
+    /// the lane was changed by a reloaded config
+    async fn run_lane(
+        &self,
+        idx: usize,
+        lane: &Lane,
+        state: &Mutex<ScheduleState>,
+        reload: &Reload,
+        args: &Args,
+    ) {
+        if lane.is_timed() {
+            return self.loop_timed(idx, lane, state, reload, args).await;
+        }
 
         let duration = Duration::from_secs(args.interval * 60 * 60);
-        let mut interval = interval(duration);
 
-        for &task in schedule.iter().cycle() {
-            interval.tick().await;
+        // Continue after the last completed task of a previous process
+        let (first, delay) = match state.lock().unwrap().lane(lane) {
+            Some(prev) => {
+                let first = (prev.index + 1) % lane.iter().len();
+                let next_start = prev.start + duration;
+                let now = OffsetDateTime::now_utc();
+
+                let delay = Duration::try_from(next_start - now).unwrap_or(Duration::ZERO);
+
+                if let Some(entry) = lane.get(prev.index) {
+                    info!(
+                        "Continuing schedule after task `{}` that finished at {}",
+                        entry.task,
+                        format_datetime(prev.finish)
+                    );
+                }
+
+                (first, delay)
+            }
+            None => (0, Duration::ZERO),
+        };
+
+        if delay.is_zero() {
+            info!("First task starting now...");
+        } else if let Some(entry) = lane.get(first) {
+            let hours = (delay.as_secs() as f64) / 3600.0;
+            info!("Task `{}` starts in {hours:.3} hour(s)", entry.task);
+        }
+
+        let mut interval = interval_at(TokioInstant::now() + delay, duration);
+
+        for (index, entry) in lane.iter().enumerate().cycle().skip(first) {
+            let task = entry.task;
+
+            if reload.is_outdated(idx, lane) {
+                return;
+            }
+
+            tokio::select! {
+                _ = interval.tick() => {},
+                _ = self.shutdown.requested() => return,
+                _ = reload.outdated(idx, lane) => return,
+            }
+
             let start = Instant::now();
+            let start_datetime = OffsetDateTime::now_utc();
 
-            self.iteration(task, &args).await;
+            self.lane_iteration(task, args).await;
 
             let elapsed = start.elapsed();
 
+            if self.shutdown.is_requested() {
+                info!(
+                    "Stopping schedule after task `{task}` took {}",
+                    TimeEstimate::new(elapsed)
+                );
+
+                return;
+            }
+
+            {
+                let mut state = state.lock().unwrap();
+                state.complete(lane, index, start_datetime, OffsetDateTime::now_utc());
+
+                if !self.persist_schedule {
+                    debug!("Skipped saving schedule state");
+                } else if let Err(err) = state.save() {
+                    warn!(?err, "Failed to save schedule state");
+                }
+            }
+
             let next = interval
                 .period()
                 .checked_sub(elapsed)
@@ -99,254 +355,844 @@ impl Context {
         }
     }
 
+    /// Runs the tasks of a lane whenever they're due.
+    ///
+    /// Tasks that were due while the script was not running or while
+    /// another task was running are caught up on once.
+    async fn loop_timed(
+        &self,
+        idx: usize,
+        lane: &Lane,
+        state: &Mutex<ScheduleState>,
+        reload: &Reload,
+        args: &Args,
+    ) {
+        let now = OffsetDateTime::now_utc();
+
+        let mut due: Vec<_> = {
+            let state = state.lock().unwrap();
+
+            lane.iter()
+                .map(|entry| entry.next_due(state.last_start(entry), now))
+                .collect()
+        };
+
+        loop {
+            if reload.is_outdated(idx, lane) {
+                return;
+            }
+
+            let next = lane
+                .iter()
+                .zip(due.iter())
+                .enumerate()
+                .filter_map(|(i, (entry, at))| Some((i, entry, (*at)?)))
+                .min_by_key(|(_, _, at)| *at);
+
+            let Some((i, entry, at)) = next else {
+                warn!(
+                    "None of the scheduled tasks of lane {} will ever be due",
+                    idx + 1
+                );
+
+                return tokio::select! {
+                    _ = self.shutdown.requested() => {},
+                    _ = reload.outdated(idx, lane) => {},
+                };
+            };
+
+            let task = entry.task;
+            let now = OffsetDateTime::now_utc();
+
+            if at > now {
+                let wait = (at - now).unsigned_abs();
+
+                info!(
+                    "Next task `{task}` starts at {} (in {})",
+                    format_datetime(at),
+                    TimeEstimate::new(wait)
+                );
+
+                tokio::select! {
+                    _ = sleep(wait) => {},
+                    _ = self.shutdown.requested() => return,
+                    _ = reload.outdated(idx, lane) => return,
+                }
+            } else if now - at > Duration::from_secs(60) {
+                info!(
+                    "Catching up on task `{task}` that was due at {}",
+                    format_datetime(at)
+                );
+            }
+
+            let start = Instant::now();
+            let start_datetime = OffsetDateTime::now_utc();
+
+            self.lane_iteration(task, args).await;
+
+            let elapsed = TimeEstimate::new(start.elapsed());
+
+            if self.shutdown.is_requested() {
+                return info!("Stopping schedule after task `{task}` took {elapsed}");
+            }
+
+            {
+                let mut state = state.lock().unwrap();
+                state.complete_timed(entry, start_datetime);
+
+                if !self.persist_schedule {
+                    debug!("Skipped saving schedule state");
+                } else if let Err(err) = state.save() {
+                    warn!(?err, "Failed to save schedule state");
+                }
+            }
+
+            info!("Finished task `{task}` in {elapsed}");
+
+            // Times that were missed while running are skipped
+            due[i] = entry.next_due(None, OffsetDateTime::now_utc());
+        }
+    }
+
+    /// Runs one iteration of a lane's task unless another lane is already
+    /// running a task that covers it.
+    ///
+    /// If the task requests users while another lane's task waits for its
+    /// turn to request them, the task is merged into that one instead so
+    /// that users are requested only once for both.
+    async fn lane_iteration(&self, task: Task, args: &Args) {
+        let requests_users = match self.plan(task) {
+            Ok(plan) => plan.request_users,
+            Err(err) => return error!(?err, "Skipping task `{task}`"),
+        };
+
+        let id = {
+            let mut running = self.running.lock().unwrap();
+
+            if let Some(other) = running.iter().find(|other| other.task.contains(task)) {
+                return info!(
+                    "Skipping task `{task}` since another lane is running `{}`",
+                    other.task
+                );
+            }
+
+            if requests_users {
+                if let Some(queued) = running.iter_mut().find(|other| other.queued) {
+                    info!(
+                        "Merging task `{task}` into task `{}` of another lane which waits \
+                        to request users",
+                        queued.task
+                    );
+
+                    queued.task |= task;
+
+                    return;
+                }
+            }
+
+            let id = self.next_run_id.fetch_add(1, Ordering::Relaxed);
+
+            running.push(RunningTask {
+                id,
+                task,
+                queued: requests_users,
+            });
+
+            id
+        };
+
+        let users_guard = if requests_users {
+            Some(lock(&self.users_lock, "requesting users").await)
+        } else {
+            None
+        };
+
+        // Other lanes may have merged their tasks in the meantime
+        let task = {
+            let mut running = self.running.lock().unwrap();
+            let run = running.iter_mut().find(|run| run.id == id);
+            let run = run.expect("missing running task");
+            run.queued = false;
+
+            run.task
+        };
+
+        self.iteration(task, args, None, users_guard).await;
+
+        let mut running = self.running.lock().unwrap();
+
+        if let Some(idx) = running.iter().position(|run| run.id == id) {
+            running.swap_remove(idx);
+        }
+    }
+
     /// Runs one single iteration based on the task
-    async fn iteration(&self, task: Task, args: &Args) {
+    ///
+    /// If the task requests users, it does so once the users lock is
+    /// acquired, unless it is already given.
+    async fn iteration(
+        &self,
+        task: Task,
+        args: &Args,
+        checkpoint: Option<Checkpoint>,
+        users_guard: Option<MutexGuard<'_, ()>>,
+    ) {
+        let report = SharedReport::new(task);
+
+        report
+            .scope(self.report_iteration(task, args, checkpoint, users_guard))
+            .await;
+    }
+
+    /// Runs the iteration while its report is the current one
+    async fn report_iteration(
+        &self,
+        task: Task,
+        args: &Args,
+        checkpoint: Option<Checkpoint>,
+        users_guard: Option<MutexGuard<'_, ()>>,
+    ) {
+        let plan = match self.plan(task) {
+            Ok(plan) => plan,
+            Err(err) => return error!(?err, "Skipping task `{task}`"),
+        };
+
+        if plan.is_empty() {
+            return warn!("Skipping task `{task}` since the storage supports none of its jobs");
+        }
+
         info!("Starting task `{task}`");
+        debug!("Plan of task `{task}`: {plan}");
+        METRICS.set_task(Some(task));
+        METRICS.set_phase(Phase::GatheringUsers);
 
         let mut db_handles = Vec::new();
 
-        let (users, badges, progress) = self.gather_users_and_badges(task, args).await;
+        let (gathered, badges_diff, progress) = self
+            .gather_users_and_badges(&plan, task, args, checkpoint, users_guard, &mut db_handles)
+            .await;
 
         // Store badges if required
-        if !badges.is_empty() && task.badges() {
-            db_handles.push(self.mysql.store_badges(badges));
+        if let Some(diff) = badges_diff.filter(|diff| !diff.is_empty()) {
+            let summary = BadgeSummary::new(&diff);
+            SharedReport::update_current(|report| report.badges = summary);
+            db_handles.push(self.storage.store_badges(diff));
         }
 
-        // If badges are all that was required then we're already done
-        if task != Task::BADGES {
-            match self.request_medals().await {
+        // Skipped if none of the jobs need medals e.g. if only badges were required
+        let medals_guard = if plan.scrape_medals {
+            let guard = lock(&self.medals_lock, "storing medals").await;
+            METRICS.set_phase(Phase::Medals);
+
+            let res = stage("medals", self.request_medals()).await;
+
+            match res {
                 Ok(medals) => {
                     // Fetch medal ids to see if we received new ones
-                    match self.mysql.fetch_medal_ids().await {
-                        Ok(old_medals) => {
+                    let old_medals = if plan.new_medals {
+                        self.storage.fetch_medal_ids().await.map(Some)
+                    } else {
+                        Ok(None)
+                    };
+
+                    match old_medals {
+                        Ok(None) => {}
+                        Ok(Some(old_medals)) => {
                             let new_medals: MedalRarities = medals
                                 .iter()
                                 .filter(|medal| !old_medals.contains(&medal.id))
                                 .map(|medal| (medal.id, 0, 0.0))
                                 .collect();
 
+                            let count = new_medals.len();
+                            SharedReport::update_current(|report| report.new_medals = count);
+
                             // If there are new medals, store their rarities
                             if !new_medals.is_empty() {
-                                db_handles.push(self.mysql.store_rarities(new_medals));
+                                db_handles.push(self.storage.store_rarities(new_medals));
                             }
                         }
-                        Err(err) => error!(?err, "Failed to fetch medal ids from DB"),
+                        Err(err) => self.report_error(err, "Failed to fetch medal ids from DB"),
                     };
 
                     // Store medals if required
-                    if task.medals() {
+                    if plan.writes(Output::Medals) {
                         // Note that this call needs to happen before storing
                         // rarities so that the DB table does not deadlock.
-                        self.mysql.store_medals(&medals).await;
+                        let _ = self.storage.store_medals(&medals).await;
                     }
 
-                    self.handle_rarities_and_ranking(task, users, &medals, &mut db_handles)
-                        .await;
+                    self.handle_rarities_and_ranking(
+                        &plan,
+                        gathered.users,
+                        gathered.medal_counts,
+                        &medals,
+                        progress.cancelled,
+                        &mut db_handles,
+                    )
+                    .await;
                 }
-                Err(err) => error!(?err, "Failed to gather medals"),
+                Err(err) => self.report_error(err, "Failed to gather medals"),
             }
-        }
 
-        for handle in db_handles {
-            let _ = handle.await;
-        }
+            Some(guard)
+        } else {
+            None
+        };
+
+        METRICS.set_phase(Phase::Storing);
+
+        stage("storing", async {
+            for handle in db_handles {
+                let _ = handle.await;
+            }
+        })
+        .await;
+
+        drop(medals_guard);
+
+        let finish = Finish::from(progress);
 
         // Notify a webhook that we're done storing
-        match self.handle_finish(progress.into()).await {
+        match self.handle_finish(finish).await {
             Ok(_) => info!("Successfully notified webhook about finishing"),
-            Err(err) => error!(?err, "Failed to notify webhook about finishing"),
+            Err(err) => self.report_error(err, "Failed to notify webhook about finishing"),
+        }
+
+        if let Some(report) = SharedReport::current() {
+            let mut report = report.snapshot();
+            report.finish(&finish);
+
+            match self.storage.store_report(&report).await {
+                Ok(_) => info!("Successfully stored run report"),
+                Err(err) => error!(?err, "Failed to store run report"),
+            }
         }
+
+        // The run is complete so its checkpoint is no longer needed.
+        // A cancelled run keeps it so that it can be resumed.
+        if !finish.cancelled {
+            if self.checkpoints {
+                Checkpoint::remove(finish.id, task);
+            }
+
+            METRICS.set_last_finish(OffsetDateTime::now_utc());
+        }
+
+        METRICS.set_phase(Phase::Idle);
+        METRICS.set_task(None);
     }
 
+    /// Request all users of the task and gather their badges.
+    ///
+    /// For ranking-only tasks the users are stored in chunks while requesting
+    /// so the returned users only contain those that still need to be stored.
     async fn gather_users_and_badges(
         &self,
+        plan: &Plan,
         task: Task,
         args: &Args,
-    ) -> (Vec<OsuUser>, Badges, Progress) {
-        // If medals are the only thing that should be updated, fetching users is not necessary
-        let mut user_ids = if task != Task::MEDALS {
-            // Otherwise fetch the user ids stored by osekai
-            match self.mysql.fetch_osekai_user_ids().await {
-                Ok(users) => users,
+        checkpoint: Option<Checkpoint>,
+        users_guard: Option<MutexGuard<'_, ()>>,
+        db_handles: &mut Vec<JoinHandle<StoreResult>>,
+    ) -> (GatheredUsers, Option<BadgesDiff>, Progress) {
+        let _users_guard = match users_guard {
+            Some(guard) => Some(guard),
+            None if plan.request_users => Some(lock(&self.users_lock, "requesting users").await),
+            None => None,
+        };
+
+        // Fetch badges stored by osekai so we know their ID and can extend the users
+        let (check_badges, stored_badges) = if plan.check_badges {
+            match self.storage.fetch_badges().await {
+                Ok(badges) => (true, badges),
                 Err(err) => {
-                    error!(?err, "Failed to fetch osekai user ids");
+                    self.report_error(err, "Failed to fetch badges from DB");
 
-                    HashSet::with_hasher(IntHasher)
+                    (false, Badges::default())
                 }
             }
         } else {
-            HashSet::with_hasher(IntHasher)
+            (false, Badges::default())
         };
 
-        // Retrieve users from the leaderboards if necessary
-        let pages = if task.rarity() {
-            Some(200)
-        } else if task.ranking() {
-            Some(5)
+        let retention = if plan.stored_rarities && plan.writes(Output::Rankings) {
+            // Rarities are not calculated so the stored ones are used
+            // to turn users into rankings while requesting
+            match self.storage.fetch_medal_rarities().await {
+                Ok(rarities) => UserRetention::Stream(rarities),
+                Err(err) => {
+                    self.report_error(err, "Failed to fetch medal rarities from DB");
+
+                    UserRetention::Discard
+                }
+            }
+        } else if plan.writes(Output::Rankings) {
+            // Rankings require the rarities of all users first
+            UserRetention::Keep
         } else {
-            None
+            UserRetention::Discard
         };
 
-        if let Some(pages) = pages.filter(|_| !args.debug) {
-            self.request_leaderboards(&mut user_ids, pages).await;
-        }
+        let count_medals = plan.count_medals;
 
-        // If really ALL users are wanted, fetch them from osekai
-        if task.contains(Task::FULL) && !args.debug {
-            if let Err(err) = self.mysql.fetch_osekai_ranking_ids(&mut user_ids).await {
-                error!(?err, "Failed to fetch osekai ranking ids");
-            }
-        }
+        let (mut gathered, mut progress) = match checkpoint {
+            Some(checkpoint) => {
+                info!(
+                    "Resuming from checkpoint {} with {}/{} remaining user(s)",
+                    checkpoint.id,
+                    checkpoint.remaining.len(),
+                    checkpoint.total,
+                );
 
-        // In case additional user ids were given through CLI, add them here
-        user_ids.extend(&args.extras);
+                let start = OffsetDateTime::from_unix_timestamp(checkpoint.id)
+                    .unwrap_or_else(|_| OffsetDateTime::now_utc());
 
-        // Fetch badges stored by osekai so we know their ID and can extend the users
-        let (check_badges, stored_badges) = if task.badges() {
-            match self.mysql.fetch_badges().await {
-                Ok(badges) => (true, badges),
-                Err(err) => {
-                    error!(?err, "Failed to fetch badges from DB");
+                let resumed = checkpoint.total - checkpoint.remaining.len();
+                let progress = Progress::resume(start, checkpoint.total, resumed, task);
 
-                    (false, Badges::default())
-                }
+                let gathered =
+                    GatheredUsers::resume(checkpoint, retention, count_medals, check_badges);
+
+                (gathered, progress)
             }
-        } else {
-            (false, Badges::default())
-        };
+            None => {
+                let user_ids = self.gather_user_ids(task, plan, args).await;
+                let progress = Progress::new(user_ids.len(), task);
 
-        if args.debug {
-            user_ids = user_ids.into_iter().take(10).collect();
+                let gathered = GatheredUsers::new(user_ids, retention, count_medals, check_badges);
 
-            if user_ids.is_empty() {
-                user_ids.insert(2211396);
+                (gathered, progress)
+            }
+        };
+
+        if plan.request_users {
+            match self.storage.fetch_failed_user_ids().await {
+                Ok(user_ids) => gathered.track_resolved(user_ids),
+                Err(err) => self.report_error(err, "Failed to fetch failed user ids from DB"),
             }
         }
 
+        let user_ids: Vec<_> = gathered.remaining.iter().copied().collect();
         let len = user_ids.len();
-        let mut users = Vec::with_capacity(len);
         let mut eta = Eta::default();
 
-        let badge_capacity = if check_badges { 10_000 } else { 0 };
-        let mut badges_incoming = Badges::with_capacity(badge_capacity);
-        let mut badge_name_buf = String::new();
-
         info!("Requesting {len} user(s)...");
-
-        let mut progress = Progress::new(len, task);
+        METRICS.set_phase(Phase::RequestingUsers);
 
         if args.progress {
             match self.handle_progress(&progress).await {
                 Ok(_) => info!("Successfully handled initial progress"),
-                Err(err) => error!(?err, "Failed to handle initial progress"),
+                Err(err) => self.report_error(err, "Failed to handle initial progress"),
             }
         }
 
-        let mut handle_user_result = |user_id, res| {
-            let mut user = match res {
-                Ok(user) => user,
-                Err(err) => {
-                    error!(err = ?Report::new(err), "Failed to request user {user_id} from osu!api");
+        // Storing a chunk of rankings while the previous one is still being
+        // stored would only pile up pending chunks so at most one is in flight
+        let mut pending_rankings: Option<JoinHandle<StoreResult>> = None;
 
-                    return;
+        if plan.request_users {
+            stage("users", async {
+                // Request osu! user data for all users for all modes.
+                // The core loop and very expensive.
+                self.request_users(&user_ids, &mut gathered, async |i, gathered| {
+                    self.update_progress(i, len, args, &mut eta, &mut progress)
+                        .await;
+
+                    if let Some(rankings) = gathered.take_rankings(false) {
+                        await_pending(&mut pending_rankings).await;
+                        pending_rankings = Some(self.storage.store_rankings(rankings));
+                    }
+
+                    if self.checkpoints && gathered.checkpoint_due() {
+                        // Users of a pending chunk are no longer in the checkpoint
+                        // so they must be stored before it's saved
+                        await_pending(&mut pending_rankings).await;
+                        gathered.save_checkpoint(&progress);
+                    }
+                })
+                .await;
+
+                self.retry_failed_users(&mut gathered).await;
+            })
+            .await;
+        }
+
+        SharedReport::update_current(|report| {
+            report.failed_users = gathered.failed.len();
+            report.restricted_users = gathered.restricted;
+        });
+
+        if let Some(rankings) = gathered.take_rankings(true) {
+            await_pending(&mut pending_rankings).await;
+            pending_rankings = Some(self.storage.store_rankings(rankings));
+        }
+
+        if self.shutdown.is_requested() {
+            let handled = gathered.handled();
+            info!("Stopped requesting users after {handled}/{len} due to shutdown");
+
+            progress.cancel(handled);
+            await_pending(&mut pending_rankings).await;
+
+            if self.checkpoints {
+                gathered.save_checkpoint(&progress);
+            }
+        } else {
+            info!("Finished requesting {len} users");
+        }
+
+        if args.progress && !progress.cancelled {
+            progress.finish();
+
+            match self.handle_progress(&progress).await {
+                Ok(_) => info!("Successfully handled final progress"),
+                Err(err) => self.report_error(err, "Failed to handle final progress"),
+            }
+        }
+
+        db_handles.extend(pending_rankings);
+
+        // Only badges of checked users can be compared with the stored ones
+        let badges_diff =
+            check_badges.then(|| mem::take(&mut gathered.badges).diff(&stored_badges));
+
+        (gathered, badges_diff, progress)
+    }
+
+    /// Request all given users and pass their results to `gathered`.
+    ///
+    /// After each handled result, `on_handled` is called with the user's index.
+    async fn request_users(
+        &self,
+        user_ids: &[u32],
+        gathered: &mut GatheredUsers,
+        mut on_handled: impl AsyncFnMut(usize, &mut GatheredUsers),
+    ) {
+        // Statistics of all modes are requested for a whole batch at once,
+        // the remaining data is requested for each user individually.
+        // Both kinds of requests are driven by the same set so that users
+        // in flight keep being polled while the next batch is requested.
+        let mut futures = FuturesUnordered::new();
+        let mut batches = user_ids.chunks(Self::USER_BATCH_SIZE);
+        let mut batch_in_flight = false;
+
+        // Users of received batches whose own request was not started yet
+        let mut queued = VecDeque::with_capacity(2 * Self::USER_BATCH_SIZE);
+        let mut i = 0;
+
+        loop {
+            if !self.shutdown.is_requested() {
+                while futures.len() - usize::from(batch_in_flight) < self.concurrency_limit() {
+                    let Some((user_id, stats)) = queued.pop_front() else {
+                        break;
+                    };
+
+                    i += 1;
+
+                    let job = async move {
+                        let start = Instant::now();
+                        let res = self.request_osu_user(user_id, stats).await;
+
+                        UserResponse::User {
+                            i,
+                            user_id,
+                            res,
+                            latency: start.elapsed(),
+                        }
+                    };
+
+                    futures.push(Either::Left(job));
                 }
+
+                // Request the next batch before the queue runs dry
+                if !batch_in_flight && queued.len() < Self::USER_BATCH_SIZE {
+                    if let Some(batch) = batches.next() {
+                        let job = async move {
+                            let start = Instant::now();
+                            let res = self.request_osu_users_batch(batch).await;
+
+                            UserResponse::Batch {
+                                batch,
+                                res,
+                                latency: start.elapsed(),
+                            }
+                        };
+
+                        futures.push(Either::Right(job));
+                        batch_in_flight = true;
+                    }
+                }
+            }
+
+            let Some(response) = futures.next().await else {
+                break;
             };
 
-            // Process badges if required
-            if check_badges {
-                if let OsuUser::Available(ref mut user) = user {
-                    for badge in user.badges.iter_mut() {
-                        badges_incoming.push(user.user_id, badge, &mut badge_name_buf);
+            match response {
+                UserResponse::Batch {
+                    batch,
+                    res,
+                    latency,
+                } => {
+                    batch_in_flight = false;
+                    self.record_request(latency, &res);
+
+                    match res {
+                        Ok(mut stats) => queued.extend(
+                            batch
+                                .iter()
+                                .map(|&user_id| (user_id, stats.remove(&user_id))),
+                        ),
+                        Err(err) => {
+                            gathered.handle_batch_error(batch, err);
+                            i += batch.len();
+                        }
                     }
                 }
+                UserResponse::User {
+                    i,
+                    user_id,
+                    res,
+                    latency,
+                } => {
+                    self.record_request(latency, &res);
+                    gathered.handle_result(user_id, res);
+                    on_handled(i, gathered).await;
+                }
             }
+        }
+    }
 
-            users.push(user);
-        };
+    /// The cassette if responses should be served from it
+    fn replaying(&self) -> Option<&Cassette> {
+        self.cassette
+            .as_ref()
+            .filter(|cassette| cassette.is_replay())
+    }
 
-        let jobs = user_ids
-            .into_iter()
-            .zip(1..)
-            .map(async |(user_id, i)| (i, user_id, self.request_osu_user(user_id).await));
+    fn concurrency_limit(&self) -> usize {
+        self.concurrency.lock().unwrap().limit()
+    }
 
-        let mut futures = FuturesUnordered::new();
+    /// Plan the jobs of the task with what the storage is able to fetch.
+    fn plan(&self, task: Task) -> Result<Plan> {
+        Plan::new(
+            task,
+            &Config::get().leaderboard_pages,
+            self.storage.fetchable(),
+        )
+    }
 
-        // Request osu! user data for all users for all modes.
-        // The core loop and very expensive.
-        for job in jobs {
-            const CONCURRENT_USERS: usize = 4;
+    /// Log the error and add it to the report of the current run.
+    fn report_error(&self, err: Report, msg: &str) {
+        error!(?err, "{msg}");
+        SharedReport::update_current(|report| report.add_error(format!("{msg}: {err:#}")));
+    }
 
-            while futures.len() >= CONCURRENT_USERS {
-                let Some((i, user_id, res)) = futures.next().await else {
-                    continue;
-                };
+    /// Let the concurrency controller know about a request's outcome
+    fn record_request<T>(&self, latency: Duration, res: &RequestResult<T>) {
+        let failure = res.as_ref().err().map(FailureKind::from);
+        self.concurrency.lock().unwrap().record(latency, failure);
+    }
 
-                handle_user_result(user_id, res);
+    /// Re-request users that failed with exponential backoff inbetween
+    /// attempts. Users that still fail after the last attempt are stored
+    /// so that persistently failing users can be identified.
+    async fn retry_failed_users(&self, gathered: &mut GatheredUsers) {
+        const MAX_ATTEMPTS: u32 = 3;
+        const INITIAL_BACKOFF: Duration = Duration::from_secs(30);
 
-                self.update_progress(i, len, args, &mut eta, &mut progress)
-                    .await;
+        for attempt in 1..=MAX_ATTEMPTS {
+            if gathered.failed.is_empty() || self.shutdown.is_requested() {
+                break;
             }
 
-            futures.push(job);
+            // Replayed responses don't change over time
+            let backoff = if self.replaying().is_some() {
+                Duration::ZERO
+            } else {
+                INITIAL_BACKOFF * 2_u32.pow(attempt - 1)
+            };
+
+            info!(
+                "Retrying {} failed user(s) in {} (attempt {attempt}/{MAX_ATTEMPTS})",
+                gathered.failed.len(),
+                TimeEstimate::new(backoff),
+            );
+
+            tokio::select! {
+                _ = sleep(backoff) => {},
+                _ = self.shutdown.requested() => break,
+            }
+
+            let user_ids = gathered.take_failed();
+            self.request_users(&user_ids, gathered, async |_, _| {})
+                .await;
         }
 
-        while let Some((i, user_id, res)) = futures.next().await {
-            handle_user_result(user_id, res);
+        // Users that failed will be retried when resuming after a shutdown
+        let no_failures = UserFailures::default();
+
+        let failed = if self.shutdown.is_requested() {
+            &no_failures
+        } else {
+            &gathered.failed
+        };
+
+        if !failed.is_empty() {
+            warn!(
+                "Failed to request {} user(s) after {MAX_ATTEMPTS} retries",
+                failed.len()
+            );
+
+            let mut kinds: Vec<_> = failed.values().copied().collect();
+            kinds.sort_unstable_by_key(|kind| kind.as_str());
+
+            SharedReport::update_current(|report| {
+                for chunk in kinds.chunk_by(|a, b| a == b) {
+                    report.add_error(format!(
+                        "Failed to request {} user(s): {}",
+                        chunk.len(),
+                        chunk[0]
+                    ));
+                }
+            });
+        }
 
-            self.update_progress(i, len, args, &mut eta, &mut progress)
+        if !failed.is_empty() || !gathered.resolved.is_empty() {
+            let _ = self
+                .storage
+                .store_failures(failed, &gathered.resolved)
                 .await;
         }
+    }
 
-        info!("Finished requesting {len} users");
+    /// Collect all user ids that should be requested for the task
+    async fn gather_user_ids(
+        &self,
+        task: Task,
+        plan: &Plan,
+        args: &Args,
+    ) -> HashSet<u32, IntHasher> {
+        let entry = CassetteEntry::UserIds(task);
 
-        if args.progress {
-            progress.finish();
+        let mut user_ids = match self.replaying() {
+            Some(cassette) => match cassette.replay(&entry) {
+                Ok(user_ids) => user_ids,
+                Err(err) => {
+                    self.report_error(err, "Failed to replay gathered user ids");
 
-            match self.handle_progress(&progress).await {
-                Ok(_) => info!("Successfully handled final progress"),
-                Err(err) => error!(?err, "Failed to handle final progress"),
+                    HashSet::with_hasher(IntHasher)
+                }
+            },
+            None => {
+                let user_ids = self.gather_stored_user_ids(plan, args).await;
+
+                if let Some(ref cassette) = self.cassette {
+                    cassette.record(&entry, &user_ids);
+                }
+
+                user_ids
+            }
+        };
+
+        // In case additional user ids were given through CLI or env, add them here
+        user_ids.extend(&args.extras);
+        user_ids.extend(&Config::get().reloadable().extra_users);
+
+        if args.debug {
+            user_ids = user_ids.into_iter().take(10).collect();
+
+            if user_ids.is_empty() {
+                user_ids.insert(2211396);
             }
         }
 
-        if check_badges {
-            badges_incoming.merge(stored_badges);
+        user_ids
+    }
+
+    /// Collect the user ids of osekai and of the leaderboards
+    async fn gather_stored_user_ids(&self, plan: &Plan, args: &Args) -> HashSet<u32, IntHasher> {
+        // If medals are the only thing that should be updated, fetching users is not necessary
+        let mut user_ids = if plan.request_users {
+            // Otherwise fetch the user ids stored by osekai
+            match self.storage.fetch_osekai_user_ids().await {
+                Ok(users) => users,
+                Err(err) => {
+                    self.report_error(err, "Failed to fetch osekai user ids");
+
+                    HashSet::with_hasher(IntHasher)
+                }
+            }
+        } else {
+            HashSet::with_hasher(IntHasher)
+        };
+
+        // Retrieve users from the leaderboards if necessary
+        if let Some(pages) = plan.leaderboard_pages.filter(|_| !args.debug) {
+            METRICS.set_phase(Phase::Leaderboards);
+            stage(
+                "leaderboards",
+                self.request_leaderboards(&mut user_ids, pages),
+            )
+            .await;
+            METRICS.set_phase(Phase::GatheringUsers);
         }
 
-        (users, badges_incoming, progress)
+        // If really ALL users are wanted, fetch them from osekai
+        if plan.all_users && !args.debug {
+            if let Err(err) = self.storage.fetch_osekai_ranking_ids(&mut user_ids).await {
+                self.report_error(err, "Failed to fetch osekai ranking ids");
+            }
+        }
+
+        user_ids
     }
 
+    /// Store rarities and the rankings of users that were not streamed already.
     async fn handle_rarities_and_ranking(
         &self,
-        task: Task,
+        plan: &Plan,
         users: Vec<OsuUser>,
+        medal_counts: Option<MedalCounts>,
         medals: &[ScrapedMedal],
-        db_handles: &mut Vec<JoinHandle<()>>,
+        cancelled: bool,
+        db_handles: &mut Vec<JoinHandle<StoreResult>>,
     ) {
-        let rarities = if users.is_empty() {
-            return;
-        } else if task.rarity() {
+        // Rarities of an incomplete set of users would be wrong
+        let calculated_rarities = medal_counts
+            .filter(|counts| !cancelled && counts.user_count() > 0)
+            .map(|counts| counts.into_rarities(medals));
+
+        let calculate_rarities = calculated_rarities.is_some();
+        let store_rankings = plan.writes(Output::Rankings) && !users.is_empty();
+
+        let rarities = if let Some(rarities) = calculated_rarities {
             // Leaderboard users were gathered so we can calculate proper rarities
-            Self::calculate_rarities(&users, medals)
-        } else if task.ranking() {
+            rarities
+        } else if store_rankings {
             // Only osekai users were retrieved, dont calculate rarities
             // and instead just fetch them from osekai
-            match self.mysql.fetch_medal_rarities().await {
+            match self.storage.fetch_medal_rarities().await {
                 Ok(rarities) => rarities,
-                Err(err) => return error!(?err, "Failed to fetch medal rarities from DB"),
+                Err(err) => {
+                    return self.report_error(err, "Failed to fetch medal rarities from DB")
+                }
             }
         } else {
             return;
         };
 
         // Calculate and store user rankings if required
-        if task.ranking() {
+        if store_rankings {
             let rankings_iter = RankingsIter::new(users, rarities.clone());
-            db_handles.push(self.mysql.store_rankings(rankings_iter));
+            db_handles.push(self.storage.store_rankings(rankings_iter));
         }
 
         // Store rarities if required
-        if task.rarity() {
-            db_handles.push(self.mysql.store_rarities(rarities));
+        if calculate_rarities {
+            db_handles.push(self.storage.store_rarities(rarities));
         }
     }
 
@@ -365,26 +1211,114 @@ impl Context {
         }
 
         let remaining_time = eta.estimate(len - i);
-        info!("User progress: {i}/{len} | ETA: {remaining_time}");
+
+        let (concurrency, rate) = {
+            let concurrency = self.concurrency.lock().unwrap();
+
+            (concurrency.limit(), concurrency.rate())
+        };
+
+        info!(
+            "User progress: {i}/{len} | ETA: {remaining_time} | \
+            Concurrency: {concurrency} | Rate: {rate:.2} users/s"
+        );
 
         if args.progress {
             progress.update(i, eta);
 
             match self.handle_progress(&*progress).await {
                 Ok(_) => info!("Successfully handled progress"),
-                Err(err) => error!(?err, "Failed to handle progress"),
+                Err(err) => self.report_error(err, "Failed to handle progress"),
             }
         }
     }
 }
 
-async fn log_args_delay(task: Option<Task>, args: &Args) {
+/// Task of a lane in [`Context::lane_iteration`].
+struct RunningTask {
+    id: u64,
+    task: Task,
+    /// Whether the task still waits for another lane to finish requesting
+    /// users; tasks of further lanes are merged into it meanwhile.
+    queued: bool,
+}
+
+/// Outcome of one of the requests in [`Context::request_users`].
+enum UserResponse<'a> {
+    Batch {
+        batch: &'a [u32],
+        res: RequestResult<HashMap<u32, UserStatisticsModes, IntHasher>>,
+        latency: Duration,
+    },
+    User {
+        /// Index of the user among all requested users
+        i: usize,
+        user_id: u32,
+        res: RequestResult<OsuUser>,
+        latency: Duration,
+    },
+}
+
+fn log_schedule(schedule: &Schedule) {
+    info!("Schedule:");
+
+    let lane_count = schedule.lanes().len();
+
+    for (lane, i) in schedule.lanes().zip(1..) {
+        if lane_count > 1 {
+            info!("  Lane {i}:");
+        }
+
+        for (entry, j) in lane.iter().zip(1..) {
+            info!("  {j}. {entry}");
+        }
+    }
+
+    info!("");
+}
+
+/// Acquire the lock, logging if another lane is holding it.
+async fn lock<'a>(lock: &'a AsyncMutex<()>, purpose: &str) -> MutexGuard<'a, ()> {
+    match lock.try_lock() {
+        Ok(guard) => guard,
+        Err(_) => {
+            info!("Waiting for another lane to finish {purpose}...");
+
+            lock.lock().await
+        }
+    }
+}
+
+/// Await a stage of the run and add its duration to the report.
+async fn stage<T>(name: &'static str, fut: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let res = fut.await;
+    let elapsed = start.elapsed();
+
+    debug!("Stage `{name}` took {}", TimeEstimate::new(elapsed));
+    SharedReport::update_current(|report| report.add_duration(name, elapsed));
+
+    res
+}
+
+/// Wait until the pending handle, if any, is finished.
+async fn await_pending(pending: &mut Option<JoinHandle<StoreResult>>) {
+    if let Some(handle) = pending.take() {
+        let _ = handle.await;
+    }
+}
+
+async fn log_args_delay(task: Option<Task>, args: &Args, shutdown: &Shutdown) {
     let Args {
         delay,
         extras,
         interval,
         progress,
         debug: debug_, // tracing::info doesn't like variables called `debug`
+        resume,
+        dry_run,
+        record,
+        replay,
         ..
     } = args;
 
@@ -395,15 +1329,45 @@ async fn log_args_delay(task: Option<Task>, args: &Args) {
         info!("  - The task will start in {delay} minute(s)");
     } else {
         info!("  - The first task will start in {delay} minute(s)");
-        info!("  - Tasks will start {interval} hour(s) after each other");
+
+        if !Config::get().reloadable().schedule.is_timed() {
+            info!("  - Tasks will start {interval} hour(s) after each other");
+        }
     }
 
     info!("  - Send progress to osekai while requesting users: {progress}");
     info!("  - Additional user ids: {extras:?}");
+    info!(
+        "  - Additional user ids of EXTRA_USERS: {:?}",
+        Config::get().reloadable().extra_users
+    );
     info!("  - Debug mode enabled: {debug_}");
+    info!("  - Resume from checkpoint: {resume}");
+
+    if let Some(path) = dry_run {
+        info!("  - Dry run into: {}", path.display());
+    }
+
+    if let Some(dir) = record {
+        info!("  - Record responses into: {}", dir.display());
+    }
+
+    if let Some(dir) = replay {
+        info!("  - Replay responses from: {}", dir.display());
+    }
+
+    let requests = &Config::get().requests;
+    info!("  - Max concurrent user requests: {}", requests.concurrency);
+    info!(
+        "  - Max osu!api requests per second: {}",
+        requests.ratelimit
+    );
     info!("");
 
     if args.delay > 0 {
-        sleep(Duration::from_secs(delay * 60)).await;
+        tokio::select! {
+            _ = sleep(Duration::from_secs(delay * 60)) => {},
+            _ = shutdown.requested() => {},
+        }
     }
 }
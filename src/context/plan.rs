@@ -0,0 +1,236 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use eyre::{bail, Result};
+
+use crate::{config::LeaderboardPages, database::Fetchable, task::Task};
+
+/// Kind of work that a flag of a [`Task`] stands for.
+///
+/// Each job declares what it reads and what it writes so that a run only
+/// consists of the stages that the task's jobs need.
+pub(super) struct Job {
+    pub task: Task,
+    pub name: &'static str,
+    pub inputs: Inputs,
+    pub outputs: &'static [Output],
+}
+
+/// Data that a job reads before it can write its outputs.
+#[derive(Copy, Clone)]
+pub(super) struct Inputs {
+    /// Users stored by osekai are requested
+    pub users: bool,
+    /// Users of this many leaderboard pages per mode are requested as well
+    pub leaderboard_pages: Option<fn(&LeaderboardPages) -> usize>,
+    /// Users of all osekai rankings are requested as well if a job of the
+    /// same run writes rankings; on their own they're too many to request
+    pub all_users: bool,
+    /// Medals of requested users are counted
+    pub medal_counts: bool,
+    /// Medals are scraped from the osu! website
+    pub medals: bool,
+    /// Badges are fetched from the database
+    pub stored_badges: bool,
+    /// Rarities are fetched from the database unless a job of the same run
+    /// calculates them
+    pub rarities: bool,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub(super) enum Output {
+    Medals,
+    Badges,
+    Rarities,
+    Rankings,
+}
+
+impl Inputs {
+    const NONE: Self = Self {
+        users: false,
+        leaderboard_pages: None,
+        all_users: false,
+        medal_counts: false,
+        medals: false,
+        stored_badges: false,
+        rarities: false,
+    };
+}
+
+/// All jobs; new kinds of jobs only need to be added here and to [`Task`].
+pub(super) static JOBS: [Job; 4] = [
+    Job {
+        task: Task::MEDALS,
+        name: "medals",
+        inputs: Inputs {
+            medals: true,
+            ..Inputs::NONE
+        },
+        outputs: &[Output::Medals],
+    },
+    Job {
+        task: Task::BADGES,
+        name: "badges",
+        inputs: Inputs {
+            users: true,
+            // Badges are checked for all available users when rankings
+            // request them anyway
+            all_users: true,
+            stored_badges: true,
+            ..Inputs::NONE
+        },
+        outputs: &[Output::Badges],
+    },
+    Job {
+        task: Task::RARITY,
+        name: "rarity",
+        inputs: Inputs {
+            users: true,
+            leaderboard_pages: Some(|pages| pages.rarity),
+            medal_counts: true,
+            medals: true,
+            ..Inputs::NONE
+        },
+        outputs: &[Output::Rarities],
+    },
+    Job {
+        task: Task::RANKING,
+        name: "ranking",
+        inputs: Inputs {
+            users: true,
+            leaderboard_pages: Some(|pages| pages.ranking),
+            // New medals get rarities that rankings can refer to
+            medals: true,
+            rarities: true,
+            ..Inputs::NONE
+        },
+        outputs: &[Output::Rankings],
+    },
+];
+
+/// Stages of a run that are needed by the jobs of a task.
+pub(super) struct Plan {
+    jobs: Vec<&'static Job>,
+    /// Whether users need to be requested at all
+    pub request_users: bool,
+    /// Leaderboard pages per mode whose users are requested
+    pub leaderboard_pages: Option<usize>,
+    /// Whether users of all osekai rankings are requested as well
+    pub all_users: bool,
+    pub count_medals: bool,
+    pub scrape_medals: bool,
+    /// Whether scraped medals are compared with the stored ones to find
+    /// new medals
+    pub new_medals: bool,
+    pub check_badges: bool,
+    /// Whether stored rarities are needed since none are calculated
+    pub stored_rarities: bool,
+}
+
+impl Plan {
+    /// Plan the jobs of the task based on what the storage can fetch.
+    ///
+    /// Jobs that need stored badges are skipped if the storage can't fetch
+    /// them. Jobs that need rarities which the storage can't fetch require a
+    /// job of the task to calculate them.
+    pub fn new(task: Task, pages: &LeaderboardPages, fetchable: Fetchable) -> Result<Self> {
+        let mut jobs: Vec<&Job> = Vec::new();
+
+        for job in JOBS.iter().filter(|job| task.contains(job.task)) {
+            if job.inputs.stored_badges && !fetchable.badges {
+                warn!(
+                    "Skipping job `{}` since the storage does not provide badges",
+                    job.name
+                );
+            } else {
+                jobs.push(job);
+            }
+        }
+
+        let calculates_rarities = jobs
+            .iter()
+            .any(|job| job.outputs.contains(&Output::Rarities));
+
+        let needing_rarities = jobs
+            .iter()
+            .find(|job| job.inputs.rarities && !calculates_rarities && !fetchable.rarities);
+
+        if let Some(job) = needing_rarities {
+            let calculating: Vec<_> = JOBS
+                .iter()
+                .filter(|job| job.outputs.contains(&Output::Rarities))
+                .map(|job| format!("`{}`", job.name))
+                .collect();
+
+            bail!(
+                "job `{}` of task `{task}` needs rarities but the storage does not provide \
+                them; add {} to the task to calculate them",
+                job.name,
+                calculating.join(" or ")
+            );
+        }
+
+        let any = |f: fn(&Inputs) -> bool| jobs.iter().any(|job| f(&job.inputs));
+
+        let leaderboard_pages = jobs
+            .iter()
+            .filter_map(|job| job.inputs.leaderboard_pages)
+            .map(|pages_of| pages_of(pages))
+            .max();
+
+        let writes_rankings = jobs
+            .iter()
+            .any(|job| job.outputs.contains(&Output::Rankings));
+
+        Ok(Self {
+            request_users: any(|inputs| inputs.users),
+            leaderboard_pages,
+            all_users: any(|inputs| inputs.all_users) && writes_rankings && fetchable.ranking_ids,
+            count_medals: any(|inputs| inputs.medal_counts),
+            scrape_medals: any(|inputs| inputs.medals),
+            new_medals: fetchable.medal_ids,
+            check_badges: any(|inputs| inputs.stored_badges),
+            stored_rarities: any(|inputs| inputs.rarities) && !calculates_rarities,
+            jobs,
+        })
+    }
+
+    /// Whether the plan has no jobs at all
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Whether any job of the plan writes the output
+    pub fn writes(&self, output: Output) -> bool {
+        self.jobs.iter().any(|job| job.outputs.contains(&output))
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let names: Vec<_> = self.jobs.iter().map(|job| job.name).collect();
+        write!(f, "jobs [{}], stages [", names.join(", "))?;
+
+        let stages = [
+            (self.request_users, "users"),
+            (self.leaderboard_pages.is_some(), "leaderboards"),
+            (self.all_users, "osekai rankings"),
+            (self.check_badges, "badges"),
+            (self.scrape_medals, "medals"),
+            (self.stored_rarities, "stored rarities"),
+        ];
+
+        let mut stages = stages
+            .into_iter()
+            .filter_map(|(needed, name)| needed.then_some(name));
+
+        if let Some(stage) = stages.next() {
+            f.write_str(stage)?;
+
+            for stage in stages {
+                write!(f, ", {stage}")?;
+            }
+        }
+
+        f.write_str("]")
+    }
+}
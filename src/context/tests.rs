@@ -0,0 +1,159 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::sleep;
+
+use crate::{
+    config,
+    database::InMemoryStorage,
+    model::FailureKind,
+    schedule::ScheduleState,
+    task::Task,
+    util::{Args, Reload, Shutdown},
+};
+
+use super::Context;
+
+const STATE_PATH: &str = "./schedule-state.json";
+
+/// Cassette of a `ranking` run over the users 1, 2, and 3:
+/// - user 1 is missing in the batch request
+/// - user 2 is in the batch but not found when requested on its own
+/// - user 3 is in the batch but its own response was not recorded
+fn cassette() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/cassette")
+}
+
+fn args() -> Args {
+    Args {
+        delay: 0,
+        extras: Default::default(),
+        interval: 12,
+        progress: false,
+        quiet: true,
+        debug: false,
+        resume: false,
+        concurrency: None,
+        ratelimit: None,
+        dry_run: None,
+        record: None,
+        replay: Some(cassette()),
+        watch_env: false,
+    }
+}
+
+#[tokio::test]
+async fn replayed_ranking_iteration() {
+    config::init_test();
+
+    let memory = InMemoryStorage::default();
+    memory.data().failures.insert(2, (FailureKind::Timeout, 1));
+
+    let args = args();
+    let ctx = Context::new(Shutdown::listen(), &args, Arc::new(memory.clone()))
+        .await
+        .unwrap();
+
+    ctx.iteration(Task::RANKING, &args, None, None).await;
+
+    let data = memory.data();
+
+    let mut ranked: Vec<_> = data.rankings.keys().copied().collect();
+    ranked.sort_unstable();
+    assert_eq!(ranked, [1, 2]);
+    assert!(data.rankings.values().all(|user| user.restricted));
+
+    // User 2 succeeded so its previous failure is resolved
+    assert_eq!(data.failures.len(), 1);
+    assert_eq!(data.failures[&3].0, FailureKind::Cassette);
+
+    // `ranking` scrapes medals for their rarities but does not store them
+    assert!(data.medals.is_empty());
+    assert!(data.rarities.get(&1).is_some());
+
+    let finish = data.finish.expect("missing finish");
+    assert!(!finish.cancelled);
+    assert_eq!(data.reports.len(), 1);
+}
+
+#[tokio::test]
+async fn merge_task_into_queued_lane() {
+    config::init_test();
+
+    let memory = InMemoryStorage::default();
+    let args = args();
+    let ctx = Context::new(Shutdown::listen(), &args, Arc::new(memory.clone()))
+        .await
+        .unwrap();
+
+    // Another lane is requesting users so the first task is queued
+    let guard = ctx.users_lock.lock().await;
+
+    let queued = ctx.lane_iteration(Task::RANKING, &args);
+
+    let merged = async {
+        ctx.lane_iteration(Task::BADGES, &args).await;
+
+        let running = ctx.running.lock().unwrap();
+        assert_eq!(running.len(), 1);
+        assert!(running[0].task == Task::RANKING | Task::BADGES);
+        assert!(running[0].queued);
+        drop(running);
+
+        drop(guard);
+    };
+
+    tokio::join!(queued, merged);
+
+    let data = memory.data();
+    assert_eq!(data.reports.len(), 1);
+    assert!(data.reports[0].task == Task::RANKING | Task::BADGES);
+    assert!(ctx.running.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn dry_run_lane_keeps_schedule_state() {
+    config::init_test();
+
+    let dry_run = std::env::temp_dir().join("osekai-scripts-dry-run-lane.ndjson");
+
+    let args = Args {
+        dry_run: Some(dry_run.clone()),
+        ..args()
+    };
+
+    let ctx = Context::new(
+        Shutdown::listen(),
+        &args,
+        Arc::new(InMemoryStorage::default()),
+    )
+    .await
+    .unwrap();
+
+    let prev_state = fs::read(STATE_PATH).ok();
+
+    let reload = Reload::listen(false);
+    let applied = reload.applied();
+    let lane = applied.schedule.lanes().next().unwrap();
+    let state = Mutex::new(ScheduleState::default());
+
+    // The lane waits for its next interval once the first task completed
+    let completed = async {
+        while state.lock().unwrap().lane(lane).is_none() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+
+    tokio::select! {
+        _ = ctx.run_lane(0, lane, &state, &reload, &args) => panic!("lane stopped early"),
+        _ = completed => {}
+    }
+
+    assert!(fs::read(STATE_PATH).ok() == prev_state);
+
+    let _ = fs::remove_file(dry_run);
+}
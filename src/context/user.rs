@@ -1,51 +1,130 @@
-use std::{collections::HashSet, error::Error};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    time::Instant,
+};
 
 use eyre::Report;
 use rosu_v2::{
-    prelude::{GameMode, OsuError, Rankings},
-    OsuResult,
+    prelude::{GameMode, OsuError, Rankings, UserExtended, UserStatisticsModes},
+    Osu,
 };
 
 use crate::{
-    model::{OsuUser, UserFull},
+    cassette::CassetteEntry,
+    metrics::{OsuEndpoint, METRICS},
+    model::{OsuUser, RequestError, RequestResult, UserFull},
     util::{Eta, IntHasher},
 };
 
 use super::Context;
 
 impl Context {
-    /// Request user data of a user for all four modes
-    pub async fn request_osu_user(&self, user_id: u32) -> OsuResult<OsuUser> {
-        let (std_res, tko_res, ctb_res, mna_res) = tokio::join!(
-            self.osu.user(user_id).mode(GameMode::Osu),
-            self.osu.user(user_id).mode(GameMode::Taiko),
-            self.osu.user(user_id).mode(GameMode::Catch),
-            self.osu.user(user_id).mode(GameMode::Mania),
-        );
-
-        macro_rules! handle_res {
-            ($res:ident: $mode:path) => {
-                match $res {
-                    Ok(user) => user,
-                    Err(OsuError::NotFound) => return Ok(OsuUser::Restricted { user_id }),
-                    // Retry on error "http2 error: connection error received: not a result of an error"
-                    // see https://github.com/hyperium/hyper/issues/2500
-                    Err(OsuError::Request { source })
-                        if source.source().is_some_and(|err| err.to_string().starts_with("http2 error")) =>
-                    {
-                        self.osu.user(user_id).mode($mode).await?
-                    }
-                    Err(err) => return Err(err),
+    /// The maximum amount of users that can be requested at once
+    pub const USER_BATCH_SIZE: usize = 50;
+
+    /// Request the statistics of up to [`Context::USER_BATCH_SIZE`] users
+    /// for all four modes at once.
+    ///
+    /// Users that are restricted or don't exist are not contained in the result.
+    pub async fn request_osu_users_batch(
+        &self,
+        user_ids: &[u32],
+    ) -> RequestResult<HashMap<u32, UserStatisticsModes, IntHasher>> {
+        if let Some(cassette) = self.replaying() {
+            let mut stats = HashMap::with_capacity_and_hasher(user_ids.len(), IntHasher);
+
+            for &user_id in user_ids {
+                let entry = CassetteEntry::Stats(user_id);
+
+                let replayed = cassette
+                    .replay::<Option<UserStatisticsModes>>(&entry)
+                    .map_err(RequestError::Cassette)?;
+
+                if let Some(replayed) = replayed {
+                    stats.insert(user_id, replayed);
                 }
+            }
+
+            return Ok(stats);
+        }
+
+        let osu = self.osu();
+        let start = Instant::now();
+
+        let res = match osu.users(user_ids.iter().copied()).await {
+            Err(err) if is_http2_error(&err) => osu.users(user_ids.iter().copied()).await,
+            res => res,
+        };
+
+        METRICS.observe_osu_request(OsuEndpoint::Users, start.elapsed());
+        let users = res?;
+
+        let stats: HashMap<_, _, IntHasher> = users
+            .into_iter()
+            .filter_map(|user| Some((user.user_id, user.statistics_modes?)))
+            .collect();
+
+        if let Some(ref cassette) = self.cassette {
+            for &user_id in user_ids {
+                cassette.record(&CassetteEntry::Stats(user_id), &stats.get(&user_id));
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Request the user data that is not contained in a batch request and
+    /// combine it with the user's statistics.
+    ///
+    /// If the statistics are `None`, the user was missing in the batch
+    /// request so they are considered restricted.
+    pub async fn request_osu_user(
+        &self,
+        user_id: u32,
+        stats: Option<UserStatisticsModes>,
+    ) -> RequestResult<OsuUser> {
+        let Some(stats) = stats else {
+            return Ok(OsuUser::Restricted { user_id });
+        };
+
+        let entry = CassetteEntry::User(user_id);
+
+        if let Some(cassette) = self.replaying() {
+            return match cassette.replay::<Option<UserExtended>>(&entry) {
+                Ok(Some(user)) => Ok(OsuUser::Available(UserFull::new(user, stats))),
+                Ok(None) => Ok(OsuUser::Restricted { user_id }),
+                Err(err) => Err(RequestError::Cassette(err)),
             };
         }
 
-        let std = handle_res!(std_res: GameMode::Osu);
-        let tko = handle_res!(tko_res: GameMode::Taiko);
-        let ctb = handle_res!(ctb_res: GameMode::Catch);
-        let mna = handle_res!(mna_res: GameMode::Mania);
+        let osu = self.osu();
+        let start = Instant::now();
+
+        let res = match osu.user(user_id).mode(GameMode::Osu).await {
+            Err(err) if is_http2_error(&err) => osu.user(user_id).mode(GameMode::Osu).await,
+            res => res,
+        };
+
+        METRICS.observe_osu_request(OsuEndpoint::User, start.elapsed());
 
-        Ok(OsuUser::Available(UserFull::new(std, tko, ctb, mna)))
+        let user = match res {
+            Ok(user) => user,
+            Err(OsuError::NotFound) => {
+                if let Some(ref cassette) = self.cassette {
+                    cassette.record(&entry, &None::<UserExtended>);
+                }
+
+                return Ok(OsuUser::Restricted { user_id });
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        if let Some(ref cassette) = self.cassette {
+            cassette.record(&entry, &Some(&user));
+        }
+
+        Ok(OsuUser::Available(UserFull::new(user, stats)))
     }
 
     /// Request leaderboard pages for all four modes and collect user ids.
@@ -61,22 +140,32 @@ impl Context {
         info!("Requesting the first {max_page} leaderboard pages for all modes...");
 
         for page in 1..=max_page as u32 {
-            let std_fut = self.osu.performance_rankings(GameMode::Osu).page(page);
-            let tko_fut = self.osu.performance_rankings(GameMode::Taiko).page(page);
-            let ctb_fut = self.osu.performance_rankings(GameMode::Catch).page(page);
-            let mna_fut = self.osu.performance_rankings(GameMode::Mania).page(page);
+            if self.shutdown.is_requested() {
+                info!(
+                    "Stopped requesting leaderboards after {} pages due to shutdown",
+                    page - 1
+                );
+
+                return;
+            }
+
+            let std_fut = self.request_rankings(GameMode::Osu, page);
+            let tko_fut = self.request_rankings(GameMode::Taiko, page);
+            let ctb_fut = self.request_rankings(GameMode::Catch, page);
+            let mna_fut = self.request_rankings(GameMode::Mania, page);
 
             let (std_res, tko_res, ctb_res, mna_res) =
                 tokio::join!(std_fut, tko_fut, ctb_fut, mna_fut);
 
             fn extend_users(
-                rankings_res: OsuResult<Rankings>,
+                rankings_res: RequestResult<Rankings>,
                 user_ids: &mut HashSet<u32, IntHasher>,
                 page: u32,
                 mode: GameMode,
             ) {
                 match rankings_res {
                     Ok(rankings) => {
+                        METRICS.leaderboard_page_fetched();
                         user_ids.extend(rankings.ranking.into_iter().map(|user| user.user_id))
                     }
                     Err(err) => {
@@ -100,4 +189,42 @@ impl Context {
 
         info!("Finished requesting {max_page} leaderboard pages for all modes");
     }
+
+    async fn request_rankings(&self, mode: GameMode, page: u32) -> RequestResult<Rankings> {
+        let entry = CassetteEntry::Rankings(mode, page);
+
+        if let Some(cassette) = self.replaying() {
+            return cassette.replay(&entry).map_err(RequestError::Cassette);
+        }
+
+        let start = Instant::now();
+        let res = self.osu().performance_rankings(mode).page(page).await;
+        METRICS.observe_osu_request(OsuEndpoint::Rankings, start.elapsed());
+        let rankings = res?;
+
+        if let Some(ref cassette) = self.cassette {
+            cassette.record(&entry, &rankings);
+        }
+
+        Ok(rankings)
+    }
+
+    fn osu(&self) -> &Osu {
+        self.osu
+            .as_ref()
+            .expect("osu client is only missing when replaying")
+    }
+}
+
+/// Whether the error is "http2 error: connection error received: not a result
+/// of an error" in which case the request should be retried.
+///
+/// See <https://github.com/hyperium/hyper/issues/2500>
+fn is_http2_error(err: &OsuError) -> bool {
+    match err {
+        OsuError::Request { source } => source
+            .source()
+            .is_some_and(|err| err.to_string().starts_with("http2 error")),
+        _ => false,
+    }
 }
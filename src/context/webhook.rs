@@ -10,7 +10,7 @@ impl Context {
             error!(?err, "Failed to notify webhook of progress");
         }
 
-        self.mysql.store_progress(progress).await
+        self.storage.store_progress(progress).await
     }
 
     pub async fn handle_finish(&self, finish: Finish) -> Result<()> {
@@ -18,6 +18,6 @@ impl Context {
             error!(?err, "Failed to notify webhook of finish");
         }
 
-        self.mysql.store_finish(&finish).await
+        self.storage.store_finish(&finish).await
     }
 }
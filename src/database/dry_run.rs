@@ -0,0 +1,419 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    num::NonZeroU32,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use eyre::{Context as _, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::model::{
+    BadgeImageUrl, BadgeName, BadgeRow, BadgesDiff, Finish, MedalRarities, MedalRarityEntry,
+    Progress, RankingUser, RankingsIter, RunReport, ScrapedMedal, UserFailures,
+};
+
+/// Instead of executing statements, writes every row that would be
+/// upserted, updated, or deleted into a file as newline-delimited JSON.
+#[derive(Clone)]
+pub struct DryRun {
+    writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+#[derive(Copy, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    Insert,
+    Upsert,
+    Update,
+    Delete,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    table: &'a str,
+    action: Action,
+    row: Value,
+}
+
+impl DryRun {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to create dry run file `{}`", path.display()))?;
+
+        info!("Dry run: database writes will go to `{}`", path.display());
+
+        Ok(Self {
+            writer: Arc::new(Mutex::new(BufWriter::new(file))),
+        })
+    }
+
+    /// Write all rows and return how many were written.
+    fn write(
+        &self,
+        table: &str,
+        action: Action,
+        rows: impl IntoIterator<Item = Value>,
+    ) -> Result<usize> {
+        let mut writer = self.writer.lock().unwrap();
+        let mut count = 0;
+
+        for row in rows {
+            let entry = Entry { table, action, row };
+
+            serde_json::to_writer(&mut *writer, &entry)
+                .context("failed to serialize dry run entry")?;
+
+            writer
+                .write_all(b"\n")
+                .context("failed to write dry run entry")?;
+
+            count += 1;
+        }
+
+        writer.flush().context("failed to flush dry run file")?;
+
+        Ok(count)
+    }
+
+    pub fn store_progress(&self, progress: &Progress) -> Result<()> {
+        let Progress {
+            start,
+            current,
+            total,
+            eta_seconds,
+            task,
+            resumed: _,
+            cancelled: _,
+        } = progress;
+
+        let row = json!({
+            "ID": start.unix_timestamp(),
+            "Type": task.to_string(),
+            "Time": datetime(*start),
+            "Count_Current": current,
+            "Count_Total": total,
+            "Elapsed_Seconds": eta_seconds,
+        });
+
+        self.write("Rankings_Script_History", Action::Upsert, [row])
+            .map(|_| ())
+    }
+
+    pub fn store_finish(&self, finish: &Finish) -> Result<()> {
+        let Finish {
+            id,
+            requested_users,
+            total_users,
+            cancelled: _,
+        } = finish;
+
+        let row = json!({
+            "ID": id,
+            "Count_Current": requested_users,
+            "Count_Total": total_users,
+            "Elapsed_Seconds": 0,
+        });
+
+        self.write("Rankings_Script_History", Action::Update, [row])
+            .map(|_| ())
+    }
+
+    pub fn store_report(&self, report: &RunReport) -> Result<()> {
+        let RunReport {
+            history_id,
+            task,
+            start,
+            end,
+            cancelled,
+            durations: _,
+            requested_users,
+            failed_users,
+            restricted_users,
+            new_medals,
+            badges,
+            rows: _,
+            errors: _,
+            omitted_errors: _,
+        } = report;
+
+        let parse = |json: String| serde_json::from_str(&json).unwrap_or(Value::Null);
+
+        let row = json!({
+            "History_ID": history_id,
+            "Type": task.to_string(),
+            "Started_At": datetime(*start),
+            "Finished_At": datetime(*end),
+            "Is_Cancelled": *cancelled as u8,
+            "Count_Requested": requested_users,
+            "Count_Failed": failed_users,
+            "Count_Restricted": restricted_users,
+            "Count_New_Medals": new_medals,
+            "Count_New_Badges": badges.new_badges,
+            "Count_Badge_Owners_Added": badges.owners_added,
+            "Count_Badge_Owners_Updated": badges.owners_updated,
+            "Count_Badge_Owners_Removed": badges.owners_removed,
+            "Count_Rows_Written": report.total_rows(),
+            "Durations": parse(report.durations_json()),
+            "Rows_Written": parse(report.rows_json()),
+            "Errors": parse(report.errors_json()),
+        });
+
+        self.write("Rankings_Script_Runs", Action::Insert, [row])
+            .map(|_| ())
+    }
+
+    pub fn store_rankings(&self, rankings: RankingsIter) -> Result<usize> {
+        let rows = rankings.map(|ranking| {
+            let stdev_acc = ranking.std_dev_acc();
+            let stdev_level = ranking.std_dev_level();
+            let stdev_pp = ranking.std_dev_pp();
+            let total_pp = ranking.total_pp();
+
+            let RankingUser {
+                id,
+                name,
+                ignore_acc,
+                medal_count,
+                rarest_medal_id,
+                rarest_medal_achieved,
+                country_code,
+                badge_count,
+                ranked_maps,
+                loved_maps,
+                subscribers,
+                replays_watched,
+                restricted,
+                std,
+                tko,
+                ctb,
+                mna,
+            } = ranking;
+
+            let acc = |acc: f32| if ignore_acc { 0.0 } else { acc };
+
+            json!({
+                "ID": id,
+                "Accuracy_Catch": acc(ctb.acc),
+                "Accuracy_Mania": acc(mna.acc),
+                "Accuracy_Standard": acc(std.acc),
+                "Accuracy_Stdev": stdev_acc,
+                "Accuracy_Taiko": acc(tko.acc),
+                "Count_Badges": badge_count,
+                "Count_Maps_Loved": loved_maps,
+                "Count_Maps_Ranked": ranked_maps,
+                "Count_Medals": medal_count,
+                "Count_Replays_Watched": replays_watched,
+                "Count_Subscribers": subscribers,
+                "Country_Code": country_code,
+                "Is_Restricted": restricted as u8,
+                "Level_Catch": ctb.level,
+                "Level_Mania": mna.level,
+                "Level_Standard": std.level,
+                "Level_Stdev": stdev_level,
+                "Level_Taiko": tko.level,
+                "Name": name,
+                "PP_Catch": ctb.pp,
+                "PP_Mania": mna.pp,
+                "PP_Standard": std.pp,
+                "PP_Stdev": stdev_pp,
+                "PP_Taiko": tko.pp,
+                "PP_Total": total_pp,
+                "Rank_Global_Catch": ctb.global_rank.map(NonZeroU32::get),
+                "Rank_Global_Mania": mna.global_rank.map(NonZeroU32::get),
+                "Rank_Global_Standard": std.global_rank.map(NonZeroU32::get),
+                "Rank_Global_Taiko": tko.global_rank.map(NonZeroU32::get),
+                "Rarest_Medal_Achieved": datetime(rarest_medal_achieved),
+                "Rarest_Medal_ID": rarest_medal_id,
+            })
+        });
+
+        self.write("Rankings_Users", Action::Upsert, rows)
+    }
+
+    pub fn store_medals(&self, medals: &[ScrapedMedal]) -> Result<usize> {
+        let rows = medals.iter().map(|medal| {
+            let ScrapedMedal {
+                icon_url,
+                id,
+                name,
+                grouping,
+                ordering,
+                description,
+                mode,
+                instructions,
+            } = medal;
+
+            let link = icon_url.rsplit('/').next().unwrap_or(icon_url);
+
+            json!({
+                "Medal_ID": id,
+                "Name": name,
+                "Link": link,
+                "Description": description,
+                "Gamemode": mode,
+                "Grouping": grouping,
+                "Instructions": instructions,
+                "Ordering": ordering,
+            })
+        });
+
+        self.write("Medals_Data", Action::Upsert, rows)
+    }
+
+    pub fn store_rarities(&self, rarities: &MedalRarities) -> Result<usize> {
+        let rows = rarities
+            .iter()
+            .map(|(medal_id, MedalRarityEntry { count, frequency })| {
+                json!({
+                    "Medal_ID": medal_id,
+                    "Frequency": frequency,
+                    "Count_Achieved_By": count,
+                })
+            });
+
+        self.write("Medals_Data", Action::Update, rows)
+    }
+
+    pub fn store_badges(&self, diff: &BadgesDiff) -> Result<usize> {
+        let names = diff
+            .names
+            .iter()
+            .map(|(BadgeName(name), BadgeImageUrl(image_url))| {
+                json!({
+                    "Name": name,
+                    "Image_URL": image_url,
+                })
+            });
+
+        let upserts = diff.upserts.iter().map(|owner| {
+            let BadgeRow {
+                name,
+                user_id,
+                description,
+                awarded_at,
+            } = owner;
+
+            json!({
+                "Name": name,
+                "User_ID": user_id,
+                "Description": description,
+                "Date_Awarded": datetime(*awarded_at),
+            })
+        });
+
+        let removals = diff.removals.iter().map(|(name, user_id)| {
+            json!({
+                "Name": name,
+                "User_ID": user_id,
+            })
+        });
+
+        let data_count = self.write("Badges_Data", Action::Upsert, names)?;
+        let upsert_count = self.write("Badge_Name", Action::Upsert, upserts)?;
+        let delete_count = self.write("Badge_Name", Action::Delete, removals)?;
+
+        Ok(data_count + upsert_count + delete_count)
+    }
+
+    pub fn store_failures(&self, failures: &UserFailures, resolved: &[u32]) -> Result<usize> {
+        let rows = failures.iter().map(|(user_id, kind)| {
+            json!({
+                "User_ID": user_id,
+                "Error_Kind": kind.as_str(),
+            })
+        });
+
+        let removals = resolved.iter().map(|user_id| json!({ "User_ID": user_id }));
+
+        let upsert_count = self.write("Rankings_Users_Failures", Action::Upsert, rows)?;
+        let delete_count = self.write("Rankings_Users_Failures", Action::Delete, removals)?;
+
+        Ok(upsert_count + delete_count)
+    }
+}
+
+fn datetime(datetime: OffsetDateTime) -> Value {
+    match datetime.format(&Rfc3339) {
+        Ok(formatted) => Value::String(formatted),
+        Err(_) => Value::from(datetime.unix_timestamp()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::model::{FailureKind, UserFailures};
+
+    use super::*;
+
+    #[test]
+    fn ndjson_entries() {
+        let path = std::env::temp_dir().join("osekai-scripts-dry-run-entries.ndjson");
+        let dry_run = DryRun::create(&path).unwrap();
+
+        let medal = ScrapedMedal {
+            icon_url: Box::from("https://assets.ppy.sh/medals/web/all-secret-jackpot.png"),
+            id: 171,
+            name: Box::from("Jackpot"),
+            grouping: Box::from("Hush-Hush"),
+            ordering: 0,
+            description: Box::from("Hit the jackpot."),
+            mode: None,
+            instructions: None,
+        };
+
+        let diff = BadgesDiff {
+            removals: vec![(Box::from("contributor"), 2)],
+            ..Default::default()
+        };
+
+        let mut failures = UserFailures::default();
+        failures.insert(3, FailureKind::Timeout);
+
+        assert_eq!(dry_run.store_medals(&[medal]).unwrap(), 1);
+        assert_eq!(dry_run.store_badges(&diff).unwrap(), 1);
+        assert_eq!(dry_run.store_failures(&failures, &[4]).unwrap(), 2);
+
+        let content = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let entries: Vec<Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(entries.len(), 4);
+
+        assert_eq!(entries[0]["table"], "Medals_Data");
+        assert_eq!(entries[0]["action"], "upsert");
+        assert_eq!(entries[0]["row"]["Medal_ID"], 171);
+        assert_eq!(entries[0]["row"]["Link"], "all-secret-jackpot.png");
+        assert_eq!(entries[0]["row"]["Gamemode"], Value::Null);
+
+        assert_eq!(
+            entries[1],
+            json!({
+                "table": "Badge_Name",
+                "action": "delete",
+                "row": { "Name": "contributor", "User_ID": 2 },
+            })
+        );
+
+        assert_eq!(
+            entries[2],
+            json!({
+                "table": "Rankings_Users_Failures",
+                "action": "upsert",
+                "row": { "User_ID": 3, "Error_Kind": FailureKind::Timeout.as_str() },
+            })
+        );
+
+        assert_eq!(entries[3]["action"], "delete");
+        assert_eq!(entries[3]["row"], json!({ "User_ID": 4 }));
+    }
+}
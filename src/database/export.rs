@@ -0,0 +1,143 @@
+use eyre::{Context as _, Result};
+use futures_util::TryStreamExt;
+use serde_json::Value;
+use sqlx::{
+    mysql::{MySql, MySqlColumn, MySqlRow},
+    Column, Database as SqlxDatabase, Encode, Executor, QueryBuilder, Row, Statement, Type,
+    TypeInfo,
+};
+use time::{format_description::well_known::Rfc3339, Date, PrimitiveDateTime};
+
+use super::Database;
+
+/// Rows of a table or subquery that should be exported.
+pub struct ExportQuery<'a> {
+    /// Table name or aliased subquery
+    pub source: &'a str,
+    /// Columns to select; all columns if empty
+    pub columns: &'a [String],
+    pub country: Option<&'a str>,
+    pub restricted: Option<bool>,
+}
+
+impl<'a> ExportQuery<'a> {
+    /// The select statement of the query; identifiers are quoted with
+    /// backticks which both MySQL and SQLite understand.
+    pub(super) fn build<DB>(&self) -> Result<QueryBuilder<'a, DB>>
+    where
+        DB: SqlxDatabase,
+        &'a str: Encode<'a, DB> + Type<DB>,
+        u8: Encode<'a, DB> + Type<DB>,
+    {
+        let mut builder = QueryBuilder::<DB>::new("SELECT ");
+
+        if self.columns.is_empty() {
+            builder.push("*");
+        } else {
+            for (column, i) in self.columns.iter().zip(0..) {
+                ensure!(
+                    column
+                        .chars()
+                        .all(|ch| ch.is_ascii_alphanumeric() || ch == '_'),
+                    "invalid column name `{column}`"
+                );
+
+                if i > 0 {
+                    builder.push(", ");
+                }
+
+                builder.push(format_args!("`{column}`"));
+            }
+        }
+
+        builder
+            .push(" FROM ")
+            .push(self.source)
+            .push(" WHERE 1 = 1");
+
+        if let Some(country) = self.country {
+            builder.push(" AND `Country_Code` = ").push_bind(country);
+        }
+
+        if let Some(restricted) = self.restricted {
+            builder
+                .push(" AND `Is_Restricted` = ")
+                .push_bind(restricted as u8);
+        }
+
+        Ok(builder)
+    }
+}
+
+impl Database {
+    /// Stream all rows of the query and pass their column names and values
+    /// to `on_row`. Returns the amount of rows.
+    pub async fn export_rows(
+        &self,
+        query: &ExportQuery<'_>,
+        mut on_row: impl FnMut(&[&str], Vec<Value>) -> Result<()>,
+    ) -> Result<usize> {
+        let mut builder = query.build()?;
+        let mut rows = builder.build().fetch(&self.mysql);
+        let mut count = 0;
+
+        while let Some(row) = rows.try_next().await.context("failed to fetch row")? {
+            let columns = row.columns();
+            let names: Vec<_> = columns.iter().map(Column::name).collect();
+
+            let values = columns
+                .iter()
+                .map(|column| column_value(&row, column))
+                .collect::<Result<_>>()?;
+
+            on_row(&names, values)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Column names of the query's rows, e.g. for the header of an export
+    /// without rows.
+    pub async fn export_columns(&self, query: &ExportQuery<'_>) -> Result<Vec<String>> {
+        let builder = query.build::<MySql>()?;
+
+        let statement = (&self.mysql)
+            .prepare(builder.sql())
+            .await
+            .context("failed to prepare export query")?;
+
+        let names = statement.columns().iter().map(Column::name);
+
+        Ok(names.map(str::to_owned).collect())
+    }
+}
+
+fn column_value(row: &MySqlRow, column: &MySqlColumn) -> Result<Value> {
+    let idx = column.ordinal();
+    let type_name = column.type_info().name();
+
+    let value = match type_name {
+        "BOOLEAN" | "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" => {
+            row.try_get::<Option<i64>, _>(idx)?.map(Value::from)
+        }
+        _ if type_name.ends_with("UNSIGNED") => {
+            row.try_get::<Option<u64>, _>(idx)?.map(Value::from)
+        }
+        "FLOAT" | "DOUBLE" => row.try_get::<Option<f64>, _>(idx)?.map(Value::from),
+        "DATETIME" | "TIMESTAMP" => row
+            .try_get::<Option<PrimitiveDateTime>, _>(idx)?
+            .map(|datetime| datetime.assume_utc().format(&Rfc3339))
+            .transpose()?
+            .map(Value::String),
+        "DATE" => row
+            .try_get::<Option<Date>, _>(idx)?
+            .map(|date| Value::String(date.to_string())),
+        // Strings as well as decimals are transmitted as text
+        _ => row
+            .try_get_unchecked::<Option<String>, _>(idx)?
+            .map(Value::String),
+    };
+
+    Ok(value.unwrap_or(Value::Null))
+}
@@ -3,12 +3,15 @@ use std::{
     ops::DerefMut,
 };
 
-use eyre::{Context as _, Result};
+use eyre::{Context as _, Report, Result};
 use futures_util::{future, TryStreamExt};
+use sqlx::{mysql::MySqlRow, Row};
 use time::OffsetDateTime;
 
 use crate::{
-    model::{BadgeDescription, BadgeImageUrl, BadgeName, BadgeOwner, Badges, MedalRarities},
+    model::{
+        BadgeDescription, BadgeImageUrl, BadgeName, BadgeOwner, Badges, HistoryEntry, MedalRarities,
+    },
     util::IntHasher,
 };
 
@@ -99,6 +102,7 @@ impl Database {
         let mut stored = Badges {
             names: names_fut.await.context("failed to fetch badges data")?,
             descriptions: HashMap::default(),
+            checked_users: HashSet::default(),
         };
 
         let query = sqlx::query!(
@@ -202,4 +206,61 @@ impl Database {
             .await
             .context("failed to fetch all medal ids")
     }
+
+    pub async fn fetch_failed_user_ids(&self) -> Result<HashSet<u32, IntHasher>> {
+        sqlx::query("SELECT `User_ID` FROM `Rankings_Users_Failures`")
+            .fetch(&self.mysql)
+            .map_ok(|row| row.get::<i32, _>(0) as u32)
+            .try_collect()
+            .await
+            .context("failed to fetch failed user ids")
+    }
+
+    /// The most recent runs, newest first.
+    pub async fn fetch_history(&self, limit: usize) -> Result<Vec<HistoryEntry>> {
+        let query = sqlx::query(
+            r#"
+SELECT
+  history.`ID`, history.`Type`, history.`Time`, history.`Count_Current`,
+  history.`Count_Total`, history.`Elapsed_Seconds`, history.`Elapsed_Last_Update`,
+  (
+    SELECT MAX(runs.`Is_Cancelled`)
+    FROM `Rankings_Script_Runs` AS runs
+    WHERE runs.`History_ID` = history.`ID`
+  ) AS `Is_Cancelled`
+FROM
+  `Rankings_Script_History` AS history
+ORDER BY
+  history.`ID` DESC
+LIMIT
+  ?"#,
+        );
+
+        query
+            .bind(limit as i64)
+            .fetch(&self.mysql)
+            .map_err(Report::new)
+            .and_then(|row| future::ready(history_entry(&row)))
+            .try_collect()
+            .await
+            .context("failed to fetch script history")
+    }
+}
+
+fn history_entry(row: &MySqlRow) -> Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.try_get(0)?,
+        task: row
+            .try_get::<Option<String>, _>(1)?
+            .unwrap_or_default()
+            .into(),
+        start: row.try_get(2)?,
+        current: row.try_get::<Option<i64>, _>(3)?.map(|count| count as u32),
+        total: row.try_get::<Option<i64>, _>(4)?.map(|count| count as u32),
+        eta_seconds: row.try_get(5)?,
+        last_update: row.try_get(6)?,
+        is_cancelled: row
+            .try_get::<Option<i64>, _>(7)?
+            .map(|cancelled| cancelled != 0),
+    })
 }
@@ -0,0 +1,457 @@
+use std::{collections::HashSet, num::NonZeroU32, sync::Arc, time::Instant};
+
+use eyre::{Context as _, Result};
+use futures_util::future::BoxFuture;
+use hyper::Uri;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::{
+    client::Client,
+    model::{
+        Badges, BadgesDiff, Finish, HistoryEntry, MedalRarities, MedalRarityEntry, Progress,
+        RankingUser, RankingsIter, RunReport, ScrapedMedal, UserFailures,
+    },
+    util::IntHasher,
+};
+
+use super::{rows_per_sec, DryRun, Fetchable, Storage, StoreResult};
+
+/// Uploads data through the osekai API described in `API.md` instead of
+/// writing into a database.
+///
+/// The API has no endpoints for badges, failures, progress, run reports, or
+/// reading rarities so those are either skipped or fail; see
+/// [`Storage::fetchable`].
+#[derive(Clone)]
+pub struct HttpStorage {
+    inner: Arc<HttpInner>,
+}
+
+struct HttpInner {
+    client: Client,
+    /// Url that the endpoint names are appended to
+    base_url: Box<str>,
+    /// Maximum amount of rankings per upload
+    batch_size: usize,
+    /// If set, uploads go into a file instead.
+    dry_run: Option<DryRun>,
+}
+
+impl HttpStorage {
+    /// Prefix of the url that selects the API; the remainder is the API root.
+    pub const SCHEME: &'static str = "api+";
+
+    pub fn new(url: &str, batch_size: usize, dry_run: Option<DryRun>) -> Self {
+        let url = url.strip_prefix(Self::SCHEME).unwrap_or(url);
+
+        let base_url = if url.ends_with('/') {
+            Box::from(url)
+        } else {
+            format!("{url}/").into_boxed_str()
+        };
+
+        let inner = HttpInner {
+            client: Client::new(false),
+            base_url,
+            batch_size: batch_size.max(1),
+            dry_run,
+        };
+
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl HttpInner {
+    fn url(&self, endpoint: &str) -> Result<Uri> {
+        format!("{}{endpoint}", self.base_url)
+            .parse()
+            .with_context(|| format!("invalid url for endpoint `{endpoint}`"))
+    }
+
+    async fn post<T: Serialize + ?Sized>(&self, endpoint: &str, data: &T) -> Result<()> {
+        let body = serde_json::to_vec(data)
+            .with_context(|| format!("failed to serialize data for `{endpoint}`"))?;
+
+        self.client
+            .send_json_post_request(self.url(endpoint)?, body)
+            .await
+            .with_context(|| format!("failed to upload to `{endpoint}`"))?;
+
+        Ok(())
+    }
+
+    async fn fetch_members(&self) -> Result<HashSet<u32, IntHasher>> {
+        /// Member ids might be given as numbers or strings
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum MemberId {
+            Int(u32),
+            Str(String),
+        }
+
+        let bytes = self
+            .client
+            .send_get_request(self.url("down_members.php")?)
+            .await
+            .context("failed to request members")?;
+
+        let members: Vec<MemberId> = serde_json::from_slice(&bytes)
+            .with_context(|| format!("failed to deserialize members: {bytes:?}"))?;
+
+        members
+            .into_iter()
+            .map(|member| match member {
+                MemberId::Int(user_id) => Ok(user_id),
+                MemberId::Str(user_id) => user_id
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("invalid member id `{user_id}`")),
+            })
+            .collect()
+    }
+
+    async fn store_rankings(&self, rankings: RankingsIter) -> Result<usize> {
+        if let Some(ref dry_run) = self.dry_run {
+            return dry_run.store_rankings(rankings);
+        }
+
+        let mut rankings = rankings.peekable();
+        let mut stored = 0;
+
+        while rankings.peek().is_some() {
+            // The API has no notion of restricted users
+            let chunk: Vec<_> = rankings
+                .by_ref()
+                .take(self.batch_size)
+                .filter(|ranking| !ranking.restricted)
+                .map(RankingUpload::new)
+                .collect();
+
+            // Chunks that consist only of restricted users
+            if chunk.is_empty() {
+                continue;
+            }
+
+            self.post("up_ranking.php", &chunk)
+                .await
+                .with_context(|| format!("failed to upload chunk after {stored} rankings"))?;
+
+            stored += chunk.len();
+        }
+
+        Ok(stored)
+    }
+
+    async fn store_medals(&self, medals: &[ScrapedMedal]) -> Result<usize> {
+        if let Some(ref dry_run) = self.dry_run {
+            return dry_run.store_medals(medals);
+        }
+
+        let medals: Vec<_> = medals.iter().map(MedalUpload::new).collect();
+        self.post("up_medals.php", &medals).await?;
+
+        Ok(medals.len())
+    }
+
+    async fn store_rarities(&self, rarities: &MedalRarities) -> Result<usize> {
+        if let Some(ref dry_run) = self.dry_run {
+            return dry_run.store_rarities(rarities);
+        }
+
+        let rarities: Vec<_> = rarities
+            .iter()
+            .map(
+                |(medal_id, MedalRarityEntry { count, frequency })| RarityUpload {
+                    medalid: *medal_id,
+                    frequency: *frequency,
+                    count: *count,
+                },
+            )
+            .collect();
+
+        self.post("up_medals_rarity.php", &rarities).await?;
+
+        Ok(rarities.len())
+    }
+
+    async fn store_finish(&self, finish: &Finish) -> Result<()> {
+        if let Some(ref dry_run) = self.dry_run {
+            return dry_run.store_finish(finish);
+        }
+
+        self.client
+            .send_get_request(self.url("finish.php")?)
+            .await
+            .context("failed to notify finish")?;
+
+        Ok(())
+    }
+}
+
+impl Storage for HttpStorage {
+    fn fetchable(&self) -> Fetchable {
+        // Only members can be fetched
+        Fetchable {
+            ranking_ids: false,
+            badges: false,
+            rarities: false,
+            medal_ids: false,
+        }
+    }
+
+    fn fetch_osekai_ranking_ids<'a>(
+        &'a self,
+        _: &'a mut HashSet<u32, IntHasher>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { bail!("the osekai API does not provide ranking ids") })
+    }
+
+    fn fetch_osekai_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>> {
+        Box::pin(self.inner.fetch_members())
+    }
+
+    fn fetch_badges(&self) -> BoxFuture<'_, Result<Badges>> {
+        Box::pin(async { bail!("the osekai API does not provide badges") })
+    }
+
+    fn fetch_medal_rarities(&self) -> BoxFuture<'_, Result<MedalRarities>> {
+        Box::pin(async { bail!("the osekai API does not provide medal rarities") })
+    }
+
+    fn fetch_medal_ids(&self) -> BoxFuture<'_, Result<HashSet<u16, IntHasher>>> {
+        Box::pin(async { bail!("the osekai API does not provide medal ids") })
+    }
+
+    fn fetch_failed_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>> {
+        // Failures are not uploaded so none are stored either
+        Box::pin(async { Ok(HashSet::default()) })
+    }
+
+    fn fetch_history(&self, _: usize) -> BoxFuture<'_, Result<Vec<HistoryEntry>>> {
+        Box::pin(async { bail!("the osekai API does not provide the script history") })
+    }
+
+    fn pending_migrations(&self) -> BoxFuture<'_, Result<Vec<&'static str>>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn migrate(&self) -> BoxFuture<'_, Result<Vec<&'static str>>> {
+        Box::pin(async { bail!("the osekai API has no schema to migrate") })
+    }
+
+    fn store_progress<'a>(&'a self, progress: &'a Progress) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async {
+            match self.inner.dry_run {
+                Some(ref dry_run) => dry_run.store_progress(progress),
+                // Progress only goes to the webhook
+                None => Ok(()),
+            }
+        })
+    }
+
+    fn store_finish<'a>(&'a self, finish: &'a Finish) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.inner.store_finish(finish))
+    }
+
+    fn store_report<'a>(&'a self, report: &'a RunReport) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async {
+            match self.inner.dry_run {
+                Some(ref dry_run) => dry_run.store_report(report),
+                None => Ok(()),
+            }
+        })
+    }
+
+    fn store_rankings(&self, rankings: RankingsIter) -> JoinHandle<StoreResult> {
+        let inner = Arc::clone(&self.inner);
+
+        tokio::spawn(async move {
+            let start = Instant::now();
+            let res = inner.store_rankings(rankings).await;
+            let _entered = info_span!("store_rankings").entered();
+
+            match res {
+                Ok(len) => info!(
+                    "Successfully uploaded {len} ranking entries ({:.0} rows/s)",
+                    rows_per_sec(len, start.elapsed())
+                ),
+                Err(ref err) => error!(?err, "Failed to upload rankings"),
+            }
+
+            res.map_err(|err| format!("{err:#}"))
+        })
+    }
+
+    fn store_medals<'a>(&'a self, medals: &'a [ScrapedMedal]) -> BoxFuture<'a, StoreResult> {
+        Box::pin(async {
+            let res = self.inner.store_medals(medals).await;
+            let _entered = info_span!("store_medals").entered();
+
+            match res {
+                Ok(len) => info!("Successfully uploaded {len} medals"),
+                Err(ref err) => error!(?err, "Failed to upload medals"),
+            }
+
+            res.map_err(|err| format!("{err:#}"))
+        })
+    }
+
+    fn store_rarities(&self, rarities: MedalRarities) -> JoinHandle<StoreResult> {
+        let inner = Arc::clone(&self.inner);
+
+        tokio::spawn(async move {
+            let res = inner.store_rarities(&rarities).await;
+            let _entered = info_span!("store_rarities").entered();
+
+            match res {
+                Ok(len) => info!("Successfully uploaded {len} medal rarities"),
+                Err(ref err) => error!(?err, "Failed to upload rarities"),
+            }
+
+            res.map_err(|err| format!("{err:#}"))
+        })
+    }
+
+    fn store_badges(&self, _: BadgesDiff) -> JoinHandle<StoreResult> {
+        tokio::spawn(async {
+            warn!("The osekai API does not support uploading badges");
+
+            Ok(0)
+        })
+    }
+
+    fn store_failures<'a>(
+        &'a self,
+        failures: &'a UserFailures,
+        resolved: &'a [u32],
+    ) -> BoxFuture<'a, StoreResult> {
+        Box::pin(async {
+            let Some(ref dry_run) = self.inner.dry_run else {
+                return Ok(0);
+            };
+
+            dry_run.store_failures(failures, resolved).map_err(|err| {
+                error!(?err, "Failed to store user failures");
+
+                format!("{err:#}")
+            })
+        })
+    }
+}
+
+/// Shape of `up_medals.php` entries
+#[derive(Serialize)]
+struct MedalUpload<'a> {
+    medalid: u16,
+    name: &'a str,
+    link: &'a str,
+    description: &'a str,
+    restriction: Option<&'a str>,
+    grouping: &'a str,
+    instructions: Option<&'a str>,
+    ordering: u8,
+}
+
+impl<'a> MedalUpload<'a> {
+    fn new(medal: &'a ScrapedMedal) -> Self {
+        Self {
+            medalid: medal.id,
+            name: &medal.name,
+            link: &medal.icon_url,
+            description: &medal.description,
+            restriction: medal.mode.as_deref(),
+            grouping: &medal.grouping,
+            instructions: medal.instructions.as_deref(),
+            ordering: medal.ordering,
+        }
+    }
+}
+
+/// Shape of `up_medals_rarity.php` entries
+#[derive(Serialize)]
+struct RarityUpload {
+    medalid: u16,
+    frequency: f32,
+    count: u32,
+}
+
+/// Shape of `up_ranking.php` entries; all values are strings
+#[derive(Serialize)]
+struct RankingUpload {
+    id: String,
+    name: Box<str>,
+    total_pp: String,
+    stdev_pp: String,
+    standard_pp: String,
+    taiko_pp: String,
+    ctb_pp: String,
+    mania_pp: String,
+    medal_count: String,
+    rarest_medal: String,
+    country_code: Box<str>,
+    standard_global: String,
+    taiko_global: String,
+    ctb_global: String,
+    mania_global: String,
+    badge_count: String,
+    ranked_maps: String,
+    loved_maps: String,
+    subscribers: String,
+    replays_watched: String,
+    avatar_url: String,
+}
+
+impl RankingUpload {
+    fn new(ranking: RankingUser) -> Self {
+        let total_pp = format!("{:.0}", ranking.total_pp());
+        let stdev_pp = format!("{:.0}", ranking.std_dev_pp());
+
+        let RankingUser {
+            id,
+            name,
+            medal_count,
+            rarest_medal_id,
+            country_code,
+            badge_count,
+            ranked_maps,
+            loved_maps,
+            subscribers,
+            replays_watched,
+            std,
+            tko,
+            ctb,
+            mna,
+            ..
+        } = ranking;
+
+        let rank =
+            |rank: Option<NonZeroU32>| rank.map_or_else(String::new, |rank| rank.to_string());
+
+        Self {
+            id: id.to_string(),
+            name,
+            total_pp,
+            stdev_pp,
+            standard_pp: format!("{:.0}", std.pp),
+            taiko_pp: format!("{:.0}", tko.pp),
+            ctb_pp: format!("{:.0}", ctb.pp),
+            mania_pp: format!("{:.0}", mna.pp),
+            medal_count: medal_count.to_string(),
+            rarest_medal: rarest_medal_id.to_string(),
+            country_code,
+            standard_global: rank(std.global_rank),
+            taiko_global: rank(tko.global_rank),
+            ctb_global: rank(ctb.global_rank),
+            mania_global: rank(mna.global_rank),
+            badge_count: badge_count.to_string(),
+            ranked_maps: ranked_maps.to_string(),
+            loved_maps: loved_maps.to_string(),
+            subscribers: subscribers.to_string(),
+            replays_watched: replays_watched.to_string(),
+            avatar_url: format!("https://a.ppy.sh/{id}"),
+        }
+    }
+}
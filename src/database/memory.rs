@@ -0,0 +1,325 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use eyre::Result;
+use futures_util::future::BoxFuture;
+use tokio::task::JoinHandle;
+
+use crate::{
+    model::{
+        BadgeDescription, BadgeName, BadgeOwner, BadgeRow, Badges, BadgesDiff, FailureKind, Finish,
+        HistoryEntry, MedalRarities, MedalRarityEntry, Progress, RankingUser, RankingsIter,
+        RunReport, ScrapedMedal, UserFailures,
+    },
+    util::IntHasher,
+};
+
+use super::{Fetchable, Storage, StoreResult};
+
+/// Storage that keeps everything in memory so that it can be prepared
+/// before and inspected after a run.
+#[derive(Clone, Default)]
+pub struct InMemoryStorage {
+    data: Arc<Mutex<MemoryData>>,
+}
+
+/// Everything that was stored in an [`InMemoryStorage`].
+#[derive(Default)]
+pub struct MemoryData {
+    pub osekai_user_ids: HashSet<u32, IntHasher>,
+    pub rankings: HashMap<u32, RankingUser, IntHasher>,
+    pub medals: HashMap<u16, ScrapedMedal, IntHasher>,
+    pub rarities: MedalRarities,
+    pub badges: Badges,
+    /// Every progress update in order
+    pub progress: Vec<Progress>,
+    pub finish: Option<Finish>,
+    /// Report of every run in order
+    pub reports: Vec<RunReport>,
+    /// Users that failed to be requested mapped to the kind of their last
+    /// error and how often they failed
+    pub failures: HashMap<u32, (FailureKind, u32), IntHasher>,
+}
+
+impl InMemoryStorage {
+    /// Database url that selects the in-memory storage
+    pub const URL: &'static str = "memory:";
+
+    pub fn data(&self) -> MutexGuard<'_, MemoryData> {
+        self.data.lock().unwrap()
+    }
+
+    /// Log how much data was stored.
+    pub fn log_summary(&self) {
+        let data = self.data();
+
+        info!(
+            rankings = data.rankings.len(),
+            medals = data.medals.len(),
+            rarities = data.rarities.len(),
+            badges = data.badges.names.len(),
+            progress_updates = data.progress.len(),
+            failures = data.failures.len(),
+            finished = data.finish.is_some(),
+            reports = data.reports.len(),
+            "In-memory storage summary"
+        );
+    }
+
+    /// Run `f` on the data in a background task; `f` returns the amount
+    /// of stored rows.
+    fn spawn(
+        &self,
+        f: impl FnOnce(&mut MemoryData) -> usize + Send + 'static,
+    ) -> JoinHandle<StoreResult> {
+        let data = Arc::clone(&self.data);
+
+        tokio::spawn(async move { Ok(f(&mut data.lock().unwrap())) })
+    }
+}
+
+impl Storage for InMemoryStorage {
+    fn fetchable(&self) -> Fetchable {
+        Fetchable::ALL
+    }
+
+    fn fetch_osekai_ranking_ids<'a>(
+        &'a self,
+        user_ids: &'a mut HashSet<u32, IntHasher>,
+    ) -> BoxFuture<'a, Result<()>> {
+        user_ids.extend(self.data().rankings.keys());
+
+        Box::pin(async { Ok(()) })
+    }
+
+    fn fetch_osekai_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>> {
+        let user_ids = self.data().osekai_user_ids.clone();
+
+        Box::pin(async { Ok(user_ids) })
+    }
+
+    fn fetch_badges(&self) -> BoxFuture<'_, Result<Badges>> {
+        let badges = self.data().badges.clone();
+
+        Box::pin(async { Ok(badges) })
+    }
+
+    fn fetch_medal_rarities(&self) -> BoxFuture<'_, Result<MedalRarities>> {
+        let rarities = self.data().rarities.clone();
+
+        Box::pin(async { Ok(rarities) })
+    }
+
+    fn fetch_medal_ids(&self) -> BoxFuture<'_, Result<HashSet<u16, IntHasher>>> {
+        let medal_ids = self.data().medals.keys().copied().collect();
+
+        Box::pin(async { Ok(medal_ids) })
+    }
+
+    fn fetch_failed_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>> {
+        let user_ids = self.data().failures.keys().copied().collect();
+
+        Box::pin(async { Ok(user_ids) })
+    }
+
+    fn fetch_history(&self, limit: usize) -> BoxFuture<'_, Result<Vec<HistoryEntry>>> {
+        let data = self.data();
+        let mut seen = HashSet::new();
+
+        // Only the latest progress update of each run is relevant
+        let history = data
+            .progress
+            .iter()
+            .rev()
+            .filter(|progress| seen.insert(progress.start.unix_timestamp()))
+            .take(limit)
+            .map(|progress| {
+                let finish = data
+                    .finish
+                    .filter(|finish| finish.id == progress.start.unix_timestamp());
+
+                let eta_seconds = match finish {
+                    Some(_) => Some(0),
+                    None => progress.eta_seconds.map(|secs| secs as i64),
+                };
+
+                HistoryEntry {
+                    id: progress.start.unix_timestamp(),
+                    task: progress.task.to_string().into(),
+                    start: Some(progress.start),
+                    current: Some(progress.current as u32),
+                    total: Some(progress.total as u32),
+                    eta_seconds,
+                    last_update: None,
+                    is_cancelled: finish.map(|finish| finish.cancelled),
+                }
+            })
+            .collect();
+
+        Box::pin(async { Ok(history) })
+    }
+
+    fn pending_migrations(&self) -> BoxFuture<'_, Result<Vec<&'static str>>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn migrate(&self) -> BoxFuture<'_, Result<Vec<&'static str>>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn store_progress<'a>(&'a self, progress: &'a Progress) -> BoxFuture<'a, Result<()>> {
+        self.data().progress.push(progress.clone());
+
+        Box::pin(async { Ok(()) })
+    }
+
+    fn store_finish<'a>(&'a self, finish: &'a Finish) -> BoxFuture<'a, Result<()>> {
+        self.data().finish = Some(*finish);
+
+        Box::pin(async { Ok(()) })
+    }
+
+    fn store_report<'a>(&'a self, report: &'a RunReport) -> BoxFuture<'a, Result<()>> {
+        self.data().reports.push(report.clone());
+
+        Box::pin(async { Ok(()) })
+    }
+
+    fn store_rankings(&self, rankings: RankingsIter) -> JoinHandle<StoreResult> {
+        self.spawn(|data| {
+            let len = rankings.len();
+            data.rankings
+                .extend(rankings.map(|ranking| (ranking.id, ranking)));
+
+            info!("Successfully stored {len} ranking entries in memory");
+
+            len
+        })
+    }
+
+    fn store_medals<'a>(&'a self, medals: &'a [ScrapedMedal]) -> BoxFuture<'a, StoreResult> {
+        self.data()
+            .medals
+            .extend(medals.iter().map(|medal| (medal.id, medal.clone())));
+
+        let len = medals.len();
+        info!("Successfully stored {len} medals in memory");
+
+        Box::pin(async move { Ok(len) })
+    }
+
+    fn store_rarities(&self, rarities: MedalRarities) -> JoinHandle<StoreResult> {
+        self.spawn(move |data| {
+            let len = rarities.len();
+
+            let entries =
+                rarities
+                    .iter()
+                    .map(|(medal_id, MedalRarityEntry { count, frequency })| {
+                        (*medal_id, *count, *frequency)
+                    });
+
+            data.rarities.extend(entries);
+
+            info!("Successfully stored {len} medal rarities in memory");
+
+            len
+        })
+    }
+
+    fn store_badges(&self, diff: BadgesDiff) -> JoinHandle<StoreResult> {
+        self.spawn(move |data| {
+            let BadgesDiff {
+                names,
+                upserts,
+                removals,
+                ..
+            } = diff;
+
+            let rows = names.len() + upserts.len() + removals.len();
+
+            let badges = &mut data.badges;
+            badges.names.extend(names);
+
+            let removed = upserts
+                .iter()
+                .map(|owner| (owner.name.as_ref(), owner.user_id))
+                .chain(
+                    removals
+                        .iter()
+                        .map(|(name, user_id)| (name.as_ref(), *user_id)),
+                );
+
+            for (name, user_id) in removed {
+                for entries in badges.descriptions.values_mut() {
+                    if let Some(owners) = entries.get_mut(name) {
+                        owners.retain(|owner| owner.user_id != user_id);
+                    }
+                }
+            }
+
+            let upsert_count = upserts.len();
+
+            for BadgeRow {
+                name,
+                user_id,
+                description,
+                awarded_at,
+            } in upserts
+            {
+                badges
+                    .descriptions
+                    .entry(BadgeDescription(description))
+                    .or_default()
+                    .entry(BadgeName(name))
+                    .or_default()
+                    .insert(BadgeOwner {
+                        user_id,
+                        awarded_at,
+                    });
+            }
+
+            info!(
+                "Successfully stored badges in memory: {upsert_count} owner(s) upserted, \
+                {} owner(s) removed",
+                removals.len()
+            );
+
+            rows
+        })
+    }
+
+    fn store_failures<'a>(
+        &'a self,
+        failures: &'a UserFailures,
+        resolved: &'a [u32],
+    ) -> BoxFuture<'a, StoreResult> {
+        let mut data = self.data();
+
+        for user_id in resolved {
+            data.failures.remove(user_id);
+        }
+
+        for (user_id, kind) in failures.iter() {
+            data.failures
+                .entry(*user_id)
+                .and_modify(|(last_kind, count)| {
+                    *last_kind = *kind;
+                    *count += 1;
+                })
+                .or_insert((*kind, 1));
+        }
+
+        info!(
+            "Successfully stored {} user failures in memory and removed {} resolved ones",
+            failures.len(),
+            resolved.len()
+        );
+
+        let len = failures.len() + resolved.len();
+
+        Box::pin(async move { Ok(len) })
+    }
+}
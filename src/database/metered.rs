@@ -0,0 +1,190 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use eyre::Result;
+use futures_util::future::BoxFuture;
+use tokio::task::JoinHandle;
+
+use crate::{
+    metrics::{StoreMethod, METRICS},
+    model::{
+        Badges, BadgesDiff, Finish, HistoryEntry, MedalRarities, Progress, RankingsIter, RunReport,
+        ScrapedMedal, SharedReport, UserFailures,
+    },
+    util::IntHasher,
+};
+
+use super::{Fetchable, Storage, StoreResult};
+
+/// Wraps another storage and records how long its writes take as well as
+/// their outcome into the report of the current run.
+pub struct MeteredStorage {
+    inner: Arc<dyn Storage>,
+}
+
+impl MeteredStorage {
+    pub fn new(inner: Arc<dyn Storage>) -> Self {
+        Self { inner }
+    }
+}
+
+fn observe_result(method: StoreMethod, elapsed: Duration, res: &StoreResult) {
+    observe(SharedReport::current(), method, elapsed, Some(res));
+}
+
+/// Observe the duration and outcome once the handle finished.
+fn observe_handle(method: StoreMethod, handle: JoinHandle<StoreResult>) -> JoinHandle<StoreResult> {
+    // The spawned task does not know the current report
+    let report = SharedReport::current();
+    let start = Instant::now();
+
+    tokio::spawn(async move {
+        let res = handle
+            .await
+            .unwrap_or_else(|err| Err(format!("store task failed: {err}")));
+
+        observe(report, method, start.elapsed(), Some(&res));
+
+        res
+    })
+}
+
+fn observe(
+    report: Option<SharedReport>,
+    method: StoreMethod,
+    elapsed: Duration,
+    res: Option<&StoreResult>,
+) {
+    METRICS.observe_store(method, elapsed);
+
+    let Some(report) = report else { return };
+
+    report.update(|report| {
+        let name = method.as_str();
+        report.add_duration(name, elapsed);
+
+        match res {
+            Some(Ok(rows)) => report.add_rows(name, *rows),
+            Some(Err(err)) => report.add_error(format!("{name}: {err}")),
+            None => {}
+        }
+    });
+}
+
+impl Storage for MeteredStorage {
+    fn fetchable(&self) -> Fetchable {
+        self.inner.fetchable()
+    }
+
+    fn fetch_osekai_ranking_ids<'a>(
+        &'a self,
+        user_ids: &'a mut HashSet<u32, IntHasher>,
+    ) -> BoxFuture<'a, Result<()>> {
+        self.inner.fetch_osekai_ranking_ids(user_ids)
+    }
+
+    fn fetch_osekai_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>> {
+        self.inner.fetch_osekai_user_ids()
+    }
+
+    fn fetch_badges(&self) -> BoxFuture<'_, Result<Badges>> {
+        self.inner.fetch_badges()
+    }
+
+    fn fetch_medal_rarities(&self) -> BoxFuture<'_, Result<MedalRarities>> {
+        self.inner.fetch_medal_rarities()
+    }
+
+    fn fetch_medal_ids(&self) -> BoxFuture<'_, Result<HashSet<u16, IntHasher>>> {
+        self.inner.fetch_medal_ids()
+    }
+
+    fn fetch_failed_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>> {
+        self.inner.fetch_failed_user_ids()
+    }
+
+    fn fetch_history(&self, limit: usize) -> BoxFuture<'_, Result<Vec<HistoryEntry>>> {
+        self.inner.fetch_history(limit)
+    }
+
+    fn pending_migrations(&self) -> BoxFuture<'_, Result<Vec<&'static str>>> {
+        self.inner.pending_migrations()
+    }
+
+    fn migrate(&self) -> BoxFuture<'_, Result<Vec<&'static str>>> {
+        self.inner.migrate()
+    }
+
+    fn store_progress<'a>(&'a self, progress: &'a Progress) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async {
+            let start = Instant::now();
+            let res = self.inner.store_progress(progress).await;
+            observe(
+                SharedReport::current(),
+                StoreMethod::Progress,
+                start.elapsed(),
+                None,
+            );
+
+            res
+        })
+    }
+
+    fn store_finish<'a>(&'a self, finish: &'a Finish) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async {
+            let start = Instant::now();
+            let res = self.inner.store_finish(finish).await;
+            observe(
+                SharedReport::current(),
+                StoreMethod::Finish,
+                start.elapsed(),
+                None,
+            );
+
+            res
+        })
+    }
+
+    fn store_report<'a>(&'a self, report: &'a RunReport) -> BoxFuture<'a, Result<()>> {
+        self.inner.store_report(report)
+    }
+
+    fn store_rankings(&self, rankings: RankingsIter) -> JoinHandle<StoreResult> {
+        observe_handle(StoreMethod::Rankings, self.inner.store_rankings(rankings))
+    }
+
+    fn store_medals<'a>(&'a self, medals: &'a [ScrapedMedal]) -> BoxFuture<'a, StoreResult> {
+        Box::pin(async {
+            let start = Instant::now();
+            let res = self.inner.store_medals(medals).await;
+            observe_result(StoreMethod::Medals, start.elapsed(), &res);
+
+            res
+        })
+    }
+
+    fn store_rarities(&self, rarities: MedalRarities) -> JoinHandle<StoreResult> {
+        observe_handle(StoreMethod::Rarities, self.inner.store_rarities(rarities))
+    }
+
+    fn store_badges(&self, diff: BadgesDiff) -> JoinHandle<StoreResult> {
+        observe_handle(StoreMethod::Badges, self.inner.store_badges(diff))
+    }
+
+    fn store_failures<'a>(
+        &'a self,
+        failures: &'a UserFailures,
+        resolved: &'a [u32],
+    ) -> BoxFuture<'a, StoreResult> {
+        Box::pin(async {
+            let start = Instant::now();
+            let res = self.inner.store_failures(failures, resolved).await;
+            observe_result(StoreMethod::Failures, start.elapsed(), &res);
+
+            res
+        })
+    }
+}
@@ -0,0 +1,226 @@
+use std::{collections::HashSet, time::Instant};
+
+use eyre::{bail, Context as _, Result};
+use futures_util::TryStreamExt;
+use sqlx::Row;
+
+use super::Database;
+
+/// A versioned schema change whose version is the name of its file.
+pub struct Migration {
+    pub version: &'static str,
+    pub sql: &'static str,
+}
+
+macro_rules! migrations {
+    ($dir:literal: $($version:literal,)*) => {
+        &[$(
+            Migration {
+                version: $version,
+                sql: include_str!(concat!("../../migrations/", $dir, $version, ".sql")),
+            },
+        )*]
+    };
+}
+
+/// Migrations in the order they need to be applied.
+///
+/// The first migration creates the initial schema and drops its tables
+/// beforehand so it is only recorded, not applied, if they exist already.
+const MYSQL_MIGRATIONS: &[Migration] = migrations!(
+    "": "2024-05-27_initial",
+    "2026-10-17_failures",
+    "2026-10-17_runs",
+);
+
+pub const SQLITE_MIGRATIONS: &[Migration] = migrations!(
+    "sqlite/": "2024-05-27_initial",
+    "2026-10-17_failures",
+    "2026-10-17_runs",
+);
+
+/// Table that keeps track of applied migrations
+pub const TRACKING_TABLE: &str = "Schema_Migrations";
+
+/// Names of the tables that the SQL creates.
+fn created_tables(sql: &str) -> Vec<&str> {
+    const CREATE: &str = "CREATE TABLE `";
+
+    sql.match_indices(CREATE)
+        .filter_map(|(i, _)| {
+            let rest = &sql[i + CREATE.len()..];
+
+            rest.split_once('`').map(|(table, _)| table)
+        })
+        .collect()
+}
+
+/// Migrations that are not yet applied in the order they need to be applied.
+pub fn pending(
+    migrations: &'static [Migration],
+    applied: &HashSet<String>,
+) -> Vec<&'static Migration> {
+    migrations
+        .iter()
+        .filter(|migration| !applied.contains(migration.version))
+        .collect()
+}
+
+impl Database {
+    /// Versions of migrations that the database is missing.
+    pub async fn pending_migrations(&self) -> Result<Vec<&'static str>> {
+        let applied = self.applied_migrations().await?;
+
+        let pending = pending(MYSQL_MIGRATIONS, &applied)
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect();
+
+        Ok(pending)
+    }
+
+    /// Apply all pending migrations and return their versions.
+    pub async fn migrate(&self) -> Result<Vec<&'static str>> {
+        let create = format!(
+            "CREATE TABLE IF NOT EXISTS `{TRACKING_TABLE}` (\
+              `Version` varchar(100) NOT NULL, \
+              `Applied_At` datetime DEFAULT NULL, \
+              PRIMARY KEY (`Version`)\
+            ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci"
+        );
+
+        sqlx::raw_sql(&create)
+            .execute(&self.mysql)
+            .await
+            .context("failed to create migration tracking table")?;
+
+        let applied = self.applied_migrations().await?;
+        let mut versions = Vec::new();
+
+        for migration in pending(MYSQL_MIGRATIONS, &applied) {
+            let initial = migration.version == MYSQL_MIGRATIONS[0].version;
+
+            if initial && self.initial_schema_exists(migration).await? {
+                info!(
+                    "Tables already exist; recording migration `{}` without applying it",
+                    migration.version
+                );
+            } else {
+                let start = Instant::now();
+
+                sqlx::raw_sql(migration.sql)
+                    .execute(&self.mysql)
+                    .await
+                    .with_context(|| {
+                        format!("failed to apply migration `{}`", migration.version)
+                    })?;
+
+                info!(
+                    "Applied migration `{}` in {:?}",
+                    migration.version,
+                    start.elapsed()
+                );
+            }
+
+            let insert = format!(
+                "INSERT INTO `{TRACKING_TABLE}` (`Version`, `Applied_At`) VALUES (?, NOW())"
+            );
+
+            sqlx::query(&insert)
+                .bind(migration.version)
+                .execute(&self.mysql)
+                .await
+                .with_context(|| format!("failed to record migration `{}`", migration.version))?;
+
+            versions.push(migration.version);
+        }
+
+        Ok(versions)
+    }
+
+    /// Whether the tables of the initial migration exist already, i.e. the
+    /// database predates the migrations.
+    ///
+    /// Fails if only some of them exist since applying the migration would
+    /// drop those while recording it would leave the others missing.
+    async fn initial_schema_exists(&self, initial: &Migration) -> Result<bool> {
+        let mut existing = Vec::new();
+        let mut missing = Vec::new();
+
+        for table in created_tables(initial.sql) {
+            if self.table_exists(table).await? {
+                existing.push(table);
+            } else {
+                missing.push(table);
+            }
+        }
+
+        if !existing.is_empty() && !missing.is_empty() {
+            bail!(
+                "database has only some tables of migration `{}`; create the missing \
+                table(s) {} manually before migrating",
+                initial.version,
+                missing.join(", ")
+            );
+        }
+
+        Ok(!existing.is_empty())
+    }
+
+    async fn applied_migrations(&self) -> Result<HashSet<String>> {
+        if !self.table_exists(TRACKING_TABLE).await? {
+            return Ok(HashSet::new());
+        }
+
+        sqlx::query(&format!("SELECT `Version` FROM `{TRACKING_TABLE}`"))
+            .fetch(&self.mysql)
+            .map_ok(|row| row.get(0))
+            .try_collect()
+            .await
+            .context("failed to fetch applied migrations")
+    }
+
+    async fn table_exists(&self, table: &str) -> Result<bool> {
+        let query = sqlx::query(
+            r#"
+SELECT
+  COUNT(*)
+FROM
+  information_schema.tables
+WHERE
+  `table_schema` = DATABASE()
+  AND `table_name` = ?"#,
+        );
+
+        let count: i64 = query
+            .bind(table)
+            .fetch_one(&self.mysql)
+            .await
+            .with_context(|| format!("failed to check whether table `{table}` exists"))?
+            .get(0);
+
+        Ok(count > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_tables() {
+        let tables = created_tables(MYSQL_MIGRATIONS[0].sql);
+
+        assert_eq!(
+            tables,
+            [
+                "Badges_Data",
+                "Badge_Name",
+                "Medals_Data",
+                "Rankings_Script_History",
+                "Rankings_Users",
+                "System_Users",
+            ]
+        );
+    }
+}
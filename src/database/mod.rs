@@ -1,19 +1,66 @@
+mod dry_run;
+mod export;
 mod fetch;
+mod http;
+mod memory;
+mod metered;
+mod migration;
+mod sqlite;
+mod storage;
 mod store;
 
+use std::{sync::Arc, time::Duration};
+
 use eyre::{Context as _, Result};
 use sqlx::{pool::PoolConnection, Error as SqlxError, MySql, MySqlPool, Transaction};
 
+pub use self::{
+    dry_run::DryRun,
+    export::ExportQuery,
+    http::HttpStorage,
+    memory::InMemoryStorage,
+    metered::MeteredStorage,
+    sqlite::SqliteDatabase,
+    storage::{Fetchable, Storage, StoreResult},
+};
+
+/// Connect to the database or API whose kind is determined by the scheme of
+/// the url.
+pub async fn connect(
+    url: &str,
+    batch_size: usize,
+    dry_run: Option<DryRun>,
+) -> Result<Arc<dyn Storage>> {
+    if url.starts_with(HttpStorage::SCHEME) {
+        Ok(Arc::new(HttpStorage::new(url, batch_size, dry_run)))
+    } else if url.starts_with(SqliteDatabase::SCHEME) {
+        let db = SqliteDatabase::new(url, batch_size, dry_run).await?;
+
+        Ok(Arc::new(db))
+    } else {
+        let db = Database::new(url, batch_size, dry_run).await?;
+
+        Ok(Arc::new(db))
+    }
+}
+
 #[derive(Clone)]
 pub struct Database {
     mysql: MySqlPool,
+    batch_size: usize,
+    /// If set, writes go into a file instead of the database.
+    dry_run: Option<DryRun>,
 }
 
 impl Database {
-    pub async fn new(url: &str) -> Result<Self> {
+    pub async fn new(url: &str, batch_size: usize, dry_run: Option<DryRun>) -> Result<Self> {
         MySqlPool::connect(url)
             .await
-            .map(|mysql| Self { mysql })
+            .map(|mysql| Self {
+                mysql,
+                batch_size,
+                dry_run,
+            })
             .context("failed to connect to database")
     }
 
@@ -25,3 +72,7 @@ impl Database {
         self.mysql.begin().await
     }
 }
+
+fn rows_per_sec(rows: usize, elapsed: Duration) -> f64 {
+    rows as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
@@ -0,0 +1,73 @@
+use eyre::{Context as _, Result};
+use futures_util::TryStreamExt;
+use serde_json::Value;
+use sqlx::{
+    sqlite::{Sqlite, SqliteRow},
+    Column, Executor, Row, Statement, TypeInfo, ValueRef,
+};
+
+use crate::database::ExportQuery;
+
+use super::SqliteDatabase;
+
+impl SqliteDatabase {
+    /// Stream all rows of the query and pass their column names and values
+    /// to `on_row`. Returns the amount of rows.
+    pub async fn export_rows(
+        &self,
+        query: &ExportQuery<'_>,
+        mut on_row: impl FnMut(&[&str], Vec<Value>) -> Result<()>,
+    ) -> Result<usize> {
+        let mut builder = query.build()?;
+        let mut rows = builder.build().fetch(&self.sqlite);
+        let mut count = 0;
+
+        while let Some(row) = rows.try_next().await.context("failed to fetch row")? {
+            let columns = row.columns();
+            let names: Vec<_> = columns.iter().map(Column::name).collect();
+
+            let values = (0..columns.len())
+                .map(|idx| column_value(&row, idx))
+                .collect::<Result<_>>()?;
+
+            on_row(&names, values)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Column names of the query's rows, e.g. for the header of an export
+    /// without rows.
+    pub async fn export_columns(&self, query: &ExportQuery<'_>) -> Result<Vec<String>> {
+        let builder = query.build::<Sqlite>()?;
+
+        let statement = (&self.sqlite)
+            .prepare(builder.sql())
+            .await
+            .context("failed to prepare export query")?;
+
+        let names = statement.columns().iter().map(Column::name);
+
+        Ok(names.map(str::to_owned).collect())
+    }
+}
+
+/// SQLite columns may hold values of any type so the type of the value
+/// itself is used instead of the column's declared type.
+fn column_value(row: &SqliteRow, idx: usize) -> Result<Value> {
+    let raw = row.try_get_raw(idx)?;
+
+    if raw.is_null() {
+        return Ok(Value::Null);
+    }
+
+    let value = match raw.type_info().name() {
+        "INTEGER" | "BOOLEAN" => Value::from(row.try_get_unchecked::<i64, _>(idx)?),
+        "REAL" => Value::from(row.try_get_unchecked::<f64, _>(idx)?),
+        // Timestamps are stored as text as well
+        _ => Value::String(row.try_get_unchecked::<String, _>(idx)?),
+    };
+
+    Ok(value)
+}
@@ -0,0 +1,184 @@
+use std::collections::{HashMap, HashSet};
+
+use eyre::{Context as _, Report, Result};
+use futures_util::{future, TryStreamExt};
+use sqlx::{sqlite::SqliteRow, Row};
+use time::OffsetDateTime;
+
+use crate::{
+    model::{
+        BadgeDescription, BadgeImageUrl, BadgeName, BadgeOwner, Badges, HistoryEntry, MedalRarities,
+    },
+    util::IntHasher,
+};
+
+use super::SqliteDatabase;
+
+impl SqliteDatabase {
+    pub async fn fetch_osekai_ranking_ids(
+        &self,
+        user_ids: &mut HashSet<u32, IntHasher>,
+    ) -> Result<()> {
+        sqlx::query("SELECT `ID` FROM `Rankings_Users`")
+            .fetch(&self.sqlite)
+            .try_for_each(|row| {
+                user_ids.insert(row.get::<i64, _>(0) as u32);
+
+                future::ready(Ok(()))
+            })
+            .await
+            .context("failed to fetch all ranking ids")
+    }
+
+    pub async fn fetch_osekai_user_ids(&self) -> Result<HashSet<u32, IntHasher>> {
+        sqlx::query("SELECT `User_ID` FROM `System_Users`")
+            .fetch(&self.sqlite)
+            .map_ok(|row| row.get::<i64, _>(0) as u32)
+            .try_collect()
+            .await
+            .context("failed to fetch system user ids")
+    }
+
+    pub async fn fetch_badges(&self) -> Result<Badges> {
+        let names = sqlx::query("SELECT `Name`, `Image_URL` FROM `Badges_Data`")
+            .fetch(&self.sqlite)
+            .map_ok(|row| {
+                let name = row.get::<String, _>(0).into_boxed_str();
+
+                let image_url = row
+                    .get::<Option<String>, _>(1)
+                    .map(String::into_boxed_str)
+                    .unwrap_or_default();
+
+                (BadgeName(name), BadgeImageUrl(image_url))
+            })
+            .try_collect()
+            .await
+            .context("failed to fetch badges data")?;
+
+        let mut stored = Badges {
+            names,
+            descriptions: HashMap::default(),
+            checked_users: HashSet::default(),
+        };
+
+        let query = sqlx::query(
+            "SELECT `Name`, `User_ID`, `Description`, `Date_Awarded` FROM `Badge_Name`",
+        );
+
+        query
+            .fetch(&self.sqlite)
+            .try_for_each(|row| {
+                let owner = BadgeOwner {
+                    user_id: row.get::<i64, _>(1) as u32,
+                    awarded_at: row
+                        .get::<Option<OffsetDateTime>, _>(3)
+                        .unwrap_or_else(OffsetDateTime::now_utc),
+                };
+
+                let name = row.get::<String, _>(0).into_boxed_str();
+                let description = row
+                    .get::<Option<String>, _>(2)
+                    .unwrap_or_default()
+                    .into_boxed_str();
+
+                stored
+                    .descriptions
+                    .entry(BadgeDescription(description))
+                    .or_default()
+                    .entry(BadgeName(name))
+                    .or_default()
+                    .insert(owner);
+
+                future::ready(Ok(()))
+            })
+            .await
+            .context("failed to fetch badge name")?;
+
+        Ok(stored)
+    }
+
+    pub async fn fetch_medal_rarities(&self) -> Result<MedalRarities> {
+        let query =
+            sqlx::query("SELECT `Medal_ID`, `Frequency`, `Count_Achieved_By` FROM `Medals_Data`");
+
+        query
+            .fetch(&self.sqlite)
+            .map_ok(|row| {
+                (
+                    row.get::<i64, _>(0) as u16,
+                    row.get::<Option<i64>, _>(2).unwrap_or(0) as u32,
+                    row.get::<Option<f32>, _>(1).unwrap_or(0.0),
+                )
+            })
+            .try_collect()
+            .await
+            .context("failed to fetch all medal rarities")
+    }
+
+    pub async fn fetch_medal_ids(&self) -> Result<HashSet<u16, IntHasher>> {
+        sqlx::query("SELECT `Medal_ID` FROM `Medals_Data`")
+            .fetch(&self.sqlite)
+            .map_ok(|row| row.get::<i64, _>(0) as u16)
+            .try_collect()
+            .await
+            .context("failed to fetch all medal ids")
+    }
+
+    pub async fn fetch_failed_user_ids(&self) -> Result<HashSet<u32, IntHasher>> {
+        sqlx::query("SELECT `User_ID` FROM `Rankings_Users_Failures`")
+            .fetch(&self.sqlite)
+            .map_ok(|row| row.get::<i64, _>(0) as u32)
+            .try_collect()
+            .await
+            .context("failed to fetch failed user ids")
+    }
+
+    /// The most recent runs, newest first.
+    pub async fn fetch_history(&self, limit: usize) -> Result<Vec<HistoryEntry>> {
+        let query = sqlx::query(
+            r#"
+SELECT
+  history.`ID`, history.`Type`, history.`Time`, history.`Count_Current`,
+  history.`Count_Total`, history.`Elapsed_Seconds`, history.`Elapsed_Last_Update`,
+  (
+    SELECT MAX(runs.`Is_Cancelled`)
+    FROM `Rankings_Script_Runs` AS runs
+    WHERE runs.`History_ID` = history.`ID`
+  ) AS `Is_Cancelled`
+FROM
+  `Rankings_Script_History` AS history
+ORDER BY
+  history.`ID` DESC
+LIMIT
+  ?"#,
+        );
+
+        query
+            .bind(limit as i64)
+            .fetch(&self.sqlite)
+            .map_err(Report::new)
+            .and_then(|row| future::ready(history_entry(&row)))
+            .try_collect()
+            .await
+            .context("failed to fetch script history")
+    }
+}
+
+fn history_entry(row: &SqliteRow) -> Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.try_get(0)?,
+        task: row
+            .try_get::<Option<String>, _>(1)?
+            .unwrap_or_default()
+            .into(),
+        start: row.try_get(2)?,
+        current: row.try_get::<Option<i64>, _>(3)?.map(|count| count as u32),
+        total: row.try_get::<Option<i64>, _>(4)?.map(|count| count as u32),
+        eta_seconds: row.try_get(5)?,
+        last_update: row.try_get(6)?,
+        is_cancelled: row
+            .try_get::<Option<i64>, _>(7)?
+            .map(|cancelled| cancelled != 0),
+    })
+}
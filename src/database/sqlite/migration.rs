@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+
+use eyre::{Context as _, Result};
+use futures_util::TryStreamExt;
+use sqlx::{Executor, Row};
+
+use super::{
+    super::migration::{pending, SQLITE_MIGRATIONS, TRACKING_TABLE},
+    SqliteDatabase,
+};
+
+impl SqliteDatabase {
+    /// Versions of migrations that the database is missing.
+    pub async fn pending_migrations(&self) -> Result<Vec<&'static str>> {
+        let applied = self.applied_migrations().await?;
+
+        let pending = pending(SQLITE_MIGRATIONS, &applied)
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect();
+
+        Ok(pending)
+    }
+
+    /// Apply all pending migrations and return their versions.
+    pub async fn migrate(&self) -> Result<Vec<&'static str>> {
+        let create = format!(
+            "CREATE TABLE IF NOT EXISTS `{TRACKING_TABLE}` (\
+              `Version` TEXT NOT NULL, \
+              `Applied_At` TEXT DEFAULT NULL, \
+              PRIMARY KEY (`Version`)\
+            )"
+        );
+
+        sqlx::raw_sql(&create)
+            .execute(&self.sqlite)
+            .await
+            .context("failed to create migration tracking table")?;
+
+        let applied = self.applied_migrations().await?;
+        let mut versions = Vec::new();
+
+        // The SQLite migrations only create tables if they don't exist yet
+        // so there is no need to record the initial one without applying it.
+        for migration in pending(SQLITE_MIGRATIONS, &applied) {
+            let mut tx = self
+                .sqlite
+                .begin()
+                .await
+                .context("failed to begin migration transaction")?;
+
+            tx.execute(migration.sql)
+                .await
+                .with_context(|| format!("failed to apply migration `{}`", migration.version))?;
+
+            let insert = format!(
+                "INSERT INTO `{TRACKING_TABLE}` (`Version`, `Applied_At`) \
+                VALUES (?, CURRENT_TIMESTAMP)"
+            );
+
+            sqlx::query(&insert)
+                .bind(migration.version)
+                .execute(&mut *tx)
+                .await
+                .with_context(|| format!("failed to record migration `{}`", migration.version))?;
+
+            tx.commit()
+                .await
+                .context("failed to commit migration transaction")?;
+
+            info!("Applied migration `{}`", migration.version);
+            versions.push(migration.version);
+        }
+
+        Ok(versions)
+    }
+
+    async fn applied_migrations(&self) -> Result<HashSet<String>> {
+        let query = sqlx::query(
+            "SELECT COUNT(*) FROM `sqlite_master` WHERE `type` = 'table' AND `name` = ?",
+        );
+
+        let count: i64 = query
+            .bind(TRACKING_TABLE)
+            .fetch_one(&self.sqlite)
+            .await
+            .context("failed to check whether migration tracking table exists")?
+            .get(0);
+
+        if count == 0 {
+            return Ok(HashSet::new());
+        }
+
+        sqlx::query(&format!("SELECT `Version` FROM `{TRACKING_TABLE}`"))
+            .fetch(&self.sqlite)
+            .map_ok(|row| row.get(0))
+            .try_collect()
+            .await
+            .context("failed to fetch applied migrations")
+    }
+}
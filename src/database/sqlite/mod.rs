@@ -0,0 +1,48 @@
+mod export;
+mod fetch;
+mod migration;
+mod store;
+
+use std::str::FromStr;
+
+use eyre::{Context as _, Result};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+
+use super::DryRun;
+
+/// SQLite counterpart of [`Database`](super::Database) so that the script
+/// can run without a MySQL server.
+#[derive(Clone)]
+pub struct SqliteDatabase {
+    sqlite: SqlitePool,
+    batch_size: usize,
+    /// If set, writes go into a file instead of the database.
+    dry_run: Option<DryRun>,
+}
+
+impl SqliteDatabase {
+    pub const SCHEME: &'static str = "sqlite:";
+
+    /// Open the database file, creating it if necessary.
+    ///
+    /// Tables are created through [`SqliteDatabase::migrate`].
+    pub async fn new(url: &str, batch_size: usize, dry_run: Option<DryRun>) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(url)
+            .context("invalid sqlite database url")?
+            .create_if_missing(true);
+
+        let sqlite = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .context("failed to connect to sqlite database")?;
+
+        Ok(Self {
+            sqlite,
+            batch_size,
+            dry_run,
+        })
+    }
+}
@@ -0,0 +1,661 @@
+use std::{num::NonZeroU32, ops::DerefMut, time::Instant};
+
+use eyre::{Context as _, Result};
+use sqlx::{query_builder::Separated, QueryBuilder, Sqlite};
+use tokio::task::JoinHandle;
+
+use crate::model::{
+    BadgeChanges, BadgeImageUrl, BadgeName, BadgeRow, BadgesDiff, Finish, MedalRarities,
+    MedalRarityEntry, Progress, RankingUser, RankingsIter, RunReport, ScrapedMedal, UserFailures,
+};
+
+use super::{
+    super::{rows_per_sec, StoreResult},
+    SqliteDatabase,
+};
+
+impl SqliteDatabase {
+    pub async fn store_progress(&self, progress: &Progress) -> Result<()> {
+        if let Some(ref dry_run) = self.dry_run {
+            return dry_run.store_progress(progress);
+        }
+
+        let Progress {
+            start,
+            current,
+            total,
+            eta_seconds,
+            task,
+            resumed: _,
+            cancelled: _,
+        } = progress;
+
+        let query = sqlx::query(
+            r#"
+INSERT INTO `Rankings_Script_History` (
+  `ID`, `Type`, `Time`, `Count_Current`, `Count_Total`,
+  `Elapsed_Seconds`, `Elapsed_Last_Update`
+) VALUES (?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
+ON CONFLICT (`ID`) DO UPDATE SET
+  `Count_Current` = excluded.`Count_Current`,
+  `Count_Total` = excluded.`Count_Total`,
+  `Elapsed_Seconds` = excluded.`Elapsed_Seconds`,
+  `Elapsed_Last_Update` = excluded.`Elapsed_Last_Update`"#,
+        )
+        .bind(start.unix_timestamp())
+        .bind(task.to_string())
+        .bind(start)
+        .bind(*current as i64)
+        .bind(*total as i64)
+        .bind(eta_seconds.map(|secs| secs as i64));
+
+        query
+            .execute(&self.sqlite)
+            .await
+            .context("failed to execute Rankings_Script_History query")?;
+
+        Ok(())
+    }
+
+    pub async fn store_finish(&self, finish: &Finish) -> Result<()> {
+        if let Some(ref dry_run) = self.dry_run {
+            return dry_run.store_finish(finish);
+        }
+
+        let Finish {
+            id,
+            requested_users,
+            total_users,
+            cancelled: _,
+        } = finish;
+
+        let query = sqlx::query(
+            r#"
+UPDATE `Rankings_Script_History`
+SET
+  `Count_Current` = ?,
+  `Count_Total` = ?,
+  `Elapsed_Seconds` = 0,
+  `Elapsed_Last_Update` = CURRENT_TIMESTAMP
+WHERE
+  `ID` = ?"#,
+        )
+        .bind(*requested_users as i64)
+        .bind(*total_users as i64)
+        .bind(id);
+
+        query
+            .execute(&self.sqlite)
+            .await
+            .context("failed to execute Rankings_Script_History query")?;
+
+        Ok(())
+    }
+
+    pub async fn store_report(&self, report: &RunReport) -> Result<()> {
+        if let Some(ref dry_run) = self.dry_run {
+            return dry_run.store_report(report);
+        }
+
+        let RunReport {
+            history_id,
+            task,
+            start,
+            end,
+            cancelled,
+            durations: _,
+            requested_users,
+            failed_users,
+            restricted_users,
+            new_medals,
+            badges,
+            rows: _,
+            errors: _,
+            omitted_errors: _,
+        } = report;
+
+        let query = sqlx::query(
+            r#"
+INSERT INTO `Rankings_Script_Runs` (
+  `History_ID`, `Type`, `Started_At`, `Finished_At`, `Is_Cancelled`,
+  `Count_Requested`, `Count_Failed`, `Count_Restricted`, `Count_New_Medals`,
+  `Count_New_Badges`, `Count_Badge_Owners_Added`, `Count_Badge_Owners_Updated`,
+  `Count_Badge_Owners_Removed`, `Count_Rows_Written`, `Durations`,
+  `Rows_Written`, `Errors`
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(history_id)
+        .bind(task.to_string())
+        .bind(start)
+        .bind(end)
+        .bind(cancelled)
+        .bind(*requested_users as i64)
+        .bind(*failed_users as i64)
+        .bind(*restricted_users as i64)
+        .bind(*new_medals as i64)
+        .bind(badges.new_badges as i64)
+        .bind(badges.owners_added as i64)
+        .bind(badges.owners_updated as i64)
+        .bind(badges.owners_removed as i64)
+        .bind(report.total_rows() as i64)
+        .bind(report.durations_json())
+        .bind(report.rows_json())
+        .bind(report.errors_json());
+
+        query
+            .execute(&self.sqlite)
+            .await
+            .context("failed to execute Rankings_Script_Runs query")?;
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn store_rankings(&self, rankings: RankingsIter) -> JoinHandle<StoreResult> {
+        async fn inner(db: SqliteDatabase, rankings: RankingsIter) -> Result<usize> {
+            if let Some(ref dry_run) = db.dry_run {
+                return dry_run.store_rankings(rankings);
+            }
+
+            let insert = r#"
+INSERT INTO `Rankings_Users` (
+    `ID`, `Accuracy_Catch`, `Accuracy_Mania`, `Accuracy_Standard`,
+    `Accuracy_Stdev`, `Accuracy_Taiko`, `Count_Badges`,
+    `Count_Maps_Loved`, `Count_Maps_Ranked`, `Count_Medals`,
+    `Count_Replays_Watched`, `Count_Subscribers`, `Country_Code`,
+    `Is_Restricted`, `Level_Catch`, `Level_Mania`, `Level_Standard`,
+    `Level_Stdev`, `Level_Taiko`, `Name`, `PP_Catch`, `PP_Mania`,
+    `PP_Standard`, `PP_Stdev`, `PP_Taiko`, `PP_Total`,
+    `Rank_Global_Catch`, `Rank_Global_Mania`, `Rank_Global_Standard`,
+    `Rank_Global_Taiko`, `Rarest_Medal_Achieved`, `Rarest_Medal_ID`
+) "#;
+
+            let on_conflict = r#"
+ON CONFLICT (`ID`) DO UPDATE SET
+    `Accuracy_Catch` = excluded.`Accuracy_Catch`,
+    `Accuracy_Mania` = excluded.`Accuracy_Mania`,
+    `Accuracy_Standard` = excluded.`Accuracy_Standard`,
+    `Accuracy_Stdev` = excluded.`Accuracy_Stdev`,
+    `Accuracy_Taiko` = excluded.`Accuracy_Taiko`,
+    `Count_Badges` = excluded.`Count_Badges`,
+    `Count_Maps_Loved` = excluded.`Count_Maps_Loved`,
+    `Count_Maps_Ranked` = excluded.`Count_Maps_Ranked`,
+    `Count_Medals` = excluded.`Count_Medals`,
+    `Count_Replays_Watched` = excluded.`Count_Replays_Watched`,
+    `Count_Subscribers` = excluded.`Count_Subscribers`,
+    `Country_Code` = excluded.`Country_Code`,
+    `Is_Restricted` = excluded.`Is_Restricted`,
+    `Level_Catch` = excluded.`Level_Catch`,
+    `Level_Mania` = excluded.`Level_Mania`,
+    `Level_Standard` = excluded.`Level_Standard`,
+    `Level_Stdev` = excluded.`Level_Stdev`,
+    `Level_Taiko` = excluded.`Level_Taiko`,
+    `Name` = excluded.`Name`,
+    `PP_Catch` = excluded.`PP_Catch`,
+    `PP_Mania` = excluded.`PP_Mania`,
+    `PP_Standard` = excluded.`PP_Standard`,
+    `PP_Stdev` = excluded.`PP_Stdev`,
+    `PP_Taiko` = excluded.`PP_Taiko`,
+    `PP_Total` = excluded.`PP_Total`,
+    `Rank_Global_Catch` = excluded.`Rank_Global_Catch`,
+    `Rank_Global_Mania` = excluded.`Rank_Global_Mania`,
+    `Rank_Global_Standard` = excluded.`Rank_Global_Standard`,
+    `Rank_Global_Taiko` = excluded.`Rank_Global_Taiko`,
+    `Rarest_Medal_Achieved` = excluded.`Rarest_Medal_Achieved`,
+    `Rarest_Medal_ID` = excluded.`Rarest_Medal_ID`"#;
+
+            db.insert_batched(insert, on_conflict, 32, rankings, |mut row, ranking| {
+                let stdev_acc = ranking.std_dev_acc();
+                let stdev_level = ranking.std_dev_level();
+                let stdev_pp = ranking.std_dev_pp();
+                let total_pp = ranking.total_pp();
+
+                let RankingUser {
+                    id,
+                    name,
+                    ignore_acc,
+                    medal_count,
+                    rarest_medal_id,
+                    rarest_medal_achieved,
+                    country_code,
+                    badge_count,
+                    ranked_maps,
+                    loved_maps,
+                    subscribers,
+                    replays_watched,
+                    restricted,
+                    std,
+                    tko,
+                    ctb,
+                    mna,
+                } = ranking;
+
+                let mut std_acc = std.acc;
+                let mut tko_acc = tko.acc;
+                let mut ctb_acc = ctb.acc;
+                let mut mna_acc = mna.acc;
+
+                if ignore_acc {
+                    std_acc = 0.0;
+                    tko_acc = 0.0;
+                    ctb_acc = 0.0;
+                    mna_acc = 0.0;
+                }
+
+                row.push_bind(id)
+                    .push_bind(ctb_acc)
+                    .push_bind(mna_acc)
+                    .push_bind(std_acc)
+                    .push_bind(stdev_acc)
+                    .push_bind(tko_acc)
+                    .push_bind(badge_count)
+                    .push_bind(loved_maps)
+                    .push_bind(ranked_maps)
+                    .push_bind(medal_count)
+                    .push_bind(replays_watched)
+                    .push_bind(subscribers)
+                    .push_bind(country_code.into_string())
+                    .push_bind(restricted as u8)
+                    .push_bind(ctb.level)
+                    .push_bind(mna.level)
+                    .push_bind(std.level)
+                    .push_bind(stdev_level)
+                    .push_bind(tko.level)
+                    .push_bind(name.into_string())
+                    .push_bind(ctb.pp)
+                    .push_bind(mna.pp)
+                    .push_bind(std.pp)
+                    .push_bind(stdev_pp)
+                    .push_bind(tko.pp)
+                    .push_bind(total_pp)
+                    .push_bind(ctb.global_rank.map(NonZeroU32::get))
+                    .push_bind(mna.global_rank.map(NonZeroU32::get))
+                    .push_bind(std.global_rank.map(NonZeroU32::get))
+                    .push_bind(tko.global_rank.map(NonZeroU32::get))
+                    .push_bind(rarest_medal_achieved)
+                    .push_bind(rarest_medal_id);
+            })
+            .await
+            .context("failed to store Rankings_Users")
+        }
+
+        let db = self.to_owned();
+
+        tokio::spawn(async move {
+            let start = Instant::now();
+            let res = inner(db, rankings).await;
+            let _entered = info_span!("store_rankings").entered();
+
+            match res {
+                Ok(len) => info!(
+                    "Successfully stored {len} ranking entries ({:.0} rows/s)",
+                    rows_per_sec(len, start.elapsed())
+                ),
+                Err(ref err) => error!(?err, "Failed to store rankings"),
+            }
+
+            res.map_err(|err| format!("{err:#}"))
+        })
+    }
+
+    pub async fn store_medals(&self, medals: &[ScrapedMedal]) -> StoreResult {
+        async fn inner(db: &SqliteDatabase, medals: &[ScrapedMedal]) -> Result<usize> {
+            if let Some(ref dry_run) = db.dry_run {
+                return dry_run.store_medals(medals);
+            }
+
+            let insert = r#"
+INSERT INTO `Medals_Data` (
+  `Medal_ID`, `Name`, `Link`, `Description`,
+  `Gamemode`, `Grouping`, `Instructions`,
+  `Ordering`
+) "#;
+
+            let on_conflict = r#"
+ON CONFLICT (`Medal_ID`) DO UPDATE SET
+  `Name` = excluded.`Name`,
+  `Link` = excluded.`Link`,
+  `Description` = excluded.`Description`,
+  `Gamemode` = excluded.`Gamemode`,
+  `Grouping` = excluded.`Grouping`,
+  `Instructions` = excluded.`Instructions`,
+  `Ordering` = excluded.`Ordering`"#;
+
+            db.insert_batched(insert, on_conflict, 8, medals, |mut row, medal| {
+                let ScrapedMedal {
+                    icon_url,
+                    id,
+                    name,
+                    grouping,
+                    ordering,
+                    description,
+                    mode,
+                    instructions,
+                } = medal;
+
+                let link = icon_url.rsplit('/').next().unwrap_or(icon_url);
+
+                row.push_bind(id)
+                    .push_bind(name.as_ref())
+                    .push_bind(link)
+                    .push_bind(description.as_ref())
+                    .push_bind(mode.as_deref())
+                    .push_bind(grouping.as_ref())
+                    .push_bind(instructions.as_deref())
+                    .push_bind(ordering);
+            })
+            .await
+            .context("failed to store Medals_Data")
+        }
+
+        let start = Instant::now();
+        let res = inner(self, medals).await;
+        let _entered = info_span!("store_medals").entered();
+
+        match res {
+            Ok(len) => info!(
+                "Successfully stored {len} medals ({:.0} rows/s)",
+                rows_per_sec(len, start.elapsed())
+            ),
+            Err(ref err) => error!(?err, "Failed to store medals"),
+        }
+
+        res.map_err(|err| format!("{err:#}"))
+    }
+
+    #[must_use]
+    pub fn store_rarities(&self, rarities: MedalRarities) -> JoinHandle<StoreResult> {
+        async fn inner(db: SqliteDatabase, rarities: &MedalRarities) -> Result<usize> {
+            if let Some(ref dry_run) = db.dry_run {
+                return dry_run.store_rarities(rarities);
+            }
+
+            let mut tx = db
+                .sqlite
+                .begin()
+                .await
+                .context("failed to begin transaction for Medals_Data")?;
+
+            for (medal_id, MedalRarityEntry { count, frequency }) in rarities.iter() {
+                let query = sqlx::query(
+                    r#"
+UPDATE `Medals_Data`
+SET
+  `Frequency` = ?,
+  `Count_Achieved_By` = ?
+WHERE
+  `Medal_ID` = ?"#,
+                )
+                .bind(frequency)
+                .bind(count)
+                .bind(medal_id);
+
+                query
+                    .execute(tx.deref_mut())
+                    .await
+                    .context("failed to execute Medals_Data query")?;
+            }
+
+            tx.commit()
+                .await
+                .context("failed to commit Medals_Data transaction")?;
+
+            Ok(rarities.len())
+        }
+
+        let db = self.to_owned();
+
+        tokio::spawn(async move {
+            let res = inner(db, &rarities).await;
+            let _entered = info_span!("store_rarities").entered();
+
+            match res {
+                Ok(len) => info!("Successfully stored {len} medal rarities"),
+                Err(ref err) => error!(?err, "Failed to store rarities"),
+            }
+
+            res.map_err(|err| format!("{err:#}"))
+        })
+    }
+
+    #[must_use]
+    pub fn store_badges(&self, diff: BadgesDiff) -> JoinHandle<StoreResult> {
+        async fn inner(db: SqliteDatabase, diff: &BadgesDiff) -> Result<usize> {
+            if let Some(ref dry_run) = db.dry_run {
+                return dry_run.store_badges(diff);
+            }
+
+            let insert = "INSERT INTO `Badges_Data` (`Name`, `Image_URL`) ";
+            let on_conflict = " ON CONFLICT (`Name`) DO NOTHING";
+
+            let data_count = db
+                .insert_batched(
+                    insert,
+                    on_conflict,
+                    2,
+                    diff.names.iter(),
+                    |mut row, (BadgeName(name), BadgeImageUrl(image_url))| {
+                        row.push_bind(name.as_ref()).push_bind(image_url.as_ref());
+                    },
+                )
+                .await
+                .context("failed to store Badges_Data")?;
+
+            let insert = r#"
+INSERT INTO `Badge_Name` (
+  `Name`, `User_ID`, `Description`, `Date_Awarded`
+) "#;
+
+            let on_conflict = r#"
+ON CONFLICT (`Name`, `User_ID`) DO UPDATE SET
+  `Description` = excluded.`Description`,
+  `Date_Awarded` = excluded.`Date_Awarded`"#;
+
+            let upsert_count = db
+                .insert_batched(insert, on_conflict, 4, &diff.upserts, |mut row, owner| {
+                    let BadgeRow {
+                        name,
+                        user_id,
+                        description,
+                        awarded_at,
+                    } = owner;
+
+                    row.push_bind(name.as_ref())
+                        .push_bind(user_id)
+                        .push_bind(description.as_ref())
+                        .push_bind(awarded_at);
+                })
+                .await
+                .context("failed to store Badge_Name")?;
+
+            let delete_count = db
+                .delete_badge_owners(&diff.removals)
+                .await
+                .context("failed to delete from Badge_Name")?;
+
+            Ok(data_count + upsert_count + delete_count)
+        }
+
+        let db = self.to_owned();
+
+        tokio::spawn(async move {
+            let start = Instant::now();
+            let res = inner(db, &diff).await;
+            let _entered = info_span!("store_badges").entered();
+
+            let rows = match res {
+                Ok(rows) => rows,
+                Err(err) => {
+                    error!(?err, "Failed to store badges");
+
+                    return Err(format!("{err:#}"));
+                }
+            };
+
+            for (name, BadgeChanges { added, removed }) in diff.changes.iter() {
+                info!("Badge `{name}`: {added} owner(s) added, {removed} owner(s) removed");
+            }
+
+            info!(
+                "Successfully stored badges: {} new badge(s), {} owner(s) added, \
+                {} owner(s) updated, {} owner(s) removed ({:.0} rows/s)",
+                diff.names.len(),
+                diff.upserts.len() - diff.updated,
+                diff.updated,
+                diff.removals.len(),
+                rows_per_sec(rows, start.elapsed())
+            );
+
+            Ok(rows)
+        })
+    }
+
+    /// Delete the owners of the given badge names and user ids in batches.
+    async fn delete_badge_owners(&self, owners: &[(Box<str>, u32)]) -> Result<usize> {
+        let batch_size = self.batch_size.clamp(1, MAX_PARAMS / 2);
+        let mut deleted = 0;
+
+        for batch in owners.chunks(batch_size) {
+            // SQLite only compares row values against a subquery
+            let mut builder = QueryBuilder::<Sqlite>::new(
+                "DELETE FROM `Badge_Name` WHERE (`Name`, `User_ID`) IN (",
+            );
+
+            builder
+                .push_values(batch, |mut row, (name, user_id)| {
+                    row.push_bind(name.as_ref()).push_bind(user_id);
+                })
+                .push(")");
+
+            builder
+                .build()
+                .execute(&self.sqlite)
+                .await
+                .with_context(|| format!("failed to execute batch after {deleted} rows"))?;
+
+            deleted += batch.len();
+        }
+
+        Ok(deleted)
+    }
+
+    pub async fn store_failures(&self, failures: &UserFailures, resolved: &[u32]) -> StoreResult {
+        async fn inner(
+            db: &SqliteDatabase,
+            failures: &UserFailures,
+            resolved: &[u32],
+        ) -> Result<usize> {
+            if let Some(ref dry_run) = db.dry_run {
+                return dry_run.store_failures(failures, resolved);
+            }
+
+            let mut tx = db
+                .sqlite
+                .begin()
+                .await
+                .context("failed to begin transaction for Rankings_Users_Failures")?;
+
+            for (user_id, kind) in failures.iter() {
+                let query = sqlx::query(
+                    r#"
+INSERT INTO `Rankings_Users_Failures` (
+  `User_ID`, `Error_Kind`, `Count_Failed`, `Last_Failed`
+)
+VALUES
+  (?, ?, 1, CURRENT_TIMESTAMP)
+ON CONFLICT (`User_ID`) DO UPDATE SET
+  `Error_Kind` = excluded.`Error_Kind`,
+  `Count_Failed` = `Count_Failed` + 1,
+  `Last_Failed` = excluded.`Last_Failed`"#,
+                )
+                .bind(user_id)
+                .bind(kind.as_str());
+
+                query
+                    .execute(tx.deref_mut())
+                    .await
+                    .context("failed to execute Rankings_Users_Failures query")?;
+            }
+
+            // Users that were requested successfully no longer fail
+            for batch in resolved.chunks(db.batch_size.max(1)) {
+                let mut builder = QueryBuilder::<Sqlite>::new(
+                    "DELETE FROM `Rankings_Users_Failures` WHERE `User_ID` IN (",
+                );
+
+                let mut separated = builder.separated(", ");
+
+                for user_id in batch {
+                    separated.push_bind(user_id);
+                }
+
+                builder
+                    .push(")")
+                    .build()
+                    .execute(tx.deref_mut())
+                    .await
+                    .context("failed to delete resolved Rankings_Users_Failures")?;
+            }
+
+            tx.commit()
+                .await
+                .context("failed to commit Rankings_Users_Failures transaction")?;
+
+            Ok(failures.len() + resolved.len())
+        }
+
+        let res = inner(self, failures, resolved).await;
+        let _entered = info_span!("store_failures").entered();
+
+        match res {
+            Ok(_) => info!(
+                "Successfully stored {} user failures and removed {} resolved ones",
+                failures.len(),
+                resolved.len()
+            ),
+            Err(ref err) => error!(?err, "Failed to store user failures"),
+        }
+
+        res.map_err(|err| format!("{err:#}"))
+    }
+
+    /// Insert the rows through multi-row statements of at most
+    /// [`SqliteDatabase::batch_size`] rows each and return the amount of rows.
+    async fn insert_batched<'args, I>(
+        &self,
+        insert: &str,
+        on_conflict: &str,
+        columns: usize,
+        rows: I,
+        mut push_row: impl FnMut(Separated<'_, 'args, Sqlite, &'static str>, I::Item),
+    ) -> Result<usize>
+    where
+        I: IntoIterator,
+        I::Item: 'args,
+    {
+        let batch_size = self.batch_size.min(MAX_PARAMS / columns).max(1);
+        let mut rows = rows.into_iter().peekable();
+        let mut stored = 0;
+
+        while rows.peek().is_some() {
+            let mut len = 0;
+            let batch = rows.by_ref().take(batch_size).inspect(|_| len += 1);
+
+            let mut builder = QueryBuilder::new(insert);
+            builder.push_values(batch, &mut push_row).push(on_conflict);
+
+            builder
+                .build()
+                .execute(&self.sqlite)
+                .await
+                .with_context(|| format!("failed to execute batch after {stored} rows"))?;
+
+            stored += len;
+        }
+
+        Ok(stored)
+    }
+}
+
+/// Default maximum amount of placeholders in a single SQLite statement
+const MAX_PARAMS: usize = 32_766;
@@ -0,0 +1,260 @@
+use std::collections::HashSet;
+
+use eyre::Result;
+use futures_util::future::BoxFuture;
+use tokio::task::JoinHandle;
+
+use crate::{
+    model::{
+        Badges, BadgesDiff, Finish, HistoryEntry, MedalRarities, Progress, RankingsIter, RunReport,
+        ScrapedMedal, UserFailures,
+    },
+    util::IntHasher,
+};
+
+use super::{Database, SqliteDatabase};
+
+/// Amount of rows that a store method wrote or a summary of the error
+/// that it already logged.
+pub type StoreResult = Result<usize, String>;
+
+/// Stored data that a [`Storage`] is able to fetch.
+#[derive(Copy, Clone)]
+pub struct Fetchable {
+    pub ranking_ids: bool,
+    pub badges: bool,
+    pub rarities: bool,
+    pub medal_ids: bool,
+}
+
+impl Fetchable {
+    pub const ALL: Self = Self {
+        ranking_ids: true,
+        badges: true,
+        rarities: true,
+        medal_ids: true,
+    };
+}
+
+/// Where data is fetched from and stored into.
+///
+/// Store methods log their outcome themselves; methods returning a
+/// [`JoinHandle`] store in the background.
+pub trait Storage: Send + Sync {
+    /// Which of the fetch methods are supported; the others fail.
+    fn fetchable(&self) -> Fetchable;
+
+    /// Add the ids of all users that have a ranking entry.
+    fn fetch_osekai_ranking_ids<'a>(
+        &'a self,
+        user_ids: &'a mut HashSet<u32, IntHasher>,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    fn fetch_osekai_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>>;
+
+    fn fetch_badges(&self) -> BoxFuture<'_, Result<Badges>>;
+
+    fn fetch_medal_rarities(&self) -> BoxFuture<'_, Result<MedalRarities>>;
+
+    fn fetch_medal_ids(&self) -> BoxFuture<'_, Result<HashSet<u16, IntHasher>>>;
+
+    /// Ids of users that are stored as persistently failing.
+    fn fetch_failed_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>>;
+
+    /// The most recent runs, newest first.
+    fn fetch_history(&self, limit: usize) -> BoxFuture<'_, Result<Vec<HistoryEntry>>>;
+
+    /// Versions of schema migrations that were not yet applied.
+    fn pending_migrations(&self) -> BoxFuture<'_, Result<Vec<&'static str>>>;
+
+    /// Apply all pending schema migrations and return their versions.
+    fn migrate(&self) -> BoxFuture<'_, Result<Vec<&'static str>>>;
+
+    fn store_progress<'a>(&'a self, progress: &'a Progress) -> BoxFuture<'a, Result<()>>;
+
+    fn store_finish<'a>(&'a self, finish: &'a Finish) -> BoxFuture<'a, Result<()>>;
+
+    fn store_report<'a>(&'a self, report: &'a RunReport) -> BoxFuture<'a, Result<()>>;
+
+    #[must_use]
+    fn store_rankings(&self, rankings: RankingsIter) -> JoinHandle<StoreResult>;
+
+    /// Should be awaited before calling [`Storage::store_rarities`].
+    fn store_medals<'a>(&'a self, medals: &'a [ScrapedMedal]) -> BoxFuture<'a, StoreResult>;
+
+    #[must_use]
+    fn store_rarities(&self, rarities: MedalRarities) -> JoinHandle<StoreResult>;
+
+    #[must_use]
+    fn store_badges(&self, diff: BadgesDiff) -> JoinHandle<StoreResult>;
+
+    /// Upsert the failures and remove the `resolved` users which were
+    /// requested successfully after failing previously.
+    fn store_failures<'a>(
+        &'a self,
+        failures: &'a UserFailures,
+        resolved: &'a [u32],
+    ) -> BoxFuture<'a, StoreResult>;
+}
+
+impl Storage for Database {
+    fn fetchable(&self) -> Fetchable {
+        Fetchable::ALL
+    }
+
+    fn fetch_osekai_ranking_ids<'a>(
+        &'a self,
+        user_ids: &'a mut HashSet<u32, IntHasher>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.fetch_osekai_ranking_ids(user_ids))
+    }
+
+    fn fetch_osekai_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>> {
+        Box::pin(self.fetch_osekai_user_ids())
+    }
+
+    fn fetch_badges(&self) -> BoxFuture<'_, Result<Badges>> {
+        Box::pin(self.fetch_badges())
+    }
+
+    fn fetch_medal_rarities(&self) -> BoxFuture<'_, Result<MedalRarities>> {
+        Box::pin(self.fetch_medal_rarities())
+    }
+
+    fn fetch_medal_ids(&self) -> BoxFuture<'_, Result<HashSet<u16, IntHasher>>> {
+        Box::pin(self.fetch_medal_ids())
+    }
+
+    fn fetch_failed_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>> {
+        Box::pin(self.fetch_failed_user_ids())
+    }
+
+    fn fetch_history(&self, limit: usize) -> BoxFuture<'_, Result<Vec<HistoryEntry>>> {
+        Box::pin(self.fetch_history(limit))
+    }
+
+    fn pending_migrations(&self) -> BoxFuture<'_, Result<Vec<&'static str>>> {
+        Box::pin(self.pending_migrations())
+    }
+
+    fn migrate(&self) -> BoxFuture<'_, Result<Vec<&'static str>>> {
+        Box::pin(self.migrate())
+    }
+
+    fn store_progress<'a>(&'a self, progress: &'a Progress) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.store_progress(progress))
+    }
+
+    fn store_finish<'a>(&'a self, finish: &'a Finish) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.store_finish(finish))
+    }
+
+    fn store_report<'a>(&'a self, report: &'a RunReport) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.store_report(report))
+    }
+
+    fn store_rankings(&self, rankings: RankingsIter) -> JoinHandle<StoreResult> {
+        self.store_rankings(rankings)
+    }
+
+    fn store_medals<'a>(&'a self, medals: &'a [ScrapedMedal]) -> BoxFuture<'a, StoreResult> {
+        Box::pin(self.store_medals(medals))
+    }
+
+    fn store_rarities(&self, rarities: MedalRarities) -> JoinHandle<StoreResult> {
+        self.store_rarities(rarities)
+    }
+
+    fn store_badges(&self, diff: BadgesDiff) -> JoinHandle<StoreResult> {
+        self.store_badges(diff)
+    }
+
+    fn store_failures<'a>(
+        &'a self,
+        failures: &'a UserFailures,
+        resolved: &'a [u32],
+    ) -> BoxFuture<'a, StoreResult> {
+        Box::pin(self.store_failures(failures, resolved))
+    }
+}
+
+impl Storage for SqliteDatabase {
+    fn fetchable(&self) -> Fetchable {
+        Fetchable::ALL
+    }
+
+    fn fetch_osekai_ranking_ids<'a>(
+        &'a self,
+        user_ids: &'a mut HashSet<u32, IntHasher>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.fetch_osekai_ranking_ids(user_ids))
+    }
+
+    fn fetch_osekai_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>> {
+        Box::pin(self.fetch_osekai_user_ids())
+    }
+
+    fn fetch_badges(&self) -> BoxFuture<'_, Result<Badges>> {
+        Box::pin(self.fetch_badges())
+    }
+
+    fn fetch_medal_rarities(&self) -> BoxFuture<'_, Result<MedalRarities>> {
+        Box::pin(self.fetch_medal_rarities())
+    }
+
+    fn fetch_medal_ids(&self) -> BoxFuture<'_, Result<HashSet<u16, IntHasher>>> {
+        Box::pin(self.fetch_medal_ids())
+    }
+
+    fn fetch_failed_user_ids(&self) -> BoxFuture<'_, Result<HashSet<u32, IntHasher>>> {
+        Box::pin(self.fetch_failed_user_ids())
+    }
+
+    fn fetch_history(&self, limit: usize) -> BoxFuture<'_, Result<Vec<HistoryEntry>>> {
+        Box::pin(self.fetch_history(limit))
+    }
+
+    fn pending_migrations(&self) -> BoxFuture<'_, Result<Vec<&'static str>>> {
+        Box::pin(self.pending_migrations())
+    }
+
+    fn migrate(&self) -> BoxFuture<'_, Result<Vec<&'static str>>> {
+        Box::pin(self.migrate())
+    }
+
+    fn store_progress<'a>(&'a self, progress: &'a Progress) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.store_progress(progress))
+    }
+
+    fn store_finish<'a>(&'a self, finish: &'a Finish) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.store_finish(finish))
+    }
+
+    fn store_report<'a>(&'a self, report: &'a RunReport) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.store_report(report))
+    }
+
+    fn store_rankings(&self, rankings: RankingsIter) -> JoinHandle<StoreResult> {
+        self.store_rankings(rankings)
+    }
+
+    fn store_medals<'a>(&'a self, medals: &'a [ScrapedMedal]) -> BoxFuture<'a, StoreResult> {
+        Box::pin(self.store_medals(medals))
+    }
+
+    fn store_rarities(&self, rarities: MedalRarities) -> JoinHandle<StoreResult> {
+        self.store_rarities(rarities)
+    }
+
+    fn store_badges(&self, diff: BadgesDiff) -> JoinHandle<StoreResult> {
+        self.store_badges(diff)
+    }
+
+    fn store_failures<'a>(
+        &'a self,
+        failures: &'a UserFailures,
+        resolved: &'a [u32],
+    ) -> BoxFuture<'a, StoreResult> {
+        Box::pin(self.store_failures(failures, resolved))
+    }
+}
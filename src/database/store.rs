@@ -1,17 +1,22 @@
-use std::{num::NonZeroU32, ops::DerefMut};
+use std::{num::NonZeroU32, ops::DerefMut, time::Instant};
 
 use eyre::{Context as _, Result};
+use sqlx::{query_builder::Separated, MySql, QueryBuilder};
 use tokio::task::JoinHandle;
 
 use crate::model::{
-    BadgeDescription, BadgeImageUrl, BadgeName, BadgeOwner, Badges, Finish, MedalRarities,
-    MedalRarityEntry, Progress, RankingUser, RankingsIter, ScrapedMedal,
+    BadgeChanges, BadgeImageUrl, BadgeName, BadgeRow, BadgesDiff, Finish, MedalRarities,
+    MedalRarityEntry, Progress, RankingUser, RankingsIter, RunReport, ScrapedMedal, UserFailures,
 };
 
-use super::Database;
+use super::{rows_per_sec, Database, StoreResult};
 
 impl Database {
     pub async fn store_progress(&self, progress: &Progress) -> Result<()> {
+        if let Some(ref dry_run) = self.dry_run {
+            return dry_run.store_progress(progress);
+        }
+
         let mut conn = self
             .acquire()
             .await
@@ -23,6 +28,8 @@ impl Database {
             total,
             eta_seconds,
             task,
+            resumed: _,
+            cancelled: _,
         } = progress;
 
         let query = sqlx::query!(
@@ -59,6 +66,10 @@ ON DUPLICATE KEY UPDATE
     }
 
     pub async fn store_finish(&self, finish: &Finish) -> Result<()> {
+        if let Some(ref dry_run) = self.dry_run {
+            return dry_run.store_finish(finish);
+        }
+
         let mut conn = self
             .acquire()
             .await
@@ -67,6 +78,8 @@ ON DUPLICATE KEY UPDATE
         let Finish {
             id,
             requested_users,
+            total_users,
+            cancelled: _,
         } = finish;
 
         let query = sqlx::query!(
@@ -81,7 +94,7 @@ SET
 WHERE
   `ID` = ?"#,
             *requested_users as i64,
-            *requested_users as i64,
+            *total_users as i64,
             0,
             id,
         );
@@ -94,15 +107,125 @@ WHERE
         Ok(())
     }
 
+    pub async fn store_report(&self, report: &RunReport) -> Result<()> {
+        if let Some(ref dry_run) = self.dry_run {
+            return dry_run.store_report(report);
+        }
+
+        let mut conn = self
+            .acquire()
+            .await
+            .context("failed to acquire connection to insert into Rankings_Script_Runs")?;
+
+        let RunReport {
+            history_id,
+            task,
+            start,
+            end,
+            cancelled,
+            durations: _,
+            requested_users,
+            failed_users,
+            restricted_users,
+            new_medals,
+            badges,
+            rows: _,
+            errors: _,
+            omitted_errors: _,
+        } = report;
+
+        let query = sqlx::query(
+            r#"
+INSERT INTO `Rankings_Script_Runs` (
+  `History_ID`, `Type`, `Started_At`, `Finished_At`, `Is_Cancelled`,
+  `Count_Requested`, `Count_Failed`, `Count_Restricted`, `Count_New_Medals`,
+  `Count_New_Badges`, `Count_Badge_Owners_Added`, `Count_Badge_Owners_Updated`,
+  `Count_Badge_Owners_Removed`, `Count_Rows_Written`, `Durations`,
+  `Rows_Written`, `Errors`
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(history_id)
+        .bind(task.to_string())
+        .bind(start)
+        .bind(end)
+        .bind(cancelled)
+        .bind(*requested_users as i64)
+        .bind(*failed_users as i64)
+        .bind(*restricted_users as i64)
+        .bind(*new_medals as i64)
+        .bind(badges.new_badges as i64)
+        .bind(badges.owners_added as i64)
+        .bind(badges.owners_updated as i64)
+        .bind(badges.owners_removed as i64)
+        .bind(report.total_rows() as i64)
+        .bind(report.durations_json())
+        .bind(report.rows_json())
+        .bind(report.errors_json());
+
+        query
+            .execute(conn.deref_mut())
+            .await
+            .context("failed to execute Rankings_Script_Runs query")?;
+
+        Ok(())
+    }
+
     #[must_use]
-    pub fn store_rankings(&self, rankings: RankingsIter) -> JoinHandle<()> {
-        async fn inner(db: Database, rankings: RankingsIter) -> Result<()> {
-            let mut tx = db
-                .begin()
-                .await
-                .context("failed to begin transaction for Rankings_Users")?;
+    pub fn store_rankings(&self, rankings: RankingsIter) -> JoinHandle<StoreResult> {
+        async fn inner(db: Database, rankings: RankingsIter) -> Result<usize> {
+            if let Some(ref dry_run) = db.dry_run {
+                return dry_run.store_rankings(rankings);
+            }
 
-            for ranking in rankings {
+            let insert = r#"
+INSERT INTO Rankings_Users (
+    `ID`, `Accuracy_Catch`, `Accuracy_Mania`, `Accuracy_Standard`, 
+    `Accuracy_Stdev`, `Accuracy_Taiko`, `Count_Badges`, 
+    `Count_Maps_Loved`, `Count_Maps_Ranked`, `Count_Medals`, 
+    `Count_Replays_Watched`, `Count_Subscribers`, `Country_Code`, 
+    `Is_Restricted`, `Level_Catch`, `Level_Mania`, `Level_Standard`, 
+    `Level_Stdev`, `Level_Taiko`, `Name`, `PP_Catch`, `PP_Mania`, 
+    `PP_Standard`, `PP_Stdev`, `PP_Taiko`, `PP_Total`, 
+    `Rank_Global_Catch`, `Rank_Global_Mania`, `Rank_Global_Standard`, 
+    `Rank_Global_Taiko`, `Rarest_Medal_Achieved`, `Rarest_Medal_ID`
+) "#;
+
+            let on_duplicate = r#"
+ON DUPLICATE KEY UPDATE
+    `ID` = VALUES(`ID`), 
+    `Accuracy_Catch` = VALUES(`Accuracy_Catch`), 
+    `Accuracy_Mania` = VALUES(`Accuracy_Mania`), 
+    `Accuracy_Standard` = VALUES(`Accuracy_Standard`), 
+    `Accuracy_Stdev` = VALUES(`Accuracy_Stdev`), 
+    `Accuracy_Taiko` = VALUES(`Accuracy_Taiko`), 
+    `Count_Badges` = VALUES(`Count_Badges`), 
+    `Count_Maps_Loved` = VALUES(`Count_Maps_Loved`), 
+    `Count_Maps_Ranked` = VALUES(`Count_Maps_Ranked`), 
+    `Count_Medals` = VALUES(`Count_Medals`), 
+    `Count_Replays_Watched` = VALUES(`Count_Replays_Watched`), 
+    `Count_Subscribers` = VALUES(`Count_Subscribers`), 
+    `Country_Code` = VALUES(`Country_Code`), 
+    `Is_Restricted` = VALUES(`Is_Restricted`), 
+    `Level_Catch` = VALUES(`Level_Catch`), 
+    `Level_Mania` = VALUES(`Level_Mania`), 
+    `Level_Standard` = VALUES(`Level_Standard`), 
+    `Level_Stdev` = VALUES(`Level_Stdev`), 
+    `Level_Taiko` = VALUES(`Level_Taiko`), 
+    `Name` = VALUES(`Name`), 
+    `PP_Catch` = VALUES(`PP_Catch`), 
+    `PP_Mania` = VALUES(`PP_Mania`), 
+    `PP_Standard` = VALUES(`PP_Standard`), 
+    `PP_Stdev` = VALUES(`PP_Stdev`), 
+    `PP_Taiko` = VALUES(`PP_Taiko`), 
+    `PP_Total` = VALUES(`PP_Total`), 
+    `Rank_Global_Catch` = VALUES(`Rank_Global_Catch`), 
+    `Rank_Global_Mania` = VALUES(`Rank_Global_Mania`), 
+    `Rank_Global_Standard` = VALUES(`Rank_Global_Standard`), 
+    `Rank_Global_Taiko` = VALUES(`Rank_Global_Taiko`), 
+    `Rarest_Medal_Achieved` = VALUES(`Rarest_Medal_Achieved`), 
+    `Rarest_Medal_ID` = VALUES(`Rarest_Medal_ID`)"#;
+
+            db.insert_batched(insert, on_duplicate, 32, rankings, |mut row, ranking| {
                 let stdev_acc = ranking.std_dev_acc();
                 let stdev_level = ranking.std_dev_level();
                 let stdev_pp = ranking.std_dev_pp();
@@ -140,129 +263,90 @@ WHERE
                     mna_acc = 0.0;
                 }
 
-                let query = sqlx::query!(
-                    r#"
-            INSERT INTO Rankings_Users (
-                `ID`, `Accuracy_Catch`, `Accuracy_Mania`, `Accuracy_Standard`, 
-                `Accuracy_Stdev`, `Accuracy_Taiko`, `Count_Badges`, 
-                `Count_Maps_Loved`, `Count_Maps_Ranked`, `Count_Medals`, 
-                `Count_Replays_Watched`, `Count_Subscribers`, `Country_Code`, 
-                `Is_Restricted`, `Level_Catch`, `Level_Mania`, `Level_Standard`, 
-                `Level_Stdev`, `Level_Taiko`, `Name`, `PP_Catch`, `PP_Mania`, 
-                `PP_Standard`, `PP_Stdev`, `PP_Taiko`, `PP_Total`, 
-                `Rank_Global_Catch`, `Rank_Global_Mania`, `Rank_Global_Standard`, 
-                `Rank_Global_Taiko`, `Rarest_Medal_Achieved`, `Rarest_Medal_ID`
-            )
-            VALUES
-              (
-                ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
-                ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
-              ) ON DUPLICATE KEY
-            UPDATE
-                `ID` = VALUES(`ID`), 
-                `Accuracy_Catch` = VALUES(`Accuracy_Catch`), 
-                `Accuracy_Mania` = VALUES(`Accuracy_Mania`), 
-                `Accuracy_Standard` = VALUES(`Accuracy_Standard`), 
-                `Accuracy_Stdev` = VALUES(`Accuracy_Stdev`), 
-                `Accuracy_Taiko` = VALUES(`Accuracy_Taiko`), 
-                `Count_Badges` = VALUES(`Count_Badges`), 
-                `Count_Maps_Loved` = VALUES(`Count_Maps_Loved`), 
-                `Count_Maps_Ranked` = VALUES(`Count_Maps_Ranked`), 
-                `Count_Medals` = VALUES(`Count_Medals`), 
-                `Count_Replays_Watched` = VALUES(`Count_Replays_Watched`), 
-                `Count_Subscribers` = VALUES(`Count_Subscribers`), 
-                `Country_Code` = VALUES(`Country_Code`), 
-                `Is_Restricted` = VALUES(`Is_Restricted`), 
-                `Level_Catch` = VALUES(`Level_Catch`), 
-                `Level_Mania` = VALUES(`Level_Mania`), 
-                `Level_Standard` = VALUES(`Level_Standard`), 
-                `Level_Stdev` = VALUES(`Level_Stdev`), 
-                `Level_Taiko` = VALUES(`Level_Taiko`), 
-                `Name` = VALUES(`Name`), 
-                `PP_Catch` = VALUES(`PP_Catch`), 
-                `PP_Mania` = VALUES(`PP_Mania`), 
-                `PP_Standard` = VALUES(`PP_Standard`), 
-                `PP_Stdev` = VALUES(`PP_Stdev`), 
-                `PP_Taiko` = VALUES(`PP_Taiko`), 
-                `PP_Total` = VALUES(`PP_Total`), 
-                `Rank_Global_Catch` = VALUES(`Rank_Global_Catch`), 
-                `Rank_Global_Mania` = VALUES(`Rank_Global_Mania`), 
-                `Rank_Global_Standard` = VALUES(`Rank_Global_Standard`), 
-                `Rank_Global_Taiko` = VALUES(`Rank_Global_Taiko`), 
-                `Rarest_Medal_Achieved` = VALUES(`Rarest_Medal_Achieved`), 
-                `Rarest_Medal_ID` = VALUES(`Rarest_Medal_ID`)"#,
-                    id,
-                    ctb_acc,
-                    mna_acc,
-                    std_acc,
-                    stdev_acc,
-                    tko_acc,
-                    badge_count,
-                    loved_maps,
-                    ranked_maps,
-                    medal_count,
-                    replays_watched,
-                    subscribers,
-                    country_code.as_ref(),
-                    restricted as u8,
-                    ctb.level,
-                    mna.level,
-                    std.level,
-                    stdev_level,
-                    tko.level,
-                    name.as_ref(),
-                    ctb.pp,
-                    mna.pp,
-                    std.pp,
-                    stdev_pp,
-                    tko.pp,
-                    total_pp,
-                    ctb.global_rank.map(NonZeroU32::get),
-                    mna.global_rank.map(NonZeroU32::get),
-                    std.global_rank.map(NonZeroU32::get),
-                    tko.global_rank.map(NonZeroU32::get),
-                    rarest_medal_achieved,
-                    rarest_medal_id,
-                );
-
-                query
-                    .execute(tx.deref_mut())
-                    .await
-                    .context("failed to execute Rankings_Users query")?;
-            }
-
-            tx.commit()
-                .await
-                .context("failed to commit Rankings_Users transaction")?;
-
-            Ok(())
+                row.push_bind(id)
+                    .push_bind(ctb_acc)
+                    .push_bind(mna_acc)
+                    .push_bind(std_acc)
+                    .push_bind(stdev_acc)
+                    .push_bind(tko_acc)
+                    .push_bind(badge_count)
+                    .push_bind(loved_maps)
+                    .push_bind(ranked_maps)
+                    .push_bind(medal_count)
+                    .push_bind(replays_watched)
+                    .push_bind(subscribers)
+                    .push_bind(country_code.into_string())
+                    .push_bind(restricted as u8)
+                    .push_bind(ctb.level)
+                    .push_bind(mna.level)
+                    .push_bind(std.level)
+                    .push_bind(stdev_level)
+                    .push_bind(tko.level)
+                    .push_bind(name.into_string())
+                    .push_bind(ctb.pp)
+                    .push_bind(mna.pp)
+                    .push_bind(std.pp)
+                    .push_bind(stdev_pp)
+                    .push_bind(tko.pp)
+                    .push_bind(total_pp)
+                    .push_bind(ctb.global_rank.map(NonZeroU32::get))
+                    .push_bind(mna.global_rank.map(NonZeroU32::get))
+                    .push_bind(std.global_rank.map(NonZeroU32::get))
+                    .push_bind(tko.global_rank.map(NonZeroU32::get))
+                    .push_bind(rarest_medal_achieved)
+                    .push_bind(rarest_medal_id);
+            })
+            .await
+            .context("failed to store Rankings_Users")
         }
 
         let db = self.to_owned();
 
         tokio::spawn(async move {
-            let len = rankings.len();
+            let start = Instant::now();
             let res = inner(db, rankings).await;
             let _entered = info_span!("store_rankings").entered();
 
             match res {
-                Ok(_) => info!("Successfully stored {len} ranking entries"),
-                Err(err) => error!(?err, "Failed to store rankings"),
+                Ok(len) => info!(
+                    "Successfully stored {len} ranking entries ({:.0} rows/s)",
+                    rows_per_sec(len, start.elapsed())
+                ),
+                Err(ref err) => error!(?err, "Failed to store rankings"),
             }
+
+            res.map_err(|err| format!("{err:#}"))
         })
     }
 
     // This method does not return a JoinHandle but is async instead and should
     // be called before `Database::store_rarities` so that the table does not
     // deadlock.
-    pub async fn store_medals(&self, medals: &[ScrapedMedal]) {
-        async fn inner(db: &Database, medals: &[ScrapedMedal]) -> Result<()> {
-            let mut tx = db
-                .begin()
-                .await
-                .context("failed to begin transaction for Medals_Data")?;
+    pub async fn store_medals(&self, medals: &[ScrapedMedal]) -> StoreResult {
+        async fn inner(db: &Database, medals: &[ScrapedMedal]) -> Result<usize> {
+            if let Some(ref dry_run) = db.dry_run {
+                return dry_run.store_medals(medals);
+            }
+
+            let insert = r#"
+INSERT INTO `Medals_Data` (
+  `Medal_ID`, `Name`, `Link`, `Description`,
+  `Gamemode`, `Grouping`, `Instructions`,
+  `Ordering`
+) "#;
 
-            for medal in medals {
+            let on_duplicate = r#"
+ON DUPLICATE KEY UPDATE
+  `Medal_ID` = VALUES(`Medal_ID`),
+  `Name` = VALUES(`Name`),
+  `Link` = VALUES(`Link`),
+  `Description` = VALUES(`Description`),
+  `Gamemode` = VALUES(`Gamemode`),
+  `Grouping` = VALUES(`Grouping`),
+  `Instructions` = VALUES(`Instructions`),
+  `Ordering` = VALUES(`Ordering`)"#;
+
+            db.insert_batched(insert, on_duplicate, 8, medals, |mut row, medal| {
                 let ScrapedMedal {
                     icon_url,
                     id,
@@ -276,59 +360,41 @@ WHERE
 
                 let link = icon_url.rsplit('/').next().unwrap_or(icon_url);
 
-                let query = sqlx::query!(
-                    r#"
-            INSERT INTO `Medals_Data` (
-              `Medal_ID`, `Name`, `Link`, `Description`,
-              `Gamemode`, `Grouping`, `Instructions`,
-              `Ordering`
-            )
-            VALUES
-              (?, ?, ?, ?, ?, ?, ?, ?) ON DUPLICATE KEY
-            UPDATE
-              `Medal_ID` = VALUES(`Medal_ID`),
-              `Name` = VALUES(`Name`),
-              `Link` = VALUES(`Link`),
-              `Description` = VALUES(`Description`),
-              `Gamemode` = VALUES(`Gamemode`),
-              `Grouping` = VALUES(`Grouping`),
-              `Instructions` = VALUES(`Instructions`),
-              `Ordering` = VALUES(`Ordering`)"#,
-                    id,
-                    name.as_ref(),
-                    link,
-                    description.as_ref(),
-                    mode.as_deref(),
-                    grouping.as_ref(),
-                    instructions.as_deref(),
-                    ordering,
-                );
-
-                query
-                    .execute(tx.deref_mut())
-                    .await
-                    .context("failed to execute Medals_Data query")?;
-            }
-
-            tx.commit()
-                .await
-                .context("failed to commit Medals_Data transaction")?;
-
-            Ok(())
+                row.push_bind(id)
+                    .push_bind(name.as_ref())
+                    .push_bind(link)
+                    .push_bind(description.as_ref())
+                    .push_bind(mode.as_deref())
+                    .push_bind(grouping.as_ref())
+                    .push_bind(instructions.as_deref())
+                    .push_bind(ordering);
+            })
+            .await
+            .context("failed to store Medals_Data")
         }
 
+        let start = Instant::now();
         let res = inner(self, medals).await;
         let _entered = info_span!("store_medals").entered();
 
         match res {
-            Ok(_) => info!("Successfully stored {} medals", medals.len()),
-            Err(err) => error!(?err, "Failed to store medals"),
+            Ok(len) => info!(
+                "Successfully stored {len} medals ({:.0} rows/s)",
+                rows_per_sec(len, start.elapsed())
+            ),
+            Err(ref err) => error!(?err, "Failed to store medals"),
         }
+
+        res.map_err(|err| format!("{err:#}"))
     }
 
     #[must_use]
-    pub fn store_rarities(&self, rarities: MedalRarities) -> JoinHandle<()> {
-        async fn inner(db: Database, rarities: &MedalRarities) -> Result<()> {
+    pub fn store_rarities(&self, rarities: MedalRarities) -> JoinHandle<StoreResult> {
+        async fn inner(db: Database, rarities: &MedalRarities) -> Result<usize> {
+            if let Some(ref dry_run) = db.dry_run {
+                return dry_run.store_rarities(rarities);
+            }
+
             let mut tx = db
                 .begin()
                 .await
@@ -359,7 +425,7 @@ WHERE
                 .await
                 .context("failed to commit Medals_Data transaction")?;
 
-            Ok(())
+            Ok(rarities.len())
         }
 
         let db = self.to_owned();
@@ -369,91 +435,295 @@ WHERE
             let _entered = info_span!("store_rarities").entered();
 
             match res {
-                Ok(_) => info!("Successfully stored {} medal rarities", rarities.len()),
-                Err(err) => error!(?err, "Failed to store rarities"),
+                Ok(len) => info!("Successfully stored {len} medal rarities"),
+                Err(ref err) => error!(?err, "Failed to store rarities"),
             }
+
+            res.map_err(|err| format!("{err:#}"))
         })
     }
 
     #[must_use]
-    pub fn store_badges(&self, badges: Badges) -> JoinHandle<()> {
-        async fn inner(db: Database, badges: &Badges) -> Result<()> {
-            let mut tx = db
-                .begin()
+    pub fn store_badges(&self, diff: BadgesDiff) -> JoinHandle<StoreResult> {
+        async fn inner(db: Database, diff: &BadgesDiff) -> Result<usize> {
+            if let Some(ref dry_run) = db.dry_run {
+                return dry_run.store_badges(diff);
+            }
+
+            let insert = r#"
+INSERT INTO `Badges_Data` (
+  `Name`, `Image_URL`
+) "#;
+
+            let on_duplicate = r#"
+ON DUPLICATE KEY UPDATE
+  `Name` = `Name`"#;
+
+            let data_count = db
+                .insert_batched(
+                    insert,
+                    on_duplicate,
+                    2,
+                    diff.names.iter(),
+                    |mut row, (BadgeName(name), BadgeImageUrl(image_url))| {
+                        row.push_bind(name.as_ref()).push_bind(image_url.as_ref());
+                    },
+                )
                 .await
-                .context("failed to begin transaction for badges")?;
+                .context("failed to store Badges_Data")?;
 
-            sqlx::query!("DELETE FROM `Badge_Name`")
-                .execute(tx.deref_mut())
+            let insert = r#"
+INSERT INTO `Badge_Name` (
+  `Name`, `User_ID`, `Description`, `Date_Awarded`
+) "#;
+
+            let on_duplicate = r#"
+ON DUPLICATE KEY UPDATE
+  `Description` = VALUES(`Description`),
+  `Date_Awarded` = VALUES(`Date_Awarded`)"#;
+
+            let upsert_count = db
+                .insert_batched(insert, on_duplicate, 4, &diff.upserts, |mut row, owner| {
+                    let BadgeRow {
+                        name,
+                        user_id,
+                        description,
+                        awarded_at,
+                    } = owner;
+
+                    row.push_bind(name.as_ref())
+                        .push_bind(user_id)
+                        .push_bind(description.as_ref())
+                        .push_bind(awarded_at);
+                })
                 .await
-                .context("failed to delete rows in Badges_Users")?;
-
-            for (BadgeDescription(description), entries) in badges.descriptions.iter() {
-                for (BadgeName(name), owners) in entries.iter() {
-                    for owner in owners {
-                        let BadgeOwner {
-                            user_id,
-                            awarded_at,
-                        } = owner;
-
-                        let query = sqlx::query!(
-                            r#"
-                INSERT INTO `Badge_Name` (
-                  `Name`, `User_ID`, `Description`, `Date_Awarded`
-                )
-                VALUES
-                  (?, ?, ?, ?)"#,
-                            name.as_ref(),
-                            user_id,
-                            description.as_ref(),
-                            awarded_at,
-                        );
-
-                        query
-                            .execute(tx.deref_mut())
-                            .await
-                            .context("failed to execute badge name query")?;
-                    }
+                .context("failed to store Badge_Name")?;
+
+            let delete_count = db
+                .delete_badge_owners(&diff.removals)
+                .await
+                .context("failed to delete from Badge_Name")?;
+
+            Ok(data_count + upsert_count + delete_count)
+        }
+
+        let db = self.to_owned();
+
+        tokio::spawn(async move {
+            let start = Instant::now();
+            let res = inner(db, &diff).await;
+            let _entered = info_span!("store_badges").entered();
+
+            let rows = match res {
+                Ok(rows) => rows,
+                Err(err) => {
+                    error!(?err, "Failed to store badges");
+
+                    return Err(format!("{err:#}"));
                 }
+            };
+
+            for (name, BadgeChanges { added, removed }) in diff.changes.iter() {
+                info!("Badge `{name}`: {added} owner(s) added, {removed} owner(s) removed");
             }
 
-            for (BadgeName(name), BadgeImageUrl(image_url)) in badges.names.iter() {
-                let query = sqlx::query!(
+            info!(
+                "Successfully stored badges: {} new badge(s), {} owner(s) added, \
+                {} owner(s) updated, {} owner(s) removed ({:.0} rows/s)",
+                diff.names.len(),
+                diff.upserts.len() - diff.updated,
+                diff.updated,
+                diff.removals.len(),
+                rows_per_sec(rows, start.elapsed())
+            );
+
+            Ok(rows)
+        })
+    }
+
+    /// Delete the owners of the given badge names and user ids in batches.
+    async fn delete_badge_owners(&self, owners: &[(Box<str>, u32)]) -> Result<usize> {
+        let batch_size = self.batch_size.max(1);
+        let mut deleted = 0;
+
+        for batch in owners.chunks(batch_size) {
+            let mut builder = QueryBuilder::<MySql>::new(
+                "DELETE FROM `Badge_Name` WHERE (`Name`, `User_ID`) IN ",
+            );
+
+            builder.push_tuples(batch, |mut row, (name, user_id)| {
+                row.push_bind(name.as_ref()).push_bind(user_id);
+            });
+
+            builder
+                .build()
+                .execute(&self.mysql)
+                .await
+                .with_context(|| format!("failed to execute batch after {deleted} rows"))?;
+
+            deleted += batch.len();
+        }
+
+        Ok(deleted)
+    }
+
+    // This method does not return a JoinHandle but is async instead since
+    // there are usually only few failures.
+    pub async fn store_failures(&self, failures: &UserFailures, resolved: &[u32]) -> StoreResult {
+        async fn inner(db: &Database, failures: &UserFailures, resolved: &[u32]) -> Result<usize> {
+            if let Some(ref dry_run) = db.dry_run {
+                return dry_run.store_failures(failures, resolved);
+            }
+
+            let mut tx = db
+                .begin()
+                .await
+                .context("failed to begin transaction for Rankings_Users_Failures")?;
+
+            for (user_id, kind) in failures.iter() {
+                let query = sqlx::query(
                     r#"
-        INSERT INTO `Badges_Data` (
-          `Name`, `Image_URL`
-        )
-        VALUES
-          (?, ?)
-        ON DUPLICATE KEY UPDATE
-          `Name` = `Name`"#,
-                    name.as_ref(),
-                    image_url.as_ref(),
-                );
+INSERT INTO `Rankings_Users_Failures` (
+  `User_ID`, `Error_Kind`, `Count_Failed`, `Last_Failed`
+)
+VALUES
+  (?, ?, 1, NOW()) ON DUPLICATE KEY
+UPDATE
+  `Error_Kind` = VALUES(`Error_Kind`),
+  `Count_Failed` = `Count_Failed` + 1,
+  `Last_Failed` = VALUES(`Last_Failed`)"#,
+                )
+                .bind(user_id)
+                .bind(kind.as_str());
 
                 query
                     .execute(tx.deref_mut())
                     .await
-                    .context("failed to execute badges data query")?;
+                    .context("failed to execute Rankings_Users_Failures query")?;
+            }
+
+            // Users that were requested successfully no longer fail
+            for batch in resolved.chunks(db.batch_size.max(1)) {
+                let mut builder = QueryBuilder::<MySql>::new(
+                    "DELETE FROM `Rankings_Users_Failures` WHERE `User_ID` IN (",
+                );
+
+                let mut separated = builder.separated(", ");
+
+                for user_id in batch {
+                    separated.push_bind(user_id);
+                }
+
+                builder
+                    .push(")")
+                    .build()
+                    .execute(tx.deref_mut())
+                    .await
+                    .context("failed to delete resolved Rankings_Users_Failures")?;
             }
 
             tx.commit()
                 .await
-                .context("failed to commit Badges transaction")?;
+                .context("failed to commit Rankings_Users_Failures transaction")?;
 
-            Ok(())
+            Ok(failures.len() + resolved.len())
         }
 
-        let db = self.to_owned();
+        let res = inner(self, failures, resolved).await;
+        let _entered = info_span!("store_failures").entered();
 
-        tokio::spawn(async move {
-            let res = inner(db, &badges).await;
-            let _entered = info_span!("store_badges").entered();
+        match res {
+            Ok(_) => info!(
+                "Successfully stored {} user failures and removed {} resolved ones",
+                failures.len(),
+                resolved.len()
+            ),
+            Err(ref err) => error!(?err, "Failed to store user failures"),
+        }
 
-            match res {
-                Ok(_) => info!("Successfully stored {} badges", badges.len()),
-                Err(err) => error!(?err, "Failed to store badges"),
+        res.map_err(|err| format!("{err:#}"))
+    }
+
+    /// Insert the rows through multi-row statements of at most
+    /// [`Database::batch_size`] rows each and return the amount of rows.
+    ///
+    /// Every statement commits on its own so that a failing batch does not
+    /// roll back previous ones.
+    async fn insert_batched<'args, I>(
+        &self,
+        insert: &str,
+        on_duplicate: &str,
+        columns: usize,
+        rows: I,
+        mut push_row: impl FnMut(Separated<'_, 'args, MySql, &'static str>, I::Item),
+    ) -> Result<usize>
+    where
+        I: IntoIterator,
+        I::Item: 'args,
+    {
+        let batch_size = batch_rows(self.batch_size, columns);
+        let mut rows = rows.into_iter().peekable();
+        let mut stored = 0;
+
+        while rows.peek().is_some() {
+            let mut len = 0;
+            let batch = rows.by_ref().take(batch_size).inspect(|_| len += 1);
+
+            let mut builder = QueryBuilder::new(insert);
+            builder.push_values(batch, &mut push_row).push(on_duplicate);
+
+            builder
+                .build()
+                .execute(&self.mysql)
+                .await
+                .with_context(|| format!("failed to execute batch after {stored} rows"))?;
+
+            stored += len;
+        }
+
+        Ok(stored)
+    }
+}
+
+/// MySQL does not allow more placeholders in a single statement
+const MAX_PARAMS: usize = u16::MAX as usize;
+
+/// Amount of rows with the given amount of columns per statement so that
+/// the statement stays within [`MAX_PARAMS`].
+fn batch_rows(batch_size: usize, columns: usize) -> usize {
+    batch_size.min(MAX_PARAMS / columns).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Execute, MySql, QueryBuilder};
+
+    use super::*;
+
+    #[test]
+    fn batches_stay_within_param_cap() {
+        // Columns of rankings, medals, badge owners, and badge names
+        for columns in [32, 8, 4, 2] {
+            for batch_size in [0, 1, 1000, 5000, 100_000] {
+                let rows = batch_rows(batch_size, columns);
+                assert!(rows >= 1);
+                assert!(rows <= batch_size.max(1));
+
+                let mut builder = QueryBuilder::<MySql>::new("INSERT INTO `Table` ");
+
+                builder.push_values(0..rows, |mut row, _| {
+                    for _ in 0..columns {
+                        row.push_bind(0_u32);
+                    }
+                });
+
+                let params = builder.build().sql().matches('?').count();
+                assert_eq!(params, rows * columns);
+                assert!(
+                    params <= MAX_PARAMS,
+                    "{params} params for {columns} columns"
+                );
             }
-        })
+        }
     }
 }
@@ -0,0 +1,439 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use eyre::{Context as _, Result};
+use serde_json::Value;
+
+use crate::{
+    config,
+    database::{Database, ExportQuery, HttpStorage, InMemoryStorage, SqliteDatabase},
+    util::{ExportArgs, ExportFormat, ExportTable},
+};
+
+/// Base url of medal icons as the database only stores their file name
+const MEDAL_ICON_URL: &str = "https://assets.ppy.sh/medals/web/";
+
+/// Mapping of `up_ranking.php` keys to `Rankings_Users` columns
+const RANKING_KEYS: &[(&str, &str)] = &[
+    ("id", "ID"),
+    ("name", "Name"),
+    ("total_pp", "PP_Total"),
+    ("stdev_pp", "PP_Stdev"),
+    ("standard_pp", "PP_Standard"),
+    ("taiko_pp", "PP_Taiko"),
+    ("ctb_pp", "PP_Catch"),
+    ("mania_pp", "PP_Mania"),
+    ("medal_count", "Count_Medals"),
+    ("rarest_medal", "Rarest_Medal_ID"),
+    ("country_code", "Country_Code"),
+    ("standard_global", "Rank_Global_Standard"),
+    ("taiko_global", "Rank_Global_Taiko"),
+    ("ctb_global", "Rank_Global_Catch"),
+    ("mania_global", "Rank_Global_Mania"),
+    ("badge_count", "Count_Badges"),
+    ("ranked_maps", "Count_Maps_Ranked"),
+    ("loved_maps", "Count_Maps_Loved"),
+    ("subscribers", "Count_Subscribers"),
+    ("replays_watched", "Count_Replays_Watched"),
+];
+
+/// Mapping of `up_medals.php` keys to `Medals_Data` columns
+const MEDAL_KEYS: &[(&str, &str)] = &[
+    ("medalid", "Medal_ID"),
+    ("name", "Name"),
+    ("link", "Link"),
+    ("description", "Description"),
+    ("restriction", "Gamemode"),
+    ("grouping", "Grouping"),
+    ("instructions", "Instructions"),
+    ("ordering", "Ordering"),
+];
+
+/// Mapping of `up_medals_rarity.php` keys to `Medals_Data` columns
+const RARITY_KEYS: &[(&str, &str)] = &[
+    ("medalid", "Medal_ID"),
+    ("frequency", "Frequency"),
+    ("count", "Count_Achieved_By"),
+];
+
+/// Database that rows can be exported from
+enum ExportDatabase {
+    MySql(Database),
+    Sqlite(SqliteDatabase),
+}
+
+impl ExportDatabase {
+    async fn connect(url: &str) -> Result<Self> {
+        ensure!(
+            !url.starts_with(HttpStorage::SCHEME) && url != InMemoryStorage::URL,
+            "exporting is only supported for MySQL and SQLite databases"
+        );
+
+        // Nothing is written so the batch size does not matter
+        if url.starts_with(SqliteDatabase::SCHEME) {
+            SqliteDatabase::new(url, 1, None).await.map(Self::Sqlite)
+        } else {
+            Database::new(url, 1, None).await.map(Self::MySql)
+        }
+    }
+
+    async fn export_rows(
+        &self,
+        query: &ExportQuery<'_>,
+        on_row: impl FnMut(&[&str], Vec<Value>) -> Result<()>,
+    ) -> Result<usize> {
+        match self {
+            Self::MySql(db) => db.export_rows(query, on_row).await,
+            Self::Sqlite(db) => db.export_rows(query, on_row).await,
+        }
+    }
+
+    async fn export_columns(&self, query: &ExportQuery<'_>) -> Result<Vec<String>> {
+        match self {
+            Self::MySql(db) => db.export_columns(query).await,
+            Self::Sqlite(db) => db.export_columns(query).await,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum OutputKind {
+    Csv,
+    Ndjson,
+    /// Keys of the API.md shape mapped to their column
+    Api(&'static [(&'static str, &'static str)]),
+}
+
+/// Export a table into a file or stdout
+pub async fn run(args: ExportArgs) -> Result<()> {
+    let ExportArgs {
+        table,
+        format,
+        columns,
+        country,
+        restricted,
+        output,
+    } = args;
+
+    let source = match table {
+        ExportTable::Rankings => "`Rankings_Users`",
+        ExportTable::Medals => "`Medals_Data`",
+        ExportTable::Rarities => {
+            "(SELECT `Medal_ID`, `Frequency`, `Count_Achieved_By` FROM `Medals_Data`) AS rarities"
+        }
+        ExportTable::Badges => {
+            r#"(
+  SELECT
+    owners.`Name`, owners.`User_ID`, owners.`Description`, owners.`Date_Awarded`,
+    badges.`Image_URL`, users.`Country_Code`, users.`Is_Restricted`
+  FROM `Badge_Name` AS owners
+  LEFT JOIN `Badges_Data` AS badges ON badges.`Name` = owners.`Name`
+  LEFT JOIN `Rankings_Users` AS users ON users.`ID` = owners.`User_ID`
+) AS badges"#
+        }
+        ExportTable::History => "`Rankings_Script_History`",
+    };
+
+    let filterable = matches!(table, ExportTable::Rankings | ExportTable::Badges);
+
+    ensure!(
+        filterable || (country.is_none() && restricted.is_none()),
+        "filtering by country or restricted status is only supported for rankings and badges"
+    );
+
+    let output_kind = match (format, table) {
+        (ExportFormat::Csv, _) => OutputKind::Csv,
+        (ExportFormat::Ndjson, _) => OutputKind::Ndjson,
+        (ExportFormat::Api, ExportTable::Rankings) => OutputKind::Api(RANKING_KEYS),
+        (ExportFormat::Api, ExportTable::Medals) => OutputKind::Api(MEDAL_KEYS),
+        (ExportFormat::Api, ExportTable::Rarities) => OutputKind::Api(RARITY_KEYS),
+        (ExportFormat::Api, _) => {
+            bail!("the api format is only supported for rankings, medals, and rarities")
+        }
+    };
+
+    ensure!(
+        !matches!(output_kind, OutputKind::Api(_)) || columns.is_empty(),
+        "column selection is not supported for the api format"
+    );
+
+    let db = ExportDatabase::connect(&config::database_url()?).await?;
+
+    let writer: Box<dyn Write> = match output {
+        Some(ref path) => {
+            let file = File::create(path)
+                .with_context(|| format!("failed to create `{}`", path.display()))?;
+
+            Box::new(file)
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+
+    let query = ExportQuery {
+        source,
+        columns: &columns,
+        country: country.as_deref(),
+        restricted,
+    };
+
+    let mut writer = ExportWriter::new(BufWriter::new(writer), output_kind, table)?;
+
+    let count = db
+        .export_rows(&query, |names, values| writer.write_row(names, values))
+        .await
+        .context("failed to export rows")?;
+
+    // The CSV header is taken from the rows so without rows the columns
+    // need to be fetched separately
+    let header = if count == 0 && matches!(output_kind, OutputKind::Csv) {
+        db.export_columns(&query)
+            .await
+            .context("failed to fetch exported columns")?
+    } else {
+        Vec::new()
+    };
+
+    writer
+        .finish(&header)?
+        .flush()
+        .context("failed to flush export")?;
+
+    info!("Exported {count} row(s)");
+
+    Ok(())
+}
+
+/// Writes exported rows in the format of its [`OutputKind`]
+struct ExportWriter<W> {
+    writer: W,
+    kind: OutputKind,
+    table: ExportTable,
+    rows: usize,
+}
+
+impl<W: Write> ExportWriter<W> {
+    fn new(mut writer: W, kind: OutputKind, table: ExportTable) -> Result<Self> {
+        if let OutputKind::Api(_) = kind {
+            writer.write_all(b"[")?;
+        }
+
+        Ok(Self {
+            writer,
+            kind,
+            table,
+            rows: 0,
+        })
+    }
+
+    fn write_row(&mut self, names: &[&str], values: Vec<Value>) -> Result<()> {
+        let writer = &mut self.writer;
+
+        match self.kind {
+            OutputKind::Csv => {
+                if self.rows == 0 {
+                    write_csv_row(writer, names.iter().map(|name| Value::from(*name)))?;
+                }
+
+                write_csv_row(writer, values)?;
+            }
+            OutputKind::Ndjson => {
+                write_object(writer, names.iter().copied().zip(values))?;
+                writer.write_all(b"\n")?;
+            }
+            OutputKind::Api(keys) => {
+                if self.rows > 0 {
+                    writer.write_all(b",")?;
+                }
+
+                writer.write_all(b"\n")?;
+                let entries = api_entries(self.table, keys, names, values);
+                write_object(writer, entries)?;
+            }
+        }
+
+        self.rows += 1;
+
+        Ok(())
+    }
+
+    /// Complete the output; `header` holds the column names for the CSV
+    /// header in case no rows were written.
+    fn finish(mut self, header: &[String]) -> Result<W> {
+        match self.kind {
+            OutputKind::Csv if self.rows == 0 => {
+                let header = header.iter().map(|name| Value::from(name.as_str()));
+                write_csv_row(&mut self.writer, header)?;
+            }
+            OutputKind::Api(_) => self.writer.write_all(b"\n]\n")?,
+            OutputKind::Csv | OutputKind::Ndjson => {}
+        }
+
+        Ok(self.writer)
+    }
+}
+
+/// Rename columns to the keys of the API.md shapes
+fn api_entries(
+    table: ExportTable,
+    keys: &[(&'static str, &str)],
+    names: &[&str],
+    values: Vec<Value>,
+) -> Vec<(&'static str, Value)> {
+    let mut values: Vec<_> = values.into_iter().map(Some).collect();
+
+    let mut column = |column: &str| {
+        names
+            .iter()
+            .position(|name| *name == column)
+            .and_then(|idx| values[idx].take())
+            .unwrap_or(Value::Null)
+    };
+
+    let mut entries: Vec<_> = keys
+        .iter()
+        .map(|(key, name)| (*key, column(name)))
+        .collect();
+
+    match table {
+        // The ranking endpoint expects all values as strings
+        ExportTable::Rankings => {
+            let avatar_url = entries
+                .iter()
+                .find(|(key, _)| *key == "id")
+                .map(|(_, id)| format!("https://a.ppy.sh/{}", stringify(id)));
+
+            for (_, value) in entries.iter_mut() {
+                *value = Value::String(stringify(value));
+            }
+
+            entries.push(("avatar_url", avatar_url.map_or(Value::Null, Value::String)));
+        }
+        ExportTable::Medals => {
+            if let Some((_, Value::String(link))) =
+                entries.iter_mut().find(|(key, _)| *key == "link")
+            {
+                link.insert_str(0, MEDAL_ICON_URL);
+            }
+        }
+        _ => {}
+    }
+
+    entries
+}
+
+/// Turn a value into a string; floats are rounded
+fn stringify(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Number(n) if n.is_f64() => format!("{:.0}", n.as_f64().unwrap_or(0.0)),
+        _ => value.to_string(),
+    }
+}
+
+fn write_object<'a>(
+    writer: &mut impl Write,
+    entries: impl IntoIterator<Item = (&'a str, Value)>,
+) -> Result<()> {
+    writer.write_all(b"{")?;
+
+    for ((key, value), i) in entries.into_iter().zip(0..) {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+
+        serde_json::to_writer(&mut *writer, key)?;
+        writer.write_all(b":")?;
+        serde_json::to_writer(&mut *writer, &value)?;
+    }
+
+    writer.write_all(b"}")?;
+
+    Ok(())
+}
+
+fn write_csv_row(writer: &mut impl Write, values: impl IntoIterator<Item = Value>) -> Result<()> {
+    for (value, i) in values.into_iter().zip(0..) {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+
+        let field = match value {
+            Value::Null => continue,
+            Value::String(s) => s,
+            value => value.to_string(),
+        };
+
+        if field.contains([',', '"', '\n', '\r']) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(field.as_bytes())?;
+        }
+    }
+
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn export(kind: OutputKind, table: ExportTable, rows: &[Vec<Value>]) -> String {
+        let names = ["Medal_ID", "Name"].map(str::to_owned);
+        let mut writer = ExportWriter::new(Vec::new(), kind, table).unwrap();
+
+        for values in rows {
+            let names: Vec<_> = names.iter().map(String::as_str).collect();
+            writer.write_row(&names, values.clone()).unwrap();
+        }
+
+        let bytes = writer.finish(&names).unwrap();
+
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn export_empty_set() {
+        let csv = export(OutputKind::Csv, ExportTable::Medals, &[]);
+        assert_eq!(csv, "Medal_ID,Name\n");
+
+        let ndjson = export(OutputKind::Ndjson, ExportTable::Medals, &[]);
+        assert_eq!(ndjson, "");
+
+        let api = export(OutputKind::Api(RARITY_KEYS), ExportTable::Rarities, &[]);
+        assert_eq!(serde_json::from_str::<Value>(&api).unwrap(), json!([]));
+    }
+
+    #[test]
+    fn export_rows() {
+        let rows = [
+            vec![json!(1), json!("Quick, \"Draw\"")],
+            vec![json!(2), Value::Null],
+        ];
+
+        let csv = export(OutputKind::Csv, ExportTable::Medals, &rows);
+        assert_eq!(csv, "Medal_ID,Name\n1,\"Quick, \"\"Draw\"\"\"\n2,\n");
+
+        let ndjson = export(OutputKind::Ndjson, ExportTable::Medals, &rows);
+        let lines: Vec<Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines[1], json!({ "Medal_ID": 2, "Name": null }));
+
+        let api = export(OutputKind::Api(RARITY_KEYS), ExportTable::Rarities, &rows);
+        let api: Value = serde_json::from_str(&api).unwrap();
+
+        assert_eq!(
+            api[0],
+            json!({ "medalid": 1, "frequency": null, "count": null })
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt::Result as FmtResult;
+use std::{env, fmt::Result as FmtResult};
 
 use time::{format_description::FormatItem, macros::format_description};
 use tracing::{Event, Subscriber};
@@ -31,7 +31,8 @@ pub fn init(quiet: bool) -> WorkerGuard {
         .event_format(StdoutEventFormat::new(formatter))
         .with_filter(stdout_filter);
 
-    let file_appender = rolling::daily("./logs", "osekai-scripts.log");
+    let directory = env::var("LOG_DIRECTORY").unwrap_or_else(|_| "./logs".to_owned());
+    let file_appender = rolling::daily(directory, "osekai-scripts.log");
     let (file_writer, guard) = NonBlocking::new(file_appender);
 
     let file_filter = EnvFilter::try_from_default_env()
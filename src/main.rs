@@ -6,30 +6,48 @@ extern crate eyre;
 #[macro_use]
 extern crate tracing;
 
-use eyre::{Context as _, Report, Result};
+use std::{path::PathBuf, sync::Arc};
+
+use eyre::{Context as _, Result};
 use self_update::Status;
 use task::Task;
-use tokio::{runtime::Builder as RuntimeBuilder, signal};
+use tokio::runtime::{Builder as RuntimeBuilder, Runtime};
 use util::ArgsResult;
 
-use crate::util::Args;
+use crate::util::{Args, Shutdown};
 
-use self::context::Context;
+use self::{
+    checkpoint::Checkpoint,
+    config::Config,
+    context::Context,
+    database::{DryRun, InMemoryStorage, Storage},
+};
 
+mod cassette;
+mod checkpoint;
 mod client;
 mod config;
 mod context;
 mod database;
+mod export;
 mod logging;
+mod metrics;
 mod model;
 mod schedule;
+mod status;
 mod task;
 mod util;
 
 fn main() {
-    if let Err(err) = dotenvy::dotenv() {
-        panic!("Failed to prepare .env variables: {err}");
-    }
+    // Reloading the config reads the files again on top of these
+    config::capture_process_env();
+
+    let env_path = match dotenvy::dotenv() {
+        Ok(path) => Some(path),
+        // All variables may be given through a config file instead
+        Err(err) if err.not_found() => None,
+        Err(err) => panic!("Failed to prepare .env variables: {err}"),
+    };
 
     // Needs to happen outside of a runtime because
     // self-updating will use its own runtime
@@ -42,33 +60,100 @@ fn main() {
                 Err(err) => eprintln!("{err:?}"),
             }
 
+            return;
+        }
+        ArgsResult::Export(args) => {
+            // Logs would interfere with the export if it's written to stdout
+            let _log_worker_guard = logging::init(args.output.is_none());
+
+            if let Err(err) = runtime().block_on(export::run(args)) {
+                error!(?err, "Failed to export");
+            }
+
+            return;
+        }
+        ArgsResult::Status(args) => {
+            // Only errors should interfere with the summary
+            let _log_worker_guard = logging::init(true);
+
+            if let Err(err) = runtime().block_on(status::run(args)) {
+                error!(?err, "Failed to gather status");
+            }
+
+            return;
+        }
+        ArgsResult::Migrate => {
+            let _log_worker_guard = logging::init(false);
+
+            if let Err(err) = runtime().block_on(migrate()) {
+                error!(?err, "Failed to migrate");
+            }
+
             return;
         }
     };
 
     let _log_worker_guard = logging::init(args.quiet);
 
-    let runtime = RuntimeBuilder::new_current_thread()
+    if let Err(err) = runtime().block_on(async_main(args, task, env_path)) {
+        error!(?err, "Critical error in main");
+    }
+}
+
+fn runtime() -> Runtime {
+    RuntimeBuilder::new_current_thread()
         .enable_all()
         .build()
-        .expect("failed to build runtime");
+        .expect("failed to build runtime")
+}
 
-    if let Err(err) = runtime.block_on(async_main(args, task)) {
-        error!(?err, "Critical error in main");
+async fn async_main(args: Args, mut task: Option<Task>, env_path: Option<PathBuf>) -> Result<()> {
+    config::init(&args, env_path).context("failed to initialize config")?;
+
+    let checkpoint = if args.resume {
+        let checkpoint = Checkpoint::load_latest(task).context("failed to load checkpoint")?;
+        task = Some(checkpoint.task);
+
+        Some(checkpoint)
+    } else {
+        None
+    };
+
+    let shutdown = Shutdown::listen();
+    let config = Config::get();
+
+    if let Some(addr) = config.metrics_addr {
+        metrics::serve(addr).await?;
     }
-}
 
-async fn async_main(mut args: Args, task: Option<Task>) -> Result<()> {
-    config::init(&mut args).context("failed to initialize config")?;
+    let memory = (*config.database_url == *InMemoryStorage::URL).then(InMemoryStorage::default);
+
+    ensure!(
+        memory.is_none() || args.dry_run.is_none(),
+        "`--dry-run` can't be used with `DATABASE_URL=\"{}\"` since nothing is written \
+        to a database anyway",
+        InMemoryStorage::URL
+    );
 
-    let ctx = Context::new().await.context("failed to create context")?;
+    let storage: Arc<dyn Storage> = match memory {
+        Some(ref memory) => Arc::new(memory.clone()),
+        None => {
+            let dry_run = args.dry_run.as_deref().map(DryRun::create).transpose()?;
 
-    tokio::select! {
-        _ = run(ctx, args, task) => {},
-        res = signal::ctrl_c() => match res {
-            Ok(_) => info!("Received Ctrl+C"),
-            Err(err) => error!(err = ?Report::new(err), "Failed to await Ctrl+C"),
+            database::connect(&config.database_url, config.database_batch_size, dry_run).await?
         }
+    };
+
+    let ctx = Context::new(shutdown, &args, storage)
+        .await
+        .context("failed to create context")?;
+
+    ctx.check_tasks(task)?;
+
+    run(ctx, args, task, checkpoint).await;
+
+    if let Some(memory) = memory {
+        memory.log_summary();
     }
 
     info!("Shutting down");
@@ -76,9 +161,25 @@ async fn async_main(mut args: Args, task: Option<Task>) -> Result<()> {
     Ok(())
 }
 
-async fn run(ctx: Context, args: Args, task: Option<Task>) {
+async fn migrate() -> Result<()> {
+    let database_url = config::database_url()?;
+
+    // Nothing but the migrations are written so the batch size does not matter
+    let storage = database::connect(&database_url, 1, None).await?;
+    let applied = storage.migrate().await?;
+
+    if applied.is_empty() {
+        info!("Database schema is already up-to-date");
+    } else {
+        info!("Applied {} migration(s)", applied.len());
+    }
+
+    Ok(())
+}
+
+async fn run(ctx: Context, args: Args, task: Option<Task>, checkpoint: Option<Checkpoint>) {
     if let Some(task) = task {
-        ctx.run_once(task, args).await
+        ctx.run_once(task, args, checkpoint).await
     } else {
         ctx.loop_forever(args).await
     }
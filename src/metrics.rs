@@ -0,0 +1,433 @@
+use std::{
+    convert::Infallible,
+    fmt::Write,
+    future::Future,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use ::bytes::Bytes;
+use eyre::{Context as _, Result};
+use http_body_util::Full;
+use hyper::{
+    body::Incoming,
+    header::{HeaderValue, CONTENT_TYPE},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use time::OffsetDateTime;
+use tokio::net::TcpListener;
+
+use crate::task::Task;
+
+pub static METRICS: Metrics = Metrics::new();
+
+tokio::task_local! {
+    static LANE: usize;
+}
+
+static TEXT_FORMAT: HeaderValue = HeaderValue::from_static("text/plain; version=0.0.4");
+
+/// Upper bounds in seconds of the duration histograms
+const BUCKETS: [f64; 15] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+/// Everything that is exposed to Prometheus
+pub struct Metrics {
+    users_succeeded: AtomicU64,
+    users_restricted: AtomicU64,
+    users_failed: AtomicU64,
+    leaderboard_pages: AtomicU64,
+    osu_requests: [Histogram; OsuEndpoint::ALL.len()],
+    store_durations: [Histogram; StoreMethod::ALL.len()],
+    /// Task and phase of each lane; lanes run concurrently so each of them
+    /// is tracked on its own
+    lanes: Mutex<Vec<LaneMetrics>>,
+    last_finish: AtomicI64,
+}
+
+#[derive(Copy, Clone)]
+pub enum OsuEndpoint {
+    Users,
+    User,
+    Rankings,
+}
+
+impl OsuEndpoint {
+    const ALL: [Self; 3] = [Self::Users, Self::User, Self::Rankings];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Users => "users",
+            Self::User => "user",
+            Self::Rankings => "rankings",
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum StoreMethod {
+    Progress,
+    Finish,
+    Rankings,
+    Medals,
+    Rarities,
+    Badges,
+    Failures,
+}
+
+impl StoreMethod {
+    const ALL: [Self; 7] = [
+        Self::Progress,
+        Self::Finish,
+        Self::Rankings,
+        Self::Medals,
+        Self::Rarities,
+        Self::Badges,
+        Self::Failures,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Progress => "store_progress",
+            Self::Finish => "store_finish",
+            Self::Rankings => "store_rankings",
+            Self::Medals => "store_medals",
+            Self::Rarities => "store_rarities",
+            Self::Badges => "store_badges",
+            Self::Failures => "store_failures",
+        }
+    }
+}
+
+struct LaneMetrics {
+    lane: usize,
+    task: Option<Task>,
+    phase: Phase,
+}
+
+/// What the current task is busy with
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Phase {
+    Idle,
+    GatheringUsers,
+    Leaderboards,
+    RequestingUsers,
+    Medals,
+    Storing,
+}
+
+impl Phase {
+    const ALL: [Self; 6] = [
+        Self::Idle,
+        Self::GatheringUsers,
+        Self::Leaderboards,
+        Self::RequestingUsers,
+        Self::Medals,
+        Self::Storing,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::GatheringUsers => "gathering_users",
+            Self::Leaderboards => "leaderboards",
+            Self::RequestingUsers => "requesting_users",
+            Self::Medals => "medals",
+            Self::Storing => "storing",
+        }
+    }
+}
+
+struct Histogram {
+    /// Observations per bucket; the last one is `+Inf`
+    buckets: [AtomicU64; BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; BUCKETS.len() + 1],
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let idx = BUCKETS
+            .iter()
+            .position(|&bound| secs <= bound)
+            .unwrap_or(BUCKETS.len());
+
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, label: &str) {
+        let mut count = 0;
+
+        for (bucket, i) in self.buckets.iter().zip(0..) {
+            count += bucket.load(Ordering::Relaxed);
+
+            let _ = match BUCKETS.get(i) {
+                Some(bound) => writeln!(out, "{name}_bucket{{{label},le=\"{bound}\"}} {count}"),
+                None => writeln!(out, "{name}_bucket{{{label},le=\"+Inf\"}} {count}"),
+            };
+        }
+
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_sum{{{label}}} {sum}");
+        let _ = writeln!(out, "{name}_count{{{label}}} {count}");
+    }
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            users_succeeded: AtomicU64::new(0),
+            users_restricted: AtomicU64::new(0),
+            users_failed: AtomicU64::new(0),
+            leaderboard_pages: AtomicU64::new(0),
+            osu_requests: [const { Histogram::new() }; OsuEndpoint::ALL.len()],
+            store_durations: [const { Histogram::new() }; StoreMethod::ALL.len()],
+            lanes: Mutex::new(Vec::new()),
+            last_finish: AtomicI64::new(0),
+        }
+    }
+
+    pub fn user_succeeded(&self) {
+        self.users_succeeded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn user_restricted(&self) {
+        self.users_restricted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn users_failed(&self, count: usize) {
+        self.users_failed.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn leaderboard_page_fetched(&self) {
+        self.leaderboard_pages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_osu_request(&self, endpoint: OsuEndpoint, latency: Duration) {
+        self.osu_requests[endpoint as usize].observe(latency);
+    }
+
+    pub fn observe_store(&self, method: StoreMethod, duration: Duration) {
+        self.store_durations[method as usize].observe(duration);
+    }
+
+    /// Await the future with `lane` as the lane whose task and phase are set.
+    ///
+    /// Outside of such a scope, e.g. when running a single task, lane 0 is used.
+    pub async fn lane_scope<F: Future>(&self, lane: usize, fut: F) -> F::Output {
+        LANE.scope(lane, fut).await
+    }
+
+    pub fn set_task(&self, task: Option<Task>) {
+        self.update_lane(|metrics| metrics.task = task);
+    }
+
+    pub fn set_phase(&self, phase: Phase) {
+        self.update_lane(|metrics| metrics.phase = phase);
+    }
+
+    fn update_lane(&self, f: impl FnOnce(&mut LaneMetrics)) {
+        let lane = LANE.try_with(|lane| *lane).unwrap_or(0);
+        let mut lanes = self.lanes.lock().unwrap();
+
+        let idx = match lanes.iter().position(|metrics| metrics.lane == lane) {
+            Some(idx) => idx,
+            None => {
+                lanes.push(LaneMetrics {
+                    lane,
+                    task: None,
+                    phase: Phase::Idle,
+                });
+
+                lanes.len() - 1
+            }
+        };
+
+        f(&mut lanes[idx]);
+    }
+
+    pub fn set_last_finish(&self, datetime: OffsetDateTime) {
+        self.last_finish
+            .store(datetime.unix_timestamp(), Ordering::Relaxed);
+    }
+
+    /// Metrics in the Prometheus text format
+    fn render(&self) -> String {
+        let mut out = String::with_capacity(8192);
+
+        let succeeded = self.users_succeeded.load(Ordering::Relaxed);
+        let restricted = self.users_restricted.load(Ordering::Relaxed);
+        let failed = self.users_failed.load(Ordering::Relaxed);
+
+        let counters = [
+            (
+                "osekai_users_requested_total",
+                "Users whose request finished",
+                succeeded + restricted + failed,
+            ),
+            (
+                "osekai_users_succeeded_total",
+                "Users that were requested successfully",
+                succeeded,
+            ),
+            (
+                "osekai_users_restricted_total",
+                "Users that turned out to be restricted",
+                restricted,
+            ),
+            (
+                "osekai_users_failed_total",
+                "User requests that failed",
+                failed,
+            ),
+            (
+                "osekai_leaderboard_pages_total",
+                "Leaderboard pages that were fetched",
+                self.leaderboard_pages.load(Ordering::Relaxed),
+            ),
+        ];
+
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name} {value}");
+        }
+
+        let name = "osekai_osu_request_duration_seconds";
+        let _ = writeln!(out, "# HELP {name} Latency of osu!api requests");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+
+        for endpoint in OsuEndpoint::ALL {
+            let label = format!("endpoint=\"{}\"", endpoint.as_str());
+            self.osu_requests[endpoint as usize].render(&mut out, name, &label);
+        }
+
+        let name = "osekai_store_duration_seconds";
+        let _ = writeln!(out, "# HELP {name} Duration of database writes");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+
+        for method in StoreMethod::ALL {
+            let label = format!("method=\"{}\"", method.as_str());
+            self.store_durations[method as usize].render(&mut out, name, &label);
+        }
+
+        let lanes = self.lanes.lock().unwrap();
+
+        let name = "osekai_task_info";
+        let _ = writeln!(out, "# HELP {name} Task that is currently running per lane");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+
+        for metrics in lanes.iter() {
+            if let Some(task) = metrics.task {
+                let _ = writeln!(out, "{name}{{lane=\"{}\",task=\"{task}\"}} 1", metrics.lane);
+            }
+        }
+
+        let name = "osekai_phase";
+        let _ = writeln!(out, "# HELP {name} Phase of the current task per lane");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+
+        // Lanes that never ran a task are idle
+        let idle = LaneMetrics {
+            lane: 0,
+            task: None,
+            phase: Phase::Idle,
+        };
+
+        let current = if lanes.is_empty() {
+            std::slice::from_ref(&idle)
+        } else {
+            &lanes[..]
+        };
+
+        for metrics in current {
+            for phase in Phase::ALL {
+                let value = (phase == metrics.phase) as u8;
+                let lane = metrics.lane;
+                let phase = phase.as_str();
+                let _ = writeln!(out, "{name}{{lane=\"{lane}\",phase=\"{phase}\"}} {value}");
+            }
+        }
+
+        drop(lanes);
+
+        let name = "osekai_last_finish_timestamp_seconds";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Unix timestamp of the last task that finished without cancellation"
+        );
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        let _ = writeln!(out, "{name} {}", self.last_finish.load(Ordering::Relaxed));
+
+        out
+    }
+}
+
+/// Serve the metrics on `/metrics` in the background.
+pub async fn serve(addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to bind metrics listener to {addr}"))?;
+
+    info!("Serving metrics on http://{addr}/metrics");
+
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    warn!(?err, "Failed to accept metrics connection");
+
+                    continue;
+                }
+            };
+
+            tokio::spawn(async move {
+                let service = service_fn(handle_request);
+
+                let conn = http1::Builder::new().serve_connection(TokioIo::new(stream), service);
+
+                if let Err(err) = conn.await {
+                    debug!(?err, "Metrics connection failed");
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+async fn handle_request(req: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+    let response = if req.method() == Method::GET && req.uri().path() == "/metrics" {
+        let mut response = Response::new(Full::from(METRICS.render()));
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, TEXT_FORMAT.clone());
+
+        response
+    } else {
+        let mut response = Response::new(Full::default());
+        *response.status_mut() = StatusCode::NOT_FOUND;
+
+        response
+    };
+
+    Ok(response)
+}
@@ -1,16 +1,18 @@
 use std::{
     borrow::{Borrow, Cow},
-    collections::{hash_map::Entry, HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     hash::{Hash, Hasher},
     mem,
 };
 
 use rosu_v2::prelude::Badge;
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
 use crate::util::IntHasher;
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct BadgeName(pub Box<str>);
 
 impl Borrow<str> for BadgeName {
@@ -19,9 +21,12 @@ impl Borrow<str> for BadgeName {
     }
 }
 
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct BadgeImageUrl(pub Box<str>);
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct BadgeDescription(pub Box<str>);
 
 impl Borrow<str> for BadgeDescription {
@@ -30,8 +35,10 @@ impl Borrow<str> for BadgeDescription {
     }
 }
 
+#[derive(Clone, Deserialize, Serialize)]
 pub struct BadgeOwner {
     pub user_id: u32,
+    #[serde(with = "time::serde::timestamp")]
     pub awarded_at: OffsetDateTime,
 }
 
@@ -86,12 +93,18 @@ impl<'a> From<PendingImageUrl<'a>> for BadgeImageUrl {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default, Deserialize, Serialize)]
 pub struct Badges {
     pub names: HashMap<BadgeName, BadgeImageUrl>,
     /// Different badges might have the same description but owners of the same
     /// badge don't necessarily have the same description.
     pub descriptions: HashMap<BadgeDescription, HashMap<BadgeName, BadgeOwners>>,
+    /// Users whose badges were checked, including users without badges.
+    ///
+    /// Users with a badge whose name could not be extracted are not included
+    /// so that none of their stored badges are considered removed.
+    #[serde(default)]
+    pub checked_users: HashSet<u32, IntHasher>,
 }
 
 impl Badges {
@@ -99,10 +112,14 @@ impl Badges {
         Self {
             names: HashMap::with_capacity(capacity),
             descriptions: HashMap::with_capacity(capacity),
+            checked_users: HashSet::default(),
         }
     }
 
-    pub fn push(&mut self, user_id: u32, original: &mut Badge, name_buf: &mut String) {
+    /// Add the badge for the given user.
+    ///
+    /// Returns `false` if no name could be extracted from the badge.
+    pub fn push(&mut self, user_id: u32, original: &mut Badge, name_buf: &mut String) -> bool {
         // Extract the image url.
         let image_url = match original.image_url.split_once('?') {
             Some((image_url, _)) => PendingImageUrl(Cow::Borrowed(image_url)),
@@ -116,7 +133,7 @@ impl Badges {
                 original.image_url
             );
 
-            return;
+            return false;
         }
 
         // If it's a new name, add it as entry.
@@ -155,41 +172,236 @@ impl Badges {
                 BadgeDescription(mem::take(&mut original.description).into_boxed_str());
             self.descriptions.insert(description, entries);
         }
-    }
 
-    pub fn len(&self) -> usize {
-        self.descriptions
-            .values()
-            .flat_map(HashMap::values)
-            .map(HashSet::len)
-            .sum()
+        true
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.descriptions.is_empty()
-    }
+    /// Compare the badges of checked users with the stored badges.
+    ///
+    /// Stored owners are only considered removed if their user was checked.
+    pub fn diff(self, stored: &Self) -> BadgesDiff {
+        // Badge names mapped to their stored owners which are mapped to their
+        // description, award date, and whether they still own the badge
+        let mut stored_owners: HashMap<&str, HashMap<u32, _, IntHasher>> = HashMap::new();
+
+        for (BadgeDescription(description), entries) in stored.descriptions.iter() {
+            for (BadgeName(name), owners) in entries.iter() {
+                let name_owners = stored_owners.entry(name.as_ref()).or_default();
+
+                for owner in owners {
+                    let value = (description.as_ref(), owner.awarded_at, false);
+                    name_owners.insert(owner.user_id, value);
+                }
+            }
+        }
 
-    pub fn merge(&mut self, mut other: Self) {
-        self.names.extend(other.names.drain());
+        let mut diff = BadgesDiff::default();
 
-        for (description, entries) in other.descriptions.drain() {
-            match self.descriptions.entry(description) {
-                Entry::Occupied(entry) => {
-                    let this = entry.into_mut();
+        for (name, image_url) in self.names {
+            if !stored.names.contains_key(&name) {
+                diff.names.push((name, image_url));
+            }
+        }
 
-                    for (name, owners) in entries {
-                        match this.entry(name) {
-                            Entry::Occupied(entry) => entry.into_mut().extend(owners),
-                            Entry::Vacant(entry) => {
-                                entry.insert(owners);
+        for (BadgeDescription(description), entries) in self.descriptions {
+            for (BadgeName(name), owners) in entries {
+                for BadgeOwner {
+                    user_id,
+                    awarded_at,
+                } in owners
+                {
+                    let stored_owner = stored_owners
+                        .get_mut(name.as_ref())
+                        .and_then(|owners| owners.get_mut(&user_id));
+
+                    match stored_owner {
+                        Some((stored_description, stored_awarded_at, owned)) => {
+                            *owned = true;
+
+                            if *stored_description == description.as_ref()
+                                && *stored_awarded_at == awarded_at
+                            {
+                                continue;
                             }
+
+                            diff.updated += 1;
                         }
+                        None => diff.changes(&name).added += 1,
                     }
+
+                    diff.upserts.push(BadgeRow {
+                        name: name.clone(),
+                        user_id,
+                        description: description.clone(),
+                        awarded_at,
+                    });
                 }
-                Entry::Vacant(entry) => {
-                    entry.insert(entries);
+            }
+        }
+
+        for (name, owners) in stored_owners {
+            for (user_id, (_, _, owned)) in owners {
+                if !owned && self.checked_users.contains(&user_id) {
+                    diff.changes(name).removed += 1;
+                    diff.removals.push((Box::from(name), user_id));
                 }
             }
         }
+
+        diff
+    }
+}
+
+/// A single owner of a badge.
+pub struct BadgeRow {
+    pub name: Box<str>,
+    pub user_id: u32,
+    pub description: Box<str>,
+    pub awarded_at: OffsetDateTime,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct BadgeChanges {
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Changes that need to be applied to the stored badges.
+#[derive(Default)]
+pub struct BadgesDiff {
+    /// Badges that are not stored yet.
+    pub names: Vec<(BadgeName, BadgeImageUrl)>,
+    /// Owners that are new or whose description or award date changed.
+    pub upserts: Vec<BadgeRow>,
+    /// Badge names and user ids of owners that no longer own the badge.
+    pub removals: Vec<(Box<str>, u32)>,
+    /// Amount of owners in `upserts` that were already stored.
+    pub updated: usize,
+    /// Added and removed owners for each badge name.
+    pub changes: BTreeMap<Box<str>, BadgeChanges>,
+}
+
+impl BadgesDiff {
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.upserts.is_empty() && self.removals.is_empty()
+    }
+
+    fn changes(&mut self, name: &str) -> &mut BadgeChanges {
+        if !self.changes.contains_key(name) {
+            self.changes
+                .insert(Box::from(name), BadgeChanges::default());
+        }
+
+        self.changes.get_mut(name).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    const AWARDED_AT: OffsetDateTime = datetime!(2024-05-23 12:00 UTC);
+
+    fn badge(description: &str, image_url: &str) -> Badge {
+        Badge {
+            awarded_at: AWARDED_AT,
+            description: description.to_owned(),
+            image_url: image_url.to_owned(),
+            url: String::new(),
+        }
+    }
+
+    /// Gather the badges of each user the same way as while requesting users.
+    fn gather(users: &[(u32, Vec<Badge>)]) -> Badges {
+        let mut badges = Badges::default();
+        let mut name_buf = String::new();
+
+        for (user_id, user_badges) in users {
+            let mut valid = true;
+
+            for badge in user_badges.iter() {
+                valid &= badges.push(*user_id, &mut badge.clone(), &mut name_buf);
+            }
+
+            if valid {
+                badges.checked_users.insert(*user_id);
+            }
+        }
+
+        badges
+    }
+
+    #[test]
+    fn extract_name_from_image_url() {
+        let mut badges = Badges::default();
+        let mut name_buf = String::new();
+        let mut winner = badge(
+            "Winner",
+            "https://assets.ppy.sh/badges/owc-2024_winner.png?1",
+        );
+
+        assert!(badges.push(2, &mut winner, &mut name_buf));
+        assert!(badges.names.contains_key("owc 2024 winner"));
+
+        let mut invalid = badge("Winner", "no-extension");
+        assert!(!badges.push(2, &mut invalid, &mut name_buf));
+    }
+
+    #[test]
+    fn diff_owners() {
+        let contributor = badge(
+            "Contributor",
+            "https://assets.ppy.sh/badges/contributor.png",
+        );
+        let mapper = badge("Mapper", "https://assets.ppy.sh/badges/mapper.png");
+
+        let stored = gather(&[
+            (1, vec![contributor.clone()]),
+            (2, vec![contributor.clone()]),
+            (3, vec![contributor.clone()]),
+        ]);
+
+        // User 1 is unchanged, user 2 lost the badge, user 3 was not
+        // checked, and user 4 gained the badge
+        let gathered = gather(&[
+            (1, vec![contributor.clone()]),
+            (2, vec![mapper.clone()]),
+            (4, vec![contributor.clone()]),
+        ]);
+
+        let diff = gathered.diff(&stored);
+
+        let mut upserts: Vec<_> = diff
+            .upserts
+            .iter()
+            .map(|row| (row.name.as_ref(), row.user_id))
+            .collect();
+        upserts.sort_unstable();
+
+        assert_eq!(upserts, [("contributor", 4), ("mapper", 2)]);
+        assert_eq!(diff.removals, [(Box::from("contributor"), 2)]);
+        assert_eq!(diff.updated, 0);
+        assert_eq!(diff.names.len(), 1);
+        assert_eq!(diff.names[0].0 .0.as_ref(), "mapper");
+
+        let changes = diff.changes["contributor"];
+        assert_eq!((changes.added, changes.removed), (1, 1));
+    }
+
+    #[test]
+    fn keep_owners_of_users_with_invalid_badge() {
+        let contributor = badge(
+            "Contributor",
+            "https://assets.ppy.sh/badges/contributor.png",
+        );
+        let invalid = badge("Invalid", "https://assets.ppy.sh/badges/invalid");
+
+        let stored = gather(&[(1, vec![contributor.clone()])]);
+        let gathered = gather(&[(1, vec![invalid])]);
+
+        assert!(!gathered.checked_users.contains(&1));
+        assert!(gathered.diff(&stored).is_empty());
     }
 }
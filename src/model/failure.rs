@@ -0,0 +1,107 @@
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+use eyre::Report;
+use rosu_v2::prelude::OsuError;
+
+use crate::util::IntHasher;
+
+/// Users that could not be requested, mapped to the kind of their last error.
+pub type UserFailures = HashMap<u32, FailureKind, IntHasher>;
+
+/// Error of a request that is either sent to the osu!api or served from a
+/// cassette.
+#[derive(Debug)]
+pub enum RequestError {
+    Osu(OsuError),
+    /// The response is missing in the cassette or is invalid
+    Cassette(Report),
+}
+
+pub type RequestResult<T> = Result<T, RequestError>;
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Osu(_) => f.write_str("osu!api request failed"),
+            Self::Cassette(_) => f.write_str("cassette replay failed"),
+        }
+    }
+}
+
+impl StdError for RequestError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Osu(err) => Some(err),
+            Self::Cassette(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl From<OsuError> for RequestError {
+    #[inline]
+    fn from(err: OsuError) -> Self {
+        Self::Osu(err)
+    }
+}
+
+/// Rough classification of an error that occurred while requesting a user.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FailureKind {
+    Ratelimited,
+    Timeout,
+    ServerError,
+    Response,
+    Request,
+    Parsing,
+    Cassette,
+    Other,
+}
+
+impl FailureKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ratelimited => "ratelimited",
+            Self::Timeout => "timeout",
+            Self::ServerError => "server_error",
+            Self::Response => "response",
+            Self::Request => "request",
+            Self::Parsing => "parsing",
+            Self::Cassette => "cassette",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl From<&OsuError> for FailureKind {
+    fn from(err: &OsuError) -> Self {
+        match err {
+            OsuError::Response { status, .. } if status.as_u16() == 429 => Self::Ratelimited,
+            OsuError::Response { status, .. } if status.is_server_error() => Self::ServerError,
+            OsuError::Response { .. } => Self::Response,
+            OsuError::RequestTimeout => Self::Timeout,
+            OsuError::Request { .. } => Self::Request,
+            OsuError::Parsing { .. } => Self::Parsing,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl From<&RequestError> for FailureKind {
+    fn from(err: &RequestError) -> Self {
+        match err {
+            RequestError::Osu(err) => Self::from(err),
+            RequestError::Cassette(_) => Self::Cassette,
+        }
+    }
+}
+
+impl Display for FailureKind {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
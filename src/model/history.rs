@@ -0,0 +1,45 @@
+use time::OffsetDateTime;
+
+/// A run as stored in `Rankings_Script_History`.
+pub struct HistoryEntry {
+    pub id: i64,
+    pub task: Box<str>,
+    pub start: Option<OffsetDateTime>,
+    pub current: Option<u32>,
+    pub total: Option<u32>,
+    /// ETA while the run is in progress; zero once it finished.
+    pub eta_seconds: Option<i64>,
+    pub last_update: Option<OffsetDateTime>,
+    /// `Is_Cancelled` of the run's report; `None` if it has none.
+    pub is_cancelled: Option<bool>,
+}
+
+impl HistoryEntry {
+    /// Finishing or cancelling a run resets its ETA to zero.
+    pub fn ended(&self) -> bool {
+        self.eta_seconds == Some(0)
+    }
+
+    /// Whether the run ended without being cancelled.
+    pub fn finished(&self) -> bool {
+        self.ended() && !self.cancelled()
+    }
+
+    /// Whether the run ended by being cancelled. Runs without report are
+    /// considered cancelled if not all of their users were requested.
+    pub fn cancelled(&self) -> bool {
+        let incomplete = || matches!((self.current, self.total), (Some(current), Some(total)) if current < total);
+
+        self.ended() && self.is_cancelled.unwrap_or_else(incomplete)
+    }
+
+    /// Whether the run did not finish and has not been updated since `since`.
+    pub fn stalled(&self, since: OffsetDateTime) -> bool {
+        let incomplete = match (self.current, self.total) {
+            (Some(current), Some(total)) => current < total,
+            _ => true,
+        };
+
+        !self.ended() && incomplete && self.last_update.is_none_or(|update| update < since)
+    }
+}
@@ -1,15 +1,24 @@
 pub use self::{
-    badge::{BadgeDescription, BadgeImageUrl, BadgeName, BadgeOwner, Badges},
+    badge::{
+        BadgeChanges, BadgeDescription, BadgeImageUrl, BadgeName, BadgeOwner, BadgeRow, Badges,
+        BadgesDiff,
+    },
+    failure::{FailureKind, RequestError, RequestResult, UserFailures},
+    history::HistoryEntry,
     progress::{Finish, Progress},
     ranking::{RankingUser, RankingsIter},
-    rarity::{MedalRarities, MedalRarityEntry},
+    rarity::{MedalCounts, MedalRarities, MedalRarityEntry},
+    report::{BadgeSummary, RunReport, SharedReport},
     scrap::{ScrapedMedal, ScrapedUser},
     user::{OsuUser, UserFull},
 };
 
 mod badge;
+mod failure;
+mod history;
 mod progress;
 mod ranking;
 mod rarity;
+mod report;
 mod scrap;
 mod user;
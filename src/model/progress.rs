@@ -3,7 +3,7 @@ use time::OffsetDateTime;
 
 use crate::{task::Task, util::Eta};
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct Progress {
     #[serde(skip)]
     pub start: OffsetDateTime,
@@ -11,27 +11,49 @@ pub struct Progress {
     pub total: usize,
     pub eta_seconds: Option<u64>,
     pub task: Task,
+    /// Amount of users that were already handled before resuming the run.
+    #[serde(skip)]
+    pub resumed: usize,
+    /// Whether the run was cancelled before all users were requested.
+    #[serde(skip)]
+    pub cancelled: bool,
 }
 
 impl Progress {
     pub const INTERVAL: usize = 100;
 
     pub fn new(total: usize, task: Task) -> Self {
-        let start = OffsetDateTime::now_utc();
+        Self::with_start(OffsetDateTime::now_utc(), total, task)
+    }
 
+    fn with_start(start: OffsetDateTime, total: usize, task: Task) -> Self {
         Self {
             start,
             task,
             total,
             current: 0,
             eta_seconds: None,
+            resumed: 0,
+            cancelled: false,
+        }
+    }
+
+    /// Create a progress for a run that started at the given time and of
+    /// which `resumed` users were already handled before the interruption.
+    ///
+    /// The `current` amount of later updates counts from there on.
+    pub fn resume(start: OffsetDateTime, total: usize, resumed: usize, task: Task) -> Self {
+        Self {
+            current: resumed,
+            resumed,
+            ..Self::with_start(start, total, task)
         }
     }
 
     pub fn update(&mut self, current: usize, eta: &Eta) {
-        self.current = current;
+        self.current = self.resumed + current;
 
-        let remaining = eta.estimate(self.total - current);
+        let remaining = eta.estimate(self.total - self.current);
         self.eta_seconds = remaining.as_seconds();
     }
 
@@ -39,19 +61,36 @@ impl Progress {
         self.current = self.total;
         self.eta_seconds = Some(0);
     }
+
+    pub fn cancel(&mut self, current: usize) {
+        self.current = self.resumed + current;
+        self.eta_seconds = None;
+        self.cancelled = true;
+    }
 }
 
 #[derive(Copy, Clone, Serialize)]
 pub struct Finish {
     pub id: i64,
     pub requested_users: usize,
+    #[serde(skip)]
+    pub total_users: usize,
+    pub cancelled: bool,
 }
 
 impl From<Progress> for Finish {
     fn from(progress: Progress) -> Self {
+        let requested_users = if progress.cancelled {
+            progress.current
+        } else {
+            progress.total
+        };
+
         Self {
             id: progress.start.unix_timestamp(),
-            requested_users: progress.total,
+            requested_users,
+            total_users: progress.total,
+            cancelled: progress.cancelled,
         }
     }
 }
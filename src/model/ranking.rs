@@ -171,10 +171,6 @@ impl RankingsIter {
             rarities,
         }
     }
-
-    pub fn len(&self) -> usize {
-        self.users.len()
-    }
 }
 
 impl Iterator for RankingsIter {
@@ -185,4 +181,10 @@ impl Iterator for RankingsIter {
             .next()
             .map(|user| RankingUser::new(user, &self.rarities))
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.users.size_hint()
+    }
 }
+
+impl ExactSizeIterator for RankingsIter {}
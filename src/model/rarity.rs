@@ -3,8 +3,12 @@ use std::{
     iter::FromIterator,
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::util::IntHasher;
 
+use super::{OsuUser, ScrapedMedal};
+
 #[derive(Copy, Clone)]
 pub struct MedalRarityEntry {
     pub count: u32,
@@ -59,3 +63,45 @@ impl Extend<(u16, u32, f32)> for MedalRarities {
         self.inner.extend(iter)
     }
 }
+
+/// Counts how many users obtained each medal so that rarities can be
+/// calculated without keeping all users around.
+#[derive(Default, Deserialize, Serialize)]
+pub struct MedalCounts {
+    counts: HashMap<u16, u32, IntHasher>,
+    users: u32,
+}
+
+impl MedalCounts {
+    pub fn add(&mut self, user: &OsuUser) {
+        self.users += 1;
+
+        if let OsuUser::Available(user) = user {
+            for medal in user.medals.iter() {
+                *self.counts.entry(medal.medal_id as u16).or_default() += 1;
+            }
+        }
+    }
+
+    /// Amount of users that were counted, including restricted ones.
+    pub fn user_count(&self) -> u32 {
+        self.users
+    }
+
+    /// Calculate each medal's rarity i.e. how many users obtained it
+    pub fn into_rarities(self, medals: &[ScrapedMedal]) -> MedalRarities {
+        let Self { mut counts, users } = self;
+
+        // In case no user owns the medal yet, still add it as an entry
+        for medal in medals {
+            counts.entry(medal.id).or_insert(0);
+        }
+
+        let user_count = users as f32;
+
+        counts
+            .into_iter()
+            .map(|(medal_id, count)| (medal_id, count, (100 * count) as f32 / user_count))
+            .collect()
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::{Map, Value};
+use time::OffsetDateTime;
+
+use crate::task::Task;
+
+use super::{BadgesDiff, Finish};
+
+/// Statistics of a run as stored in `Rankings_Script_Runs`.
+#[derive(Clone)]
+pub struct RunReport {
+    /// Id of the run's `Rankings_Script_History` entry.
+    ///
+    /// Resuming a run creates another report with the same id.
+    pub history_id: i64,
+    pub task: Task,
+    pub start: OffsetDateTime,
+    pub end: OffsetDateTime,
+    pub cancelled: bool,
+    /// Duration of each phase and the summed up duration of each store method
+    pub durations: BTreeMap<&'static str, Duration>,
+    pub requested_users: usize,
+    /// Users that still failed after all retries
+    pub failed_users: usize,
+    pub restricted_users: usize,
+    pub new_medals: usize,
+    pub badges: BadgeSummary,
+    /// Amount of written rows for each store method
+    pub rows: BTreeMap<&'static str, usize>,
+    pub errors: Vec<String>,
+    /// Amount of errors that exceeded [`RunReport::MAX_ERRORS`]
+    pub omitted_errors: usize,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct BadgeSummary {
+    pub new_badges: usize,
+    pub owners_added: usize,
+    pub owners_updated: usize,
+    pub owners_removed: usize,
+}
+
+impl BadgeSummary {
+    pub fn new(diff: &BadgesDiff) -> Self {
+        Self {
+            new_badges: diff.names.len(),
+            owners_added: diff.upserts.len() - diff.updated,
+            owners_updated: diff.updated,
+            owners_removed: diff.removals.len(),
+        }
+    }
+}
+
+impl RunReport {
+    /// Errors beyond this amount are only counted so a run with
+    /// persistent errors does not produce a huge report.
+    const MAX_ERRORS: usize = 50;
+
+    pub fn new(task: Task) -> Self {
+        let now = OffsetDateTime::now_utc();
+
+        Self {
+            history_id: now.unix_timestamp(),
+            task,
+            start: now,
+            end: now,
+            cancelled: false,
+            durations: BTreeMap::new(),
+            requested_users: 0,
+            failed_users: 0,
+            restricted_users: 0,
+            new_medals: 0,
+            badges: BadgeSummary::default(),
+            rows: BTreeMap::new(),
+            errors: Vec::new(),
+            omitted_errors: 0,
+        }
+    }
+
+    /// Add to the duration of a phase or store method.
+    pub fn add_duration(&mut self, name: &'static str, elapsed: Duration) {
+        *self.durations.entry(name).or_default() += elapsed;
+    }
+
+    pub fn add_rows(&mut self, name: &'static str, rows: usize) {
+        *self.rows.entry(name).or_default() += rows;
+    }
+
+    pub fn add_error(&mut self, error: String) {
+        if self.errors.len() < Self::MAX_ERRORS {
+            self.errors.push(error);
+        } else {
+            self.omitted_errors += 1;
+        }
+    }
+
+    pub fn finish(&mut self, finish: &Finish) {
+        self.history_id = finish.id;
+        self.requested_users = finish.requested_users;
+        self.cancelled = finish.cancelled;
+        self.end = OffsetDateTime::now_utc();
+    }
+
+    pub fn total_rows(&self) -> usize {
+        self.rows.values().sum()
+    }
+
+    /// JSON object of the durations in seconds
+    pub fn durations_json(&self) -> String {
+        let durations: Map<_, _> = self
+            .durations
+            .iter()
+            .map(|(name, elapsed)| {
+                let secs = (elapsed.as_secs_f64() * 1000.0).round() / 1000.0;
+
+                (name.to_string(), Value::from(secs))
+            })
+            .collect();
+
+        Value::Object(durations).to_string()
+    }
+
+    /// JSON object of the written rows
+    pub fn rows_json(&self) -> String {
+        let rows: Map<_, _> = self
+            .rows
+            .iter()
+            .map(|(name, rows)| (name.to_string(), Value::from(*rows)))
+            .collect();
+
+        Value::Object(rows).to_string()
+    }
+
+    /// JSON array of the errors
+    pub fn errors_json(&self) -> String {
+        let mut errors: Vec<_> = self.errors.iter().map(|err| Value::from(&**err)).collect();
+
+        if self.omitted_errors > 0 {
+            errors.push(Value::from(format!(
+                "... and {} more error(s)",
+                self.omitted_errors
+            )));
+        }
+
+        Value::Array(errors).to_string()
+    }
+}
+
+tokio::task_local! {
+    static CURRENT: SharedReport;
+}
+
+/// Report of a run, shared between everything that contributes to it.
+///
+/// Runs of different lanes happen concurrently so the report that belongs
+/// to the current run is tracked through a task-local.
+#[derive(Clone)]
+pub struct SharedReport {
+    inner: Arc<Mutex<RunReport>>,
+}
+
+impl SharedReport {
+    pub fn new(task: Task) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(RunReport::new(task))),
+        }
+    }
+
+    /// Await the future with this report as the current one.
+    pub async fn scope<F: Future>(&self, fut: F) -> F::Output {
+        CURRENT.scope(self.clone(), fut).await
+    }
+
+    /// The report of the run that the current future belongs to.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Self::clone).ok()
+    }
+
+    /// Modify the current report if there is one.
+    pub fn update_current(f: impl FnOnce(&mut RunReport)) {
+        if let Some(report) = Self::current() {
+            report.update(f);
+        }
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut RunReport)) {
+        f(&mut self.inner.lock().unwrap());
+    }
+
+    pub fn snapshot(&self) -> RunReport {
+        self.inner.lock().unwrap().clone()
+    }
+}
@@ -6,7 +6,7 @@ pub struct ScrapedUser {
     pub medals: Box<[ScrapedMedal]>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct ScrapedMedal {
     pub icon_url: Box<str>,
     pub id: u16,
@@ -1,15 +1,18 @@
 use std::num::NonZeroU32;
 
-use rosu_v2::prelude::{Badge, MedalCompact, UserExtended, UserStatistics};
+use rosu_v2::prelude::{Badge, MedalCompact, UserExtended, UserStatistics, UserStatisticsModes};
+
+use serde::{Deserialize, Serialize};
 
 use super::MedalRarities;
 
+#[derive(Deserialize, Serialize)]
 pub enum OsuUser {
     Available(UserFull),
     Restricted { user_id: u32 },
 }
 
-#[derive(Default)]
+#[derive(Default, Deserialize, Serialize)]
 pub struct ModeStats {
     pub acc: f32,
     pub level: f32,
@@ -34,6 +37,7 @@ impl From<Option<&UserStatistics>> for ModeStats {
     }
 }
 
+#[derive(Deserialize, Serialize)]
 pub struct UserFull {
     pub inner: [ModeStats; 4],
     pub badges: Box<[Badge]>,
@@ -48,20 +52,22 @@ pub struct UserFull {
 }
 
 impl UserFull {
-    pub fn new(std: UserExtended, tko: UserExtended, ctb: UserExtended, mna: UserExtended) -> Self {
-        let badges = std.badges.unwrap_or_default().into_boxed_slice();
-        let country_code = std.country_code.into_string().into_boxed_str();
-        let maps_ranked = std.ranked_mapset_count.map_or(0, |count| count as u16);
-        let maps_loved = std.loved_mapset_count.map_or(0, |count| count as u16);
-        let medals = std.medals.unwrap_or_default().into_boxed_slice();
-        let subscribers = std.mapping_follower_count.unwrap_or(0);
-        let user_id = std.user_id;
-        let username = std.username.into_string().into_boxed_str();
+    /// Combine the user data of a single user request with the statistics
+    /// of a batch request.
+    pub fn new(user: UserExtended, stats: UserStatisticsModes) -> Self {
+        let badges = user.badges.unwrap_or_default().into_boxed_slice();
+        let country_code = user.country_code.into_string().into_boxed_str();
+        let maps_ranked = user.ranked_mapset_count.map_or(0, |count| count as u16);
+        let maps_loved = user.loved_mapset_count.map_or(0, |count| count as u16);
+        let medals = user.medals.unwrap_or_default().into_boxed_slice();
+        let subscribers = user.mapping_follower_count.unwrap_or(0);
+        let user_id = user.user_id;
+        let username = user.username.into_string().into_boxed_str();
 
-        let std = std.statistics.as_ref();
-        let tko = tko.statistics.as_ref();
-        let ctb = ctb.statistics.as_ref();
-        let mna = mna.statistics.as_ref();
+        let std = stats.osu.as_ref();
+        let tko = stats.taiko.as_ref();
+        let ctb = stats.catch.as_ref();
+        let mna = stats.mania.as_ref();
 
         let replays_watched = std.map_or(0, |stats| stats.replays_watched)
             + tko.map_or(0, |stats| stats.replays_watched)
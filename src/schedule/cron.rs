@@ -0,0 +1,588 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+};
+
+use eyre::{Report, Result};
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+const WEEKDAYS: &[&str] = &[
+    "sunday",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+];
+
+const MONTHS: &[&str] = &[
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// Wall-clock times at which a schedule entry is due.
+///
+/// Supported notations are `*/<n>h` or `*/<n>m` for every n-th hour or
+/// minute, `[<weekdays>] <HH:MM>` for a time of day, and cron expressions
+/// of five fields. Each of them can be followed by a timezone, either
+/// `UTC` or a fixed offset like `+02:00`; without one, UTC is used.
+///
+/// Named timezones like `Europe/Berlin` are not supported since their
+/// offset changes with daylight saving time.
+#[derive(Clone)]
+pub struct Cron {
+    /// Bitsets of the allowed values of each field
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    /// Sunday is 0
+    weekdays: u64,
+    /// If both days and weekdays are restricted, either of them has to
+    /// match, just like in cron.
+    days_restricted: bool,
+    weekdays_restricted: bool,
+    offset: UtcOffset,
+    source: Box<str>,
+}
+
+impl Cron {
+    /// How far to look for a due time; covers schedules on February 29.
+    const MAX_DAYS: usize = 8 * 366;
+
+    /// The first due time after `after`.
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let local = after.to_offset(self.offset);
+        let mut date = local.date();
+        let mut from = (local.hour(), local.minute() + 1);
+
+        for _ in 0..Self::MAX_DAYS {
+            if self.matches_date(date) {
+                if let Some(time) = self.first_time(from.0, from.1) {
+                    return Some(self.datetime(date, time));
+                }
+            }
+
+            date = date.next_day()?;
+            from = (0, 0);
+        }
+
+        None
+    }
+
+    /// The last due time at or before `at`.
+    pub fn prev(&self, at: OffsetDateTime) -> Option<OffsetDateTime> {
+        let local = at.to_offset(self.offset);
+        let mut date = local.date();
+        let mut until = (local.hour(), local.minute());
+
+        for _ in 0..Self::MAX_DAYS {
+            if self.matches_date(date) {
+                if let Some(time) = self.last_time(until.0, until.1) {
+                    return Some(self.datetime(date, time));
+                }
+            }
+
+            date = date.previous_day()?;
+            until = (23, 59);
+        }
+
+        None
+    }
+
+    fn matches_date(&self, date: Date) -> bool {
+        let month = contains(self.months, u8::from(date.month()));
+        let day = contains(self.days, date.day());
+        let weekday = contains(self.weekdays, date.weekday().number_days_from_sunday());
+
+        let day = if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        };
+
+        month && day
+    }
+
+    /// The earliest due time of a day that is not before `hour:minute`.
+    fn first_time(&self, hour: u8, minute: u8) -> Option<Time> {
+        (hour..24)
+            .filter(|&h| contains(self.hours, h))
+            .find_map(|h| {
+                let start = if h == hour { minute } else { 0 };
+
+                (start..60)
+                    .find(|&m| contains(self.minutes, m))
+                    .map(|m| (h, m))
+            })
+            .and_then(|(h, m)| Time::from_hms(h, m, 0).ok())
+    }
+
+    /// The latest due time of a day that is not after `hour:minute`.
+    fn last_time(&self, hour: u8, minute: u8) -> Option<Time> {
+        (0..=hour)
+            .rev()
+            .filter(|&h| contains(self.hours, h))
+            .find_map(|h| {
+                let end = if h == hour { minute } else { 59 };
+
+                (0..=end)
+                    .rev()
+                    .find(|&m| contains(self.minutes, m))
+                    .map(|m| (h, m))
+            })
+            .and_then(|(h, m)| Time::from_hms(h, m, 0).ok())
+    }
+
+    fn datetime(&self, date: Date, time: Time) -> OffsetDateTime {
+        PrimitiveDateTime::new(date, time)
+            .assume_offset(self.offset)
+            .to_offset(UtcOffset::UTC)
+    }
+
+    fn set_time(&mut self, time: &str) -> Result<()> {
+        let (hour, minute) = time
+            .split_once(':')
+            .ok_or_else(|| eyre!("invalid time `{time}`; expected HH:MM"))?;
+
+        self.hours = 1 << parse_value(hour, 0, 23, &[])?;
+        self.minutes = 1 << parse_value(minute, 0, 59, &[])?;
+
+        Ok(())
+    }
+}
+
+impl FromStr for Cron {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens: Vec<_> = s.split_whitespace().collect();
+
+        let offset = match tokens.last().and_then(|token| parse_offset(token)) {
+            Some(offset) if tokens.len() > 1 => {
+                tokens.pop();
+
+                offset
+            }
+            _ => UtcOffset::UTC,
+        };
+
+        if let Some(&zone) = tokens.last().filter(|_| tokens.len() > 1) {
+            // Weekdays of cron expressions start with letters as well
+            let named = zone.starts_with(|ch: char| ch.is_ascii_alphabetic())
+                && parse_weekdays(zone).is_err();
+
+            ensure!(
+                !named,
+                "unsupported timezone `{zone}` in `{s}`; only `UTC` and fixed offsets \
+                like `+02:00` are supported"
+            );
+        }
+
+        let mut cron = Self {
+            minutes: 1,
+            hours: range(0, 23),
+            days: range(1, 31),
+            months: range(1, 12),
+            weekdays: range(0, 6),
+            days_restricted: false,
+            weekdays_restricted: false,
+            offset,
+            source: Box::from(s),
+        };
+
+        match tokens[..] {
+            [every] if every.starts_with("*/") => {
+                let step = &every[2..];
+
+                if let Some(hours) = step.strip_suffix('h') {
+                    cron.hours = parse_field(&format!("*/{hours}"), 0, 23, &[])?;
+                } else if let Some(minutes) = step.strip_suffix('m') {
+                    cron.minutes = parse_field(&format!("*/{minutes}"), 0, 59, &[])?;
+                } else {
+                    bail!("invalid interval `{every}`; expected `*/<n>h` or `*/<n>m`");
+                }
+            }
+            [minutes, hours, days, months, weekdays] => {
+                cron.minutes = parse_field(minutes, 0, 59, &[])?;
+                cron.hours = parse_field(hours, 0, 23, &[])?;
+                cron.days = parse_field(days, 1, 31, &[])?;
+                cron.months = parse_field(months, 1, 12, MONTHS)?;
+                cron.weekdays = parse_weekdays(weekdays)?;
+                cron.days_restricted = !days.starts_with('*');
+                cron.weekdays_restricted = !weekdays.starts_with('*');
+            }
+            [time] => cron.set_time(time)?,
+            [weekdays, time] => {
+                cron.weekdays = parse_weekdays(weekdays)?;
+                cron.weekdays_restricted = true;
+                cron.set_time(time)?;
+            }
+            _ => bail!(
+                "invalid time `{s}`; expected `*/<n>h`, `*/<n>m`, `[<weekdays>] <HH:MM>`, \
+                or a cron expression, optionally followed by a timezone"
+            ),
+        }
+
+        Ok(cron)
+    }
+}
+
+impl Display for Cron {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(&self.source)
+    }
+}
+
+fn contains(set: u64, value: u8) -> bool {
+    set & (1 << value) != 0
+}
+
+fn range(start: u8, end: u8) -> u64 {
+    (start..=end).fold(0, |set, value| set | (1 << value))
+}
+
+/// Parse a comma-separated list of values, ranges, and steps like
+/// `1,5-7,*/2` into a bitset of values within `min..=max`.
+///
+/// If names are given, the first name stands for `min`.
+fn parse_field(field: &str, min: u8, max: u8, names: &[&str]) -> Result<u64> {
+    let mut set = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse()
+                    .ok()
+                    .filter(|step| (1..=max).contains(step))
+                    .ok_or_else(|| eyre!("invalid step `{step}`; must be within 1-{max}"))?;
+
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (
+                parse_value(start, min, max, names)?,
+                parse_value(end, min, max, names)?,
+            ),
+            // `5/10` means every 10th value starting at 5
+            None if step.is_some() => (parse_value(range, min, max, names)?, max),
+            None => {
+                let value = parse_value(range, min, max, names)?;
+
+                (value, value)
+            }
+        };
+
+        ensure!(start <= end, "invalid range `{range}`");
+
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << value;
+        }
+    }
+
+    Ok(set)
+}
+
+fn parse_value(s: &str, min: u8, max: u8, names: &[&str]) -> Result<u8> {
+    let lowercase = s.to_ascii_lowercase();
+
+    let named = names
+        .iter()
+        .position(|name| lowercase.len() >= 3 && name.starts_with(&lowercase));
+
+    let value = match named {
+        Some(idx) => min + idx as u8,
+        None => s.parse().map_err(|_| eyre!("invalid value `{s}`"))?,
+    };
+
+    ensure!(
+        (min..=max).contains(&value),
+        "value `{s}` is not within {min}-{max}"
+    );
+
+    Ok(value)
+}
+
+/// Weekdays from 0 to 7 where both 0 and 7 are Sunday
+fn parse_weekdays(field: &str) -> Result<u64> {
+    let set = parse_field(field, 0, 7, WEEKDAYS)?;
+
+    Ok((set | (set >> 7)) & range(0, 6))
+}
+
+/// Parse `UTC`, `GMT`, `Z`, or a fixed offset like `+02:00`, `-5`, or `UTC+2`.
+fn parse_offset(s: &str) -> Option<UtcOffset> {
+    let uppercase = s.to_ascii_uppercase();
+
+    let rest = uppercase
+        .strip_prefix("UTC")
+        .or_else(|| uppercase.strip_prefix("GMT"))
+        .unwrap_or(&uppercase);
+
+    if rest.is_empty() || rest == "Z" {
+        return Some(UtcOffset::UTC);
+    }
+
+    let (sign, rest) = if let Some(rest) = rest.strip_prefix('+') {
+        (1, rest)
+    } else if let Some(rest) = rest.strip_prefix('-') {
+        (-1, rest)
+    } else {
+        return None;
+    };
+
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours.parse::<i8>().ok()?, minutes.parse::<i8>().ok()?),
+        None => (rest.parse::<i8>().ok()?, 0),
+    };
+
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn cron(s: &str) -> Cron {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn weekday_and_time() {
+        let cron = cron("Sun 03:00 UTC");
+
+        // 2024-05-26 is a Sunday
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-23 12:00 UTC)),
+            Some(datetime!(2024-05-26 03:00 UTC))
+        );
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-26 03:00 UTC)),
+            Some(datetime!(2024-06-02 03:00 UTC))
+        );
+        assert_eq!(
+            cron.prev(datetime!(2024-05-26 03:00 UTC)),
+            Some(datetime!(2024-05-26 03:00 UTC))
+        );
+        assert_eq!(
+            cron.prev(datetime!(2024-05-26 02:59 UTC)),
+            Some(datetime!(2024-05-19 03:00 UTC))
+        );
+    }
+
+    #[test]
+    fn every_n_hours() {
+        let cron = cron("*/6h");
+
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-26 05:59 UTC)),
+            Some(datetime!(2024-05-26 06:00 UTC))
+        );
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-26 06:00 UTC)),
+            Some(datetime!(2024-05-26 12:00 UTC))
+        );
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-26 18:30 UTC)),
+            Some(datetime!(2024-05-27 00:00 UTC))
+        );
+        assert_eq!(
+            cron.prev(datetime!(2024-05-26 11:59 UTC)),
+            Some(datetime!(2024-05-26 06:00 UTC))
+        );
+    }
+
+    #[test]
+    fn every_n_minutes() {
+        let cron = cron("*/20m");
+
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-26 10:40 UTC)),
+            Some(datetime!(2024-05-26 11:00 UTC))
+        );
+    }
+
+    #[test]
+    fn day_or_weekday() {
+        // Either the 15th or a Monday, 2024-05-13 and 2024-05-20 are Mondays
+        let cron = cron("0 0 15 * Mon");
+        let mut at = datetime!(2024-05-10 12:00 UTC);
+        let mut due = Vec::new();
+
+        for _ in 0..3 {
+            at = cron.next_after(at).unwrap();
+            due.push(at);
+        }
+
+        assert_eq!(
+            due,
+            [
+                datetime!(2024-05-13 00:00 UTC),
+                datetime!(2024-05-15 00:00 UTC),
+                datetime!(2024-05-20 00:00 UTC),
+            ]
+        );
+
+        // With unrestricted weekdays, only the day counts
+        let cron = self::cron("0 0 15 * *");
+
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-15 00:00 UTC)),
+            Some(datetime!(2024-06-15 00:00 UTC))
+        );
+    }
+
+    #[test]
+    fn sunday_as_seven() {
+        let cron = cron("0 0 * * 7");
+
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-23 00:00 UTC)),
+            Some(datetime!(2024-05-26 00:00 UTC))
+        );
+    }
+
+    #[test]
+    fn end_of_day() {
+        let cron = cron("59 23 * * *");
+
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-26 23:58 UTC)),
+            Some(datetime!(2024-05-26 23:59 UTC))
+        );
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-26 23:59 UTC)),
+            Some(datetime!(2024-05-27 23:59 UTC))
+        );
+        assert_eq!(
+            cron.prev(datetime!(2024-05-27 00:00 UTC)),
+            Some(datetime!(2024-05-26 23:59 UTC))
+        );
+    }
+
+    #[test]
+    fn end_of_year() {
+        let cron = cron("0 0 1 Jan *");
+
+        assert_eq!(
+            cron.next_after(datetime!(2024-12-31 23:59 UTC)),
+            Some(datetime!(2025-01-01 00:00 UTC))
+        );
+        assert_eq!(
+            cron.prev(datetime!(2024-12-31 23:59 UTC)),
+            Some(datetime!(2024-01-01 00:00 UTC))
+        );
+    }
+
+    #[test]
+    fn leap_day() {
+        let cron = cron("0 0 29 2 *");
+
+        assert_eq!(
+            cron.next_after(datetime!(2024-03-01 00:00 UTC)),
+            Some(datetime!(2028-02-29 00:00 UTC))
+        );
+    }
+
+    #[test]
+    fn fixed_offset() {
+        let cron = cron("03:00 +02:00");
+
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-26 00:00 UTC)),
+            Some(datetime!(2024-05-26 01:00 UTC))
+        );
+        assert_eq!(
+            cron.next_after(datetime!(2024-05-26 01:00 UTC)),
+            Some(datetime!(2024-05-27 01:00 UTC))
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        let hours = |h| UtcOffset::from_hms(h, 0, 0).unwrap();
+
+        assert_eq!(parse_offset("UTC"), Some(UtcOffset::UTC));
+        assert_eq!(parse_offset("z"), Some(UtcOffset::UTC));
+        assert_eq!(parse_offset("+02:00"), Some(hours(2)));
+        assert_eq!(parse_offset("-5"), Some(hours(-5)));
+        assert_eq!(parse_offset("UTC+2"), Some(hours(2)));
+        assert_eq!(parse_offset("-03:30"), UtcOffset::from_hms(-3, -30, 0).ok());
+        assert_eq!(parse_offset("03:00"), None);
+        assert_eq!(parse_offset("Sun"), None);
+    }
+
+    #[test]
+    fn fields() {
+        let set = |values: &[u8]| values.iter().fold(0, |set, value| set | (1 << value));
+
+        assert_eq!(
+            parse_field("1,5-7,*/20", 0, 59, &[]).unwrap(),
+            set(&[0, 1, 5, 6, 7, 20, 40])
+        );
+        assert_eq!(parse_field("5/20", 0, 59, &[]).unwrap(), set(&[5, 25, 45]));
+        assert_eq!(
+            parse_field("feb-apr", 1, 12, MONTHS).unwrap(),
+            set(&[2, 3, 4])
+        );
+        assert_eq!(parse_weekdays("Mon-Fri").unwrap(), set(&[1, 2, 3, 4, 5]));
+        assert_eq!(parse_weekdays("6,7").unwrap(), set(&[0, 6]));
+
+        assert!(parse_field("7-5", 0, 59, &[]).is_err());
+        assert!(parse_field("0-60", 0, 59, &[]).is_err());
+        assert!(parse_field("*/0", 0, 59, &[]).is_err());
+        assert!(parse_field("x", 0, 59, &[]).is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        for s in [
+            "*/6x",
+            "*/25h",
+            "24:00",
+            "12:60",
+            "Sun",
+            "0 0 * *",
+            "0 0 32 * *",
+        ] {
+            assert!(s.parse::<Cron>().is_err(), "`{s}` should be invalid");
+        }
+    }
+
+    #[test]
+    fn named_timezones() {
+        for s in [
+            "03:00 Europe/Berlin",
+            "Sun 03:00 CET",
+            "0 3 * * * America/New_York",
+        ] {
+            let Err(err) = s.parse::<Cron>() else {
+                panic!("`{s}` should be invalid");
+            };
+
+            assert!(err.to_string().contains("unsupported timezone"), "{err}");
+        }
+
+        // Named weekdays are no timezones
+        assert!("0 3 * * Mon-Fri".parse::<Cron>().is_ok());
+        assert!("Sun 03:00 UTC+1".parse::<Cron>().is_ok());
+    }
+}
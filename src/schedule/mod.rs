@@ -0,0 +1,210 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    slice::Iter,
+    str::FromStr,
+};
+
+use eyre::{Context as _, Report};
+use time::OffsetDateTime;
+
+use crate::task::Task;
+
+pub use self::state::ScheduleState;
+
+use self::cron::Cron;
+
+mod cron;
+mod state;
+
+/// Lanes of tasks that run independently of each other.
+pub struct Schedule {
+    lanes: Box<[Lane]>,
+}
+
+/// Contains a list of tasks to be executed one after the other with an
+/// interval in between or, if each task has a time, whenever they're due.
+pub struct Lane {
+    entries: Box<[ScheduleEntry]>,
+}
+
+pub struct ScheduleEntry {
+    pub task: Task,
+    pub cron: Option<Cron>,
+}
+
+impl Schedule {
+    pub fn lanes(&self) -> Iter<'_, Lane> {
+        self.lanes.iter()
+    }
+
+    /// Whether all lanes run at wall-clock times
+    pub fn is_timed(&self) -> bool {
+        self.lanes.iter().all(Lane::is_timed)
+    }
+}
+
+impl Lane {
+    pub fn iter(&self) -> Iter<'_, ScheduleEntry> {
+        self.entries.iter()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&ScheduleEntry> {
+        self.entries.get(idx)
+    }
+
+    /// Whether tasks run at wall-clock times instead of an interval
+    pub fn is_timed(&self) -> bool {
+        self.entries.iter().any(|entry| entry.cron.is_some())
+    }
+}
+
+impl ScheduleEntry {
+    /// When the entry is due next based on when its task last started.
+    ///
+    /// If the entry was due since then, e.g. because the script was not
+    /// running, that missed time is returned so the task is caught up on
+    /// once instead of for every missed time.
+    pub fn next_due(
+        &self,
+        last_start: Option<OffsetDateTime>,
+        now: OffsetDateTime,
+    ) -> Option<OffsetDateTime> {
+        let cron = self.cron.as_ref()?;
+
+        match (last_start, cron.prev(now)) {
+            (Some(last_start), Some(prev)) if prev > last_start => Some(prev),
+            _ => cron.next_after(now),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lanes = s
+            .split(';')
+            .map(str::trim)
+            .filter(|lane| !lane.is_empty())
+            .map(Lane::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            lanes: lanes.into_boxed_slice(),
+        })
+    }
+}
+
+impl FromStr for Lane {
+    type Err = Report;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sources: Vec<String> = Vec::new();
+
+        // Lists of weekdays and cron expressions may contain commas too so
+        // a piece that does not start with a task continues the previous
+        // entry's time.
+        for piece in s.split(',').map(str::trim) {
+            match sources.last_mut() {
+                Some(prev) if prev.contains('@') && !starts_with_task(piece) => {
+                    prev.push(',');
+                    prev.push_str(piece);
+                }
+                _ => sources.push(piece.to_owned()),
+            }
+        }
+
+        let entries = sources
+            .iter()
+            .map(|source| source.parse())
+            .collect::<Result<Vec<ScheduleEntry>, _>>()?;
+
+        let timed = entries.iter().filter(|entry| entry.cron.is_some()).count();
+
+        ensure!(
+            timed == 0 || timed == entries.len(),
+            "either all or none of the tasks of a lane must have a time"
+        );
+
+        Ok(Self {
+            entries: entries.into_boxed_slice(),
+        })
+    }
+}
+
+impl FromStr for ScheduleEntry {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((task, time)) = s.split_once('@') else {
+            return Ok(Self {
+                task: s.parse()?,
+                cron: None,
+            });
+        };
+
+        let time = time.trim();
+
+        let cron = time
+            .parse()
+            .with_context(|| format!("failed to parse time of task `{}`", task.trim()))?;
+
+        Ok(Self {
+            task: task.trim().parse()?,
+            cron: Some(cron),
+        })
+    }
+}
+
+fn starts_with_task(piece: &str) -> bool {
+    piece
+        .split('@')
+        .next()
+        .is_some_and(|task| task.trim().parse::<Task>().is_ok())
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut iter = self.lanes.iter();
+
+        if let Some(lane) = iter.next() {
+            Display::fmt(lane, f)?;
+
+            for lane in iter {
+                write!(f, "; {lane}")?;
+            }
+
+            Ok(())
+        } else {
+            f.write_str("No tasks")
+        }
+    }
+}
+
+impl Display for Lane {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut iter = self.entries.iter();
+
+        if let Some(entry) = iter.next() {
+            Display::fmt(entry, f)?;
+
+            for entry in iter {
+                write!(f, ", {entry}")?;
+            }
+
+            Ok(())
+        } else {
+            f.write_str("No tasks")
+        }
+    }
+}
+
+impl Display for ScheduleEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.cron {
+            Some(ref cron) => write!(f, "{} @ {cron}", self.task),
+            None => Display::fmt(&self.task, f),
+        }
+    }
+}
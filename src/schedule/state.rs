@@ -0,0 +1,127 @@
+use std::{fs, io::ErrorKind};
+
+use eyre::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::{Lane, ScheduleEntry};
+
+const PATH: &str = "./schedule-state.json";
+
+/// Position of each lane without times within its cycle so that a restart
+/// continues with the next task instead of the first one, and the last
+/// start of each entry with a time so that missed times are caught up on.
+#[derive(Default, Deserialize, Serialize)]
+pub struct ScheduleState {
+    lanes: Vec<LaneState>,
+    #[serde(default)]
+    timed: Vec<TimedState>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct LaneState {
+    /// The lane as it was written in the schedule; a lane that was changed
+    /// since then starts its cycle over.
+    lane: String,
+    /// Index of the last completed task
+    pub index: usize,
+    #[serde(with = "time::serde::rfc3339")]
+    pub start: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub finish: OffsetDateTime,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TimedState {
+    /// The entry as it was written in the schedule; an entry that was
+    /// changed since then is not caught up on.
+    entry: String,
+    #[serde(with = "time::serde::rfc3339")]
+    start: OffsetDateTime,
+}
+
+impl ScheduleState {
+    /// Load the state, starting out empty if there is none yet.
+    pub fn load() -> Result<Self> {
+        let bytes = match fs::read(PATH) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read schedule state `{PATH}`"))
+            }
+        };
+
+        serde_json::from_slice(&bytes)
+            .with_context(|| format!("failed to deserialize schedule state `{PATH}`"))
+    }
+
+    /// The state of the lane if it is still part of the schedule unchanged.
+    pub fn lane(&self, lane: &Lane) -> Option<&LaneState> {
+        let source = lane.to_string();
+
+        self.lanes
+            .iter()
+            .find(|state| state.lane == source)
+            .filter(|state| lane.get(state.index).is_some())
+    }
+
+    /// Record that the task at `index` of the lane completed.
+    pub fn complete(
+        &mut self,
+        lane: &Lane,
+        index: usize,
+        start: OffsetDateTime,
+        finish: OffsetDateTime,
+    ) {
+        let state = LaneState {
+            lane: lane.to_string(),
+            index,
+            start,
+            finish,
+        };
+
+        match self.lanes.iter_mut().find(|prev| prev.lane == state.lane) {
+            Some(prev) => *prev = state,
+            None => self.lanes.push(state),
+        }
+    }
+
+    /// When the task of the entry last started if the entry is unchanged.
+    pub fn last_start(&self, entry: &ScheduleEntry) -> Option<OffsetDateTime> {
+        let source = entry.to_string();
+
+        self.timed
+            .iter()
+            .find(|state| state.entry == source)
+            .map(|state| state.start)
+    }
+
+    /// Record that the task of the entry started at `start` and completed.
+    pub fn complete_timed(&mut self, entry: &ScheduleEntry, start: OffsetDateTime) {
+        let state = TimedState {
+            entry: entry.to_string(),
+            start,
+        };
+
+        match self.timed.iter_mut().find(|prev| prev.entry == state.entry) {
+            Some(prev) => *prev = state,
+            None => self.timed.push(state),
+        }
+    }
+
+    /// Write the state to disk, replacing the previous one.
+    pub fn save(&self) -> Result<()> {
+        let bytes =
+            serde_json::to_vec_pretty(self).context("failed to serialize schedule state")?;
+
+        // Same as for checkpoints, an interruption while writing must not
+        // corrupt the previous state
+        let tmp_path = format!("{PATH}.tmp");
+
+        fs::write(&tmp_path, bytes)
+            .with_context(|| format!("failed to write schedule state `{tmp_path}`"))?;
+
+        fs::rename(&tmp_path, PATH)
+            .with_context(|| format!("failed to rename schedule state `{tmp_path}`"))
+    }
+}
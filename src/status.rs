@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use eyre::{Context as _, Result};
+use time::OffsetDateTime;
+
+use crate::{
+    config, database,
+    model::HistoryEntry,
+    schedule::{Lane, ScheduleState},
+    util::{format_datetime, StatusArgs, TimeEstimate},
+};
+
+/// Print the most recent runs and when the schedule would continue
+pub async fn run(args: StatusArgs) -> Result<()> {
+    let StatusArgs {
+        runs,
+        interval,
+        stalled_after,
+    } = args;
+
+    let database_url = config::database_url()?;
+    let schedule = config::schedule()?;
+    let state = ScheduleState::load()?;
+
+    // Nothing is written so the batch size does not matter
+    let storage = database::connect(&database_url, 1, None).await?;
+
+    let history = storage
+        .fetch_history(runs)
+        .await
+        .context("failed to fetch history")?;
+
+    let now = OffsetDateTime::now_utc();
+    let stalled_since = now - Duration::from_secs(stalled_after * 60);
+
+    if history.is_empty() {
+        println!("No runs recorded yet");
+    } else {
+        println!("Last {} run(s):", history.len());
+
+        for entry in history.iter() {
+            println!("  {}", format_entry(entry, stalled_since));
+        }
+    }
+
+    println!();
+
+    let lane_count = schedule.lanes().len();
+
+    for (lane, i) in schedule.lanes().zip(1..) {
+        let label = if lane_count > 1 {
+            format!("Lane {i}")
+        } else {
+            "Schedule".to_owned()
+        };
+
+        if lane.is_timed() {
+            println!("{label}: {lane}");
+            print_due(lane, &history, now);
+        } else {
+            println!("{label}: {lane} (every {interval} hour(s))");
+            print_next(lane, &state, &history, interval, now);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print when the next task of a lane without times would start
+fn print_next(
+    lane: &Lane,
+    state: &ScheduleState,
+    history: &[HistoryEntry],
+    interval: u64,
+    now: OffsetDateTime,
+) {
+    let Some(first) = lane.iter().next() else {
+        return;
+    };
+
+    let interval = Duration::from_secs(interval * 60 * 60);
+
+    // The lane continues after the task of its most recent run
+    let last = history
+        .iter()
+        .find(|run| lane.iter().any(|entry| entry.task.to_string() == *run.task));
+
+    let (next_task, next_start) = match (state.lane(lane), last) {
+        // The position of the running script is more accurate than the history
+        (Some(prev), _) => {
+            let next_task = lane.get(prev.index + 1).unwrap_or(first).task;
+
+            (next_task, Some(prev.start + interval))
+        }
+        (None, Some(last)) => {
+            let next_task = lane
+                .iter()
+                .position(|entry| entry.task.to_string() == *last.task)
+                .and_then(|idx| lane.iter().cycle().nth(idx + 1))
+                .unwrap_or(first)
+                .task;
+
+            (next_task, last.start.map(|start| start + interval))
+        }
+        (None, None) => (first.task, None),
+    };
+
+    match next_start {
+        Some(start) if last.is_some_and(|last| !last.ended()) => {
+            println!(
+                "Next task `{next_task}` starts once the current run finished, not before {}",
+                format_datetime(start)
+            )
+        }
+        Some(start) if start > now => println!(
+            "Next task `{next_task}` would start at {} (in {})",
+            format_datetime(start),
+            TimeEstimate::new((start - now).unsigned_abs())
+        ),
+        Some(_) => println!("Next task `{next_task}` would start as soon as the script runs"),
+        None => println!("Next task `{next_task}` starts as soon as the script runs"),
+    }
+}
+
+/// Print when each task of a timed lane is due next
+fn print_due(lane: &Lane, history: &[HistoryEntry], now: OffsetDateTime) {
+    for entry in lane.iter() {
+        let task = entry.task.to_string();
+
+        let last_start = history
+            .iter()
+            .find(|run| *run.task == task)
+            .and_then(|run| run.start);
+
+        match entry.next_due(last_start, now) {
+            Some(at) if at > now => println!(
+                "  {entry}: next at {} (in {})",
+                format_datetime(at),
+                TimeEstimate::new((at - now).unsigned_abs())
+            ),
+            Some(at) => println!(
+                "  {entry}: missed at {}; caught up on as soon as the script runs",
+                format_datetime(at)
+            ),
+            None => println!("  {entry}: never due"),
+        }
+    }
+}
+
+fn format_entry(entry: &HistoryEntry, stalled_since: OffsetDateTime) -> String {
+    let HistoryEntry {
+        id,
+        task,
+        start,
+        current,
+        total,
+        eta_seconds,
+        last_update,
+        is_cancelled: _,
+    } = entry;
+
+    let start = start.map_or_else(|| format!("#{id}"), format_datetime);
+
+    let progress = match (current, total) {
+        (Some(current), Some(total)) if *total > 0 => {
+            let percent = 100.0 * *current as f64 / *total as f64;
+
+            format!("{current}/{total} ({percent:.1}%)")
+        }
+        (Some(current), Some(total)) => format!("{current}/{total}"),
+        _ => "-".to_owned(),
+    };
+
+    let state = if entry.finished() {
+        let elapsed = start_and_update(entry).map_or_else(
+            || "-".to_owned(),
+            |elapsed| TimeEstimate::new(elapsed).to_string(),
+        );
+
+        format!("finished after {elapsed}")
+    } else if entry.cancelled() {
+        let update = last_update.map_or_else(|| "-".to_owned(), format_datetime);
+
+        format!("cancelled at {update}")
+    } else if entry.stalled(stalled_since) {
+        let update = last_update.map_or_else(|| "never".to_owned(), format_datetime);
+
+        format!("STALLED, last update {update}")
+    } else {
+        match eta_seconds {
+            Some(secs) => format!(
+                "running, ETA {}",
+                TimeEstimate::new(Duration::from_secs(*secs as u64))
+            ),
+            None => "running, no ETA".to_owned(),
+        }
+    };
+
+    format!("{start} | {task} | {progress} | {state}")
+}
+
+/// Time between the start of the run and its last update
+fn start_and_update(entry: &HistoryEntry) -> Option<Duration> {
+    let start = entry.start?;
+    let update = entry.last_update?;
+
+    Some((update - start).unsigned_abs())
+}
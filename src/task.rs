@@ -2,10 +2,20 @@ use std::{
     fmt::{Display, Formatter, Result as FmtResult},
     ops::{BitAndAssign, BitOr, BitOrAssign, Not},
     str::FromStr,
+    sync::OnceLock,
 };
 
-use eyre::Report;
-use serde::{Serialize, Serializer};
+use eyre::{Report, Result};
+use serde::{de::Error as DeError, Deserialize, Deserializer, Serialize, Serializer};
+
+/// User-defined presets; set once at startup and not reloaded
+static PRESETS: OnceLock<Box<[Preset]>> = OnceLock::new();
+
+/// Named task that can be used just like `default` or `full`
+struct Preset {
+    name: Box<str>,
+    task: Task,
+}
 
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub struct Task(u8);
@@ -22,6 +32,18 @@ impl Task {
 }
 
 impl Task {
+    /// The flags of the task as stable identifier
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// The task of the given flags unless it has none or unknown ones.
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        let task = Self(bits);
+
+        (bits != 0 && Self::FULL.contains(task)).then_some(task)
+    }
+
     pub fn contains(self, other: Self) -> bool {
         self.0 & other.0 == other.0
     }
@@ -34,25 +56,140 @@ impl Task {
         Self(0)
     }
 
-    /// Should all medals be retrieved and uploaded?
-    pub fn medals(self) -> bool {
-        self.contains(Self::MEDALS)
+    /// Define presets from a comma-separated list of `<name> = <task>`.
+    ///
+    /// Presets may refer to each other regardless of their order.
+    pub fn init_presets(s: &str) -> Result<()> {
+        let mut pending = Vec::new();
+
+        for definition in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (name, task) = definition.split_once('=').ok_or_else(|| {
+                eyre!("invalid preset `{definition}`; expected `<name> = <task>`")
+            })?;
+
+            let name = name.trim().to_ascii_lowercase();
+
+            ensure!(
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                "invalid preset name `{name}`; must only consist of letters, digits, and `_`"
+            );
+
+            ensure!(
+                Self::builtin(&name).is_none(),
+                "preset `{name}` would replace a predefined task"
+            );
+
+            ensure!(
+                pending.iter().all(|(prev, _)| *prev != name),
+                "preset `{name}` is defined more than once"
+            );
+
+            pending.push((name, task.trim()));
+        }
+
+        let mut presets: Vec<Preset> = Vec::with_capacity(pending.len());
+
+        // Resolve presets whose references are already known until all are
+        while !pending.is_empty() {
+            let len = pending.len();
+            let mut last_err = None;
+
+            pending.retain(|(name, task)| match Self::parse(task, &presets) {
+                Ok(task) => {
+                    presets.push(Preset {
+                        name: Box::from(name.as_str()),
+                        task,
+                    });
+
+                    false
+                }
+                Err(err) => {
+                    last_err = Some(err.wrap_err(format!("failed to parse preset `{name}`")));
+
+                    true
+                }
+            });
+
+            if pending.len() == len {
+                return Err(last_err.unwrap());
+            }
+        }
+
+        PRESETS
+            .set(presets.into_boxed_slice())
+            .map_err(|_| eyre!("presets have already been defined"))
     }
 
-    /// Should badges be processed and uploaded?
-    pub fn badges(self) -> bool {
-        self.contains(Self::BADGES)
+    fn presets() -> &'static [Preset] {
+        PRESETS.get().map_or(&[], |presets| presets)
     }
 
-    /// Should the leaderboards be requested so that
-    /// medal rarities are calculated and uploaded?
-    pub fn rarity(self) -> bool {
-        self.contains(Self::RARITY)
+    fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::DEFAULT),
+            "full" => Some(Self::FULL),
+            "medal" | "medals" => Some(Self::MEDALS),
+            "rarity" | "rarities" => Some(Self::RARITY),
+            "ranking" => Some(Self::RANKING),
+            "badge" | "badges" => Some(Self::BADGES),
+            _ => None,
+        }
     }
 
-    /// Should user data be processed and uploaded?
-    pub fn ranking(self) -> bool {
-        self.contains(Self::RANKING)
+    /// Parse names separated by `|` to add or `-` to subtract them,
+    /// evaluated from left to right.
+    ///
+    /// Tasks without any flag, e.g. `full - full`, are invalid.
+    fn parse(s: &str, presets: &[Preset]) -> Result<Self> {
+        let s = s.to_ascii_lowercase();
+        let mut task = Self::empty();
+        let mut subtract = false;
+        let mut rest = s.as_str();
+
+        let invalid = || {
+            let names: Vec<_> = ["default", "full"]
+                .into_iter()
+                .chain(presets.iter().map(|preset| &*preset.name))
+                .collect();
+
+            eyre!(
+                "failed to parse task `{s}`; must be a list of the following, \
+                separated by `|` to add or `-` to subtract, that leaves at least one: \
+                medal, rarity, badge, ranking, or one of the presets {}",
+                names.join(", ")
+            )
+        };
+
+        loop {
+            let end = rest.find(['|', '-']).unwrap_or(rest.len());
+            let name = rest[..end].trim();
+
+            let next = Self::builtin(name)
+                .or_else(|| {
+                    presets
+                        .iter()
+                        .find(|preset| *preset.name == *name)
+                        .map(|preset| preset.task)
+                })
+                .ok_or_else(invalid)?;
+
+            if subtract {
+                task.remove(next);
+            } else {
+                task |= next;
+            }
+
+            let Some(op) = rest[end..].chars().next() else {
+                return if task == Self::empty() {
+                    Err(invalid())
+                } else {
+                    Ok(task)
+                };
+            };
+
+            subtract = op == '-';
+            rest = &rest[end + 1..];
+        }
     }
 }
 
@@ -65,6 +202,13 @@ impl Display for Task {
             return f.write_str("Full");
         }
 
+        // Only an exact match so that the name can be parsed back
+        if task != Self::DEFAULT {
+            if let Some(preset) = Self::presets().iter().find(|preset| preset.task == task) {
+                return f.write_str(&preset.name);
+            }
+        }
+
         if task.contains(Self::DEFAULT) {
             f.write_str("Default")?;
             found = true;
@@ -117,26 +261,7 @@ impl FromStr for Task {
     type Err = Report;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.to_ascii_lowercase();
-
-        s.split('|')
-            .map(str::trim)
-            .try_fold(Self::empty(), |res, next| match next {
-                "default" => Ok(res | Self::DEFAULT),
-                "full" => Ok(res | Self::FULL),
-                "medal" | "medals" => Ok(res | Self::MEDALS),
-                "rarity" | "rarities" => Ok(res | Self::RARITY),
-                "ranking" => Ok(res | Self::RANKING),
-                "badge" | "badges" => Ok(res | Self::BADGES),
-                _ => {
-                    let msg = format!(
-                        "failed to parse task `{s}`; must be a `|`-separated list of the following: \
-                        default, full, medal, rarity, badge, ranking"
-                    );
-
-                    Err(Report::msg(msg))
-                }
-            })
+        Self::parse(s, Self::presets())
     }
 }
 
@@ -178,3 +303,101 @@ impl Serialize for Task {
         s.collect_str(self)
     }
 }
+
+impl<'de> Deserialize<'de> for Task {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let s = <&str>::deserialize(d)?;
+
+        s.parse().map_err(DeError::custom)
+    }
+}
+
+/// (De)serialize a task through its flags instead of its name since names of
+/// presets may change between runs.
+///
+/// Names are still accepted when deserializing.
+pub mod bits {
+    use serde::{de::Error as DeError, Deserialize, Deserializer, Serializer};
+
+    use super::Task;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum TaskRepr {
+        Bits(u8),
+        Name(Task),
+    }
+
+    pub fn serialize<S: Serializer>(task: &Task, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u8(task.bits())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Task, D::Error> {
+        match TaskRepr::deserialize(d)? {
+            TaskRepr::Bits(bits) => Task::from_bits(bits)
+                .ok_or_else(|| DeError::custom(format!("invalid task flags {bits}"))),
+            TaskRepr::Name(task) => Ok(task),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_round_trip() {
+        #[derive(Deserialize, Serialize)]
+        struct Wrapper(#[serde(with = "bits")] Task);
+
+        let json = serde_json::to_string(&Wrapper(Task::DEFAULT | Task::BADGES)).unwrap();
+        assert_eq!(json, "11");
+
+        let Wrapper(task) = serde_json::from_str(&json).unwrap();
+        assert!(task == Task::DEFAULT | Task::BADGES);
+
+        let Wrapper(task) = serde_json::from_str(r#""Full""#).unwrap();
+        assert!(task == Task::FULL);
+
+        assert!(serde_json::from_str::<Wrapper>("0").is_err());
+        assert!(serde_json::from_str::<Wrapper>("16").is_err());
+    }
+
+    #[test]
+    fn parse_from_left_to_right() {
+        let task = Task::parse("full - rarity | rarity - badges", &[]).unwrap();
+        assert!(task == Task::DEFAULT | Task::RARITY);
+
+        let task = Task::parse("Medal | RANKING", &[]).unwrap();
+        assert!(task == Task::DEFAULT);
+    }
+
+    #[test]
+    fn reject_empty() {
+        for s in ["full - full", "medal - default", ""] {
+            let Err(err) = Task::parse(s, &[]) else {
+                panic!("`{s}` should be invalid");
+            };
+
+            assert!(err.to_string().contains("one of the presets default, full"));
+        }
+    }
+
+    #[test]
+    fn parse_presets() {
+        let presets = [Preset {
+            name: Box::from("nightly"),
+            task: Task::DEFAULT | Task::BADGES,
+        }];
+
+        let task = Task::parse("nightly - medal", &presets).unwrap();
+        assert!(task == Task::RANKING | Task::BADGES);
+
+        let Err(err) = Task::parse("weekly", &presets) else {
+            panic!("`weekly` should be invalid");
+        };
+
+        assert!(err.to_string().contains("presets default, full, nightly"));
+    }
+}
@@ -1,10 +1,10 @@
-use std::{collections::HashSet, ops::BitOr};
+use std::{collections::HashSet, ops::BitOr, path::PathBuf};
 
-use clap::{Parser, Subcommand};
+use clap::{error::ErrorKind, Args as ClapArgs, CommandFactory, Parser, Subcommand, ValueEnum};
 use eyre::Result;
 use self_update::Status;
 
-use crate::task::Task;
+use crate::{config, task::Task};
 
 use super::IntHasher;
 
@@ -15,11 +15,21 @@ pub struct Args {
     pub progress: bool,
     pub quiet: bool,
     pub debug: bool,
+    pub resume: bool,
+    pub concurrency: Option<usize>,
+    pub ratelimit: Option<u32>,
+    pub dry_run: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub watch_env: bool,
 }
 
 pub enum ArgsResult {
     Args(Args, Option<Task>),
     Update(Result<Status>),
+    Export(ExportArgs),
+    Migrate,
+    Status(StatusArgs),
 }
 
 impl Args {
@@ -31,18 +41,59 @@ impl Args {
             progress,
             quiet,
             debug,
+            resume,
+            concurrency,
+            ratelimit,
+            dry_run,
+            record,
+            replay,
+            watch_env,
             task,
+            file,
             command,
         } = ArgsCli::parse();
 
-        if let Some(ArgCommand::Update) = command {
+        // Updating does not depend on the config
+        if matches!(command, Some(ArgCommand::Update)) {
             return ArgsResult::Update(update());
         }
 
-        let task = task.into_iter().reduce(Task::bitor);
+        // Needs to happen before anything reads env variables
+        if let Some(path) = file.config {
+            if let Err(err) = config::file::init(path, file.profile) {
+                ArgsCli::command()
+                    .error(ErrorKind::ValueValidation, format!("{err:#}"))
+                    .exit()
+            }
+        }
+
+        // Tasks may refer to presets so they can only be parsed afterwards
+        if let Err(err) = config::init_presets() {
+            ArgsCli::command()
+                .error(ErrorKind::InvalidValue, format!("{err:#}"))
+                .exit()
+        }
+
+        match command {
+            Some(ArgCommand::Export(args)) => return ArgsResult::Export(args),
+            Some(ArgCommand::Migrate) => return ArgsResult::Migrate,
+            Some(ArgCommand::Status(args)) => return ArgsResult::Status(args),
+            Some(ArgCommand::Update) | None => {}
+        }
+
+        let task = task
+            .iter()
+            .map(|task| {
+                task.parse::<Task>().unwrap_or_else(|err| {
+                    ArgsCli::command()
+                        .error(ErrorKind::InvalidValue, format!("{err:#}"))
+                        .exit()
+                })
+            })
+            .reduce(Task::bitor);
 
         // Default delay when looping is 1 minute, otherwise 0
-        let delay = initial_delay.unwrap_or_else(|| task.is_none() as u64);
+        let delay = initial_delay.unwrap_or_else(|| (task.is_none() && !resume) as u64);
 
         let args = Args {
             delay,
@@ -51,6 +102,13 @@ impl Args {
             progress,
             quiet,
             debug,
+            resume,
+            concurrency,
+            ratelimit,
+            dry_run,
+            record,
+            replay,
+            watch_env,
         };
 
         ArgsResult::Args(args, task)
@@ -78,17 +136,122 @@ struct ArgsCli {
     #[arg(long, action)]
     /// Set this to process only one user
     debug: bool,
+    #[arg(long, action)]
+    /// Continue the most recent interrupted run from its checkpoint
+    resume: bool,
+    #[arg(short, long, value_name = "REQUESTS")]
+    /// Maximum amount of concurrent user requests [default: 4]
+    concurrency: Option<usize>,
+    #[arg(short, long, value_name = "REQUESTS")]
+    /// Maximum amount of osu!api requests per second [default: 10]
+    ratelimit: Option<u32>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        default_missing_value = "dry-run.ndjson"
+    )]
+    /// Write planned database changes into a file instead of the database
+    /// and only log webhook notifications
+    dry_run: Option<PathBuf>,
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    /// Save all osu!api responses and scraped pages into a cassette directory
+    record: Option<PathBuf>,
+    #[arg(long, value_name = "DIR")]
+    /// Serve osu!api responses and scraped pages from a recorded cassette
+    /// directory instead of requesting them
+    replay: Option<PathBuf>,
+    #[arg(long, action)]
+    /// Reload the config whenever the .env or config file changes, not
+    /// only on SIGHUP
+    watch_env: bool,
     #[arg(short, long)]
     /// Specific task to be run only once (repeatable)
-    task: Vec<Task>,
+    task: Vec<String>,
+    #[command(flatten)]
+    file: FileArgs,
     #[command(subcommand)]
     command: Option<ArgCommand>,
 }
 
+#[derive(ClapArgs)]
+pub struct FileArgs {
+    #[arg(long, global = true, value_name = "FILE")]
+    /// TOML file to read the config from; env variables take precedence
+    pub config: Option<PathBuf>,
+    #[arg(long, global = true, requires = "config", value_name = "NAME")]
+    /// Profile of the config file whose values override the others
+    pub profile: Option<String>,
+}
+
 #[derive(Subcommand)]
 enum ArgCommand {
     /// Just check for an update and install it
     Update,
+    /// Export stored data instead of running tasks
+    Export(ExportArgs),
+    /// Apply pending database schema migrations
+    Migrate,
+    /// Summarize recent runs and the schedule
+    Status(StatusArgs),
+}
+
+#[derive(ClapArgs)]
+pub struct StatusArgs {
+    #[arg(short = 'n', long, default_value_t = 10)]
+    /// Amount of most recent runs to show
+    pub runs: usize,
+    #[arg(short, long, default_value_t = 12, value_name = "HOURS")]
+    /// Time inbetween two tasks of the schedule
+    pub interval: u64,
+    #[arg(long, default_value_t = 30, value_name = "MINUTES")]
+    /// Consider unfinished runs without update for this long as stalled
+    pub stalled_after: u64,
+}
+
+#[derive(ClapArgs)]
+pub struct ExportArgs {
+    /// Data that should be exported
+    pub table: ExportTable,
+    #[arg(short, long, value_enum, default_value_t = ExportFormat::Csv)]
+    /// Output format
+    pub format: ExportFormat,
+    #[arg(long, value_delimiter = ',', value_name = "COLUMNS")]
+    /// Comma-separated list of database columns to export [default: all]
+    pub columns: Vec<String>,
+    #[arg(long, value_name = "CODE")]
+    /// Only export users of this country
+    pub country: Option<String>,
+    #[arg(long, value_name = "BOOL")]
+    /// Only export users that are (not) restricted
+    pub restricted: Option<bool>,
+    #[arg(short, long, value_name = "FILE")]
+    /// File to write into [default: stdout]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum ExportTable {
+    /// Rankings_Users
+    Rankings,
+    /// Medals_Data
+    Medals,
+    /// Rarity columns of Medals_Data
+    Rarities,
+    /// Badge_Name alongside Badges_Data
+    Badges,
+    /// Rankings_Script_History
+    History,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+    /// JSON array as documented in API.md
+    Api,
 }
 
 #[cfg(any(target_os = "windows", target_os = "linux"))]
@@ -140,4 +303,8 @@ Task values:
   - ranking: Process all users and upload them.
   - badges: Collect badges of all available users and upload them.
   - default: medals | ranking | badges
-  - full: medals | ranking | badges | rarity"#;
+  - full: medals | ranking | badges | rarity
+  - presets of the `PRESETS` env variable
+
+Tasks can be combined with `|` and subtracted with `-`,
+e.g. `full - rarity`."#;
@@ -0,0 +1,174 @@
+use std::time::{Duration, Instant};
+
+use crate::model::FailureKind;
+
+/// Amount of results after which the limit is reconsidered.
+const WINDOW_LEN: usize = 50;
+
+/// Average latency below which the limit may be increased.
+const LOW_LATENCY: Duration = Duration::from_millis(1000);
+
+/// Average latency above which the limit is decreased.
+const HIGH_LATENCY: Duration = Duration::from_millis(3000);
+
+/// Maximum ratio of errors in a window so that the limit may be increased.
+const MAX_ERROR_RATIO: f32 = 0.02;
+
+/// Controls how many requests should be in flight at the same time.
+///
+/// The limit starts out at the maximum. It is increased by one while latency
+/// is low and errors are rare, and halved as soon as requests are throttled
+/// by the API i.e. when receiving a 429, a 5xx, or a timeout. Since requests
+/// of a burst tend to be throttled together, the limit is halved at most once
+/// per window.
+pub struct AdaptiveConcurrency {
+    limit: usize,
+    max: usize,
+    window: Window,
+    rate: f32,
+}
+
+#[derive(Default)]
+struct Window {
+    start: Option<Instant>,
+    results: usize,
+    errors: usize,
+    latency: Duration,
+    /// Whether the limit was already decreased due to throttling
+    throttled: bool,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+
+        Self {
+            limit: max,
+            max,
+            window: Window::default(),
+            rate: 0.0,
+        }
+    }
+
+    /// The current amount of allowed concurrent requests.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// The amount of results per second observed in the last window.
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    /// Record the outcome of a request.
+    pub fn record(&mut self, latency: Duration, failure: Option<FailureKind>) {
+        let window = &mut self.window;
+        let start = *window.start.get_or_insert_with(Instant::now);
+
+        window.results += 1;
+        window.latency += latency;
+
+        match failure {
+            Some(FailureKind::Ratelimited | FailureKind::ServerError | FailureKind::Timeout) => {
+                window.errors += 1;
+
+                if !window.throttled {
+                    window.throttled = true;
+                    let limit = (self.limit / 2).max(1);
+
+                    if limit < self.limit {
+                        debug!("Request was throttled; decreasing concurrency to {limit}");
+                    }
+
+                    self.limit = limit;
+                }
+            }
+            Some(_) => window.errors += 1,
+            None => {}
+        }
+
+        if window.results < WINDOW_LEN {
+            return;
+        }
+
+        let elapsed = start.elapsed().as_secs_f32();
+
+        if elapsed > 0.0 {
+            self.rate = window.results as f32 / elapsed;
+        }
+
+        let avg_latency = window.latency / window.results as u32;
+        let error_ratio = window.errors as f32 / window.results as f32;
+
+        // The limit was already decreased if requests were throttled
+        if !window.throttled {
+            if avg_latency > HIGH_LATENCY {
+                self.limit = self.limit.saturating_sub(1).max(1);
+            } else if avg_latency < LOW_LATENCY && error_ratio <= MAX_ERROR_RATIO {
+                self.limit = (self.limit + 1).min(self.max);
+            }
+        }
+
+        self.window = Window::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn halve_once_per_window() {
+        let mut concurrency = AdaptiveConcurrency::new(16);
+
+        // A burst of throttled requests only halves the limit once
+        for _ in 0..10 {
+            concurrency.record(FAST, Some(FailureKind::Ratelimited));
+        }
+
+        assert_eq!(concurrency.limit(), 8);
+
+        // The throttled window does not increase the limit once it's full
+        for _ in 10..WINDOW_LEN {
+            concurrency.record(FAST, None);
+        }
+
+        assert_eq!(concurrency.limit(), 8);
+
+        // The next window may halve it again
+        concurrency.record(FAST, Some(FailureKind::Timeout));
+        concurrency.record(FAST, Some(FailureKind::ServerError));
+        assert_eq!(concurrency.limit(), 4);
+    }
+
+    #[test]
+    fn increase_up_to_max() {
+        let mut concurrency = AdaptiveConcurrency::new(4);
+        concurrency.record(FAST, Some(FailureKind::Ratelimited));
+
+        for _ in 1..WINDOW_LEN {
+            concurrency.record(FAST, None);
+        }
+
+        assert_eq!(concurrency.limit(), 2);
+
+        for _ in 0..3 * WINDOW_LEN {
+            concurrency.record(FAST, None);
+        }
+
+        assert_eq!(concurrency.limit(), 4);
+    }
+
+    #[test]
+    fn never_below_one() {
+        let mut concurrency = AdaptiveConcurrency::new(1);
+
+        for _ in 0..2 * WINDOW_LEN {
+            concurrency.record(FAST, Some(FailureKind::Ratelimited));
+        }
+
+        assert_eq!(concurrency.limit(), 1);
+    }
+}
@@ -0,0 +1,10 @@
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// RFC 3339 without fractional seconds
+pub fn format_datetime(datetime: OffsetDateTime) -> String {
+    datetime
+        .replace_nanosecond(0)
+        .unwrap_or(datetime)
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| datetime.unix_timestamp().to_string())
+}
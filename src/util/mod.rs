@@ -1,9 +1,17 @@
 pub use self::{
-    args::{Args, ArgsResult},
+    args::{Args, ArgsResult, ExportArgs, ExportFormat, ExportTable, StatusArgs},
+    concurrency::AdaptiveConcurrency,
+    datetime::format_datetime,
     eta::{Eta, TimeEstimate},
     hasher::IntHasher,
+    reload::Reload,
+    shutdown::Shutdown,
 };
 
 mod args;
+mod concurrency;
+mod datetime;
 mod eta;
 mod hasher;
+mod reload;
+mod shutdown;
@@ -0,0 +1,175 @@
+use std::{
+    fs,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use eyre::{Context as _, Result};
+use tokio::{sync::watch, time::interval};
+
+use crate::{
+    config::{self, file, Config, Reloadable},
+    schedule::Lane,
+};
+
+/// Handle to check whether a reloaded config is waiting to be applied and
+/// whether a lane changed through an applied one.
+#[derive(Clone)]
+pub struct Reload {
+    tx: Arc<watch::Sender<Option<Arc<Reloadable>>>>,
+    applied: Arc<watch::Sender<Arc<Reloadable>>>,
+}
+
+impl Reload {
+    /// How often the files are checked for changes when watching them
+    const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// Start listening for SIGHUP and, if `watch` is set, for changes of the
+    /// `.env` file and the config file.
+    ///
+    /// The files are then re-read and, if they're valid, their config is held
+    /// until it's taken and applied.
+    pub fn listen(watch: bool) -> Self {
+        let (tx, _) = watch::channel(None);
+        let tx = Arc::new(tx);
+        let (applied, _) = watch::channel(Config::get().reloadable());
+
+        #[cfg(unix)]
+        tokio::spawn(listen_sighup(Arc::clone(&tx)));
+
+        if watch {
+            tokio::spawn(watch_env(Arc::clone(&tx)));
+        }
+
+        Self {
+            tx,
+            applied: Arc::new(applied),
+        }
+    }
+
+    /// Wait until a reloaded config is waiting to be applied.
+    pub async fn pending(&self) {
+        let mut rx = self.tx.subscribe();
+
+        // The sender is held by `self` so it can't be dropped
+        let _ = rx.wait_for(Option::is_some).await;
+    }
+
+    /// Take the reloaded config if there is one.
+    pub fn take(&self) -> Option<Arc<Reloadable>> {
+        self.tx.send_replace(None)
+    }
+
+    /// Apply a reloaded config; lanes that changed through it stop at their
+    /// next task boundary.
+    pub fn apply(&self, reloadable: Arc<Reloadable>) {
+        Config::get().apply(Arc::clone(&reloadable));
+        self.applied.send_replace(reloadable);
+    }
+
+    /// The config that was applied last.
+    pub fn applied(&self) -> Arc<Reloadable> {
+        Arc::clone(&self.applied.borrow())
+    }
+
+    /// Whether the lane at `idx` was changed or removed by an applied config.
+    pub fn is_outdated(&self, idx: usize, lane: &Lane) -> bool {
+        is_outdated(&self.applied.borrow(), idx, lane)
+    }
+
+    /// Wait until the lane at `idx` was changed or removed by an applied config.
+    pub async fn outdated(&self, idx: usize, lane: &Lane) {
+        let mut rx = self.applied.subscribe();
+
+        // The sender is held by `self` so it can't be dropped
+        let _ = rx
+            .wait_for(|reloadable| is_outdated(reloadable, idx, lane))
+            .await;
+    }
+}
+
+fn is_outdated(reloadable: &Reloadable, idx: usize, lane: &Lane) -> bool {
+    reloadable
+        .schedule
+        .lanes()
+        .nth(idx)
+        .is_none_or(|applied| applied.to_string() != lane.to_string())
+}
+
+#[cfg(unix)]
+async fn listen_sighup(tx: Arc<watch::Sender<Option<Arc<Reloadable>>>>) {
+    use tokio::signal::unix::{self, SignalKind};
+
+    let mut sighup = match unix::signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(err) => return error!(?err, "Failed to listen for SIGHUP"),
+    };
+
+    while sighup.recv().await.is_some() {
+        info!("Received SIGHUP; reloading config");
+        reload(&tx);
+    }
+}
+
+async fn watch_env(tx: Arc<watch::Sender<Option<Arc<Reloadable>>>>) {
+    let paths: Vec<&Path> = [Config::get().env_path.as_deref(), file::path()]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let mut last_modified: Vec<_> = paths.iter().map(|path| modified(path).ok()).collect();
+    let mut interval = interval(Reload::POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let mut changed = None;
+
+        for (path, last_modified) in paths.iter().zip(last_modified.iter_mut()) {
+            let modified = match modified(path) {
+                Ok(modified) => modified,
+                Err(err) => {
+                    warn!(?err, "Failed to check a config file for changes");
+
+                    continue;
+                }
+            };
+
+            if last_modified.replace(modified) != Some(modified) {
+                changed = Some(path);
+            }
+        }
+
+        if let Some(path) = changed {
+            info!("Config file `{}` changed; reloading config", path.display());
+            reload(&tx);
+        }
+    }
+}
+
+fn reload(tx: &watch::Sender<Option<Arc<Reloadable>>>) {
+    // Nothing is modified until the config turned out to be valid
+    let res = config::read_vars().and_then(|vars| {
+        if config::presets_changed(&vars) {
+            warn!("`PRESETS` changed but presets can't be changed without a restart");
+        }
+
+        Reloadable::from_vars(&vars)
+    });
+
+    match res {
+        Ok(reloadable) => {
+            info!(schedule = %reloadable.schedule, "Reloaded config");
+
+            tx.send_replace(Some(Arc::new(reloadable)));
+        }
+        Err(err) => error!(?err, "Invalid config; keeping the previous one"),
+    }
+}
+
+fn modified(path: &Path) -> Result<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("failed to read metadata of `{}`", path.display()))
+}
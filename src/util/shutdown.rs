@@ -0,0 +1,78 @@
+use std::process;
+
+use eyre::Report;
+use tokio::{signal, sync::watch};
+
+/// Handle to check whether a graceful shutdown was requested.
+#[derive(Clone)]
+pub struct Shutdown {
+    rx: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Start listening for shutdown signals i.e. Ctrl+C or SIGTERM.
+    ///
+    /// The first signal requests a graceful shutdown, the second signal
+    /// exits the process immediately.
+    pub fn listen() -> Self {
+        let (tx, rx) = watch::channel(false);
+
+        tokio::spawn(async move {
+            match wait_for_signal().await {
+                Ok(signal) => info!(
+                    "Received {signal}; finishing up gracefully. \
+                    Send another signal to force the shutdown."
+                ),
+                Err(err) => return error!(?err, "Failed to await shutdown signal"),
+            }
+
+            let _ = tx.send(true);
+
+            match wait_for_signal().await {
+                Ok(signal) => warn!("Received {signal} again; forcing the shutdown"),
+                Err(err) => return error!(?err, "Failed to await shutdown signal"),
+            }
+
+            process::exit(1);
+        });
+
+        Self { rx }
+    }
+
+    /// Whether a graceful shutdown was requested.
+    pub fn is_requested(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Wait until a graceful shutdown is requested.
+    pub async fn requested(&self) {
+        let mut rx = self.rx.clone();
+
+        // If the sender is dropped, no shutdown will ever be requested
+        if rx.wait_for(|requested| *requested).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+async fn wait_for_signal() -> Result<&'static str, Report> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{self, SignalKind};
+
+        let mut sigterm = unix::signal(SignalKind::terminate())?;
+
+        tokio::select! {
+            res = signal::ctrl_c() => res.map(|_| "Ctrl+C").map_err(Report::new),
+            _ = sigterm.recv() => Ok("SIGTERM"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        signal::ctrl_c()
+            .await
+            .map(|_| "Ctrl+C")
+            .map_err(Report::new)
+    }
+}